@@ -0,0 +1,24 @@
+use std::path::PathBuf;
+use tauri::State;
+use crate::AppState;
+use crate::services::audit_log::{self, AuditEntry, AuditExportFormat, AuditFilter};
+
+#[tauri::command]
+pub async fn get_audit_log(filter: Option<AuditFilter>, state: State<'_, AppState>) -> Result<Vec<AuditEntry>, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    audit_log::query(&db_path, &filter.unwrap_or_default())
+}
+
+/// Writes the entries matching `filter` to `path`; returns how many.
+#[tauri::command]
+pub async fn export_audit_log(
+    filter: Option<AuditFilter>,
+    format: AuditExportFormat,
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let count = audit_log::export(&db_path, &filter.unwrap_or_default(), format, &PathBuf::from(&path))?;
+    eprintln!("[AUDIT] Exported {} entries to {}", count, path);
+    Ok(count)
+}
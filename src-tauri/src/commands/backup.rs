@@ -1,12 +1,51 @@
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
-use chrono::{DateTime, Utc};
-use zip::write::FileOptions;
-use zip::{CompressionMethod, ZipWriter};
+use chrono::{DateTime, Duration, Months, Utc};
+use rusqlite::Connection;
+use zip::ZipWriter;
 use std::io::{Read, Write};
-use tauri::State;
+use tauri::{AppHandle, Emitter, Manager, State};
 use crate::AppState;
+use crate::commands::discord;
+use crate::services::audit_log;
+use crate::services::server_service::ServerService;
+use crate::services::backup_store::{hash_file, BackupStore, GcReport, Manifest, ManifestEntry, MANIFEST_EXTENSION};
+use crate::services::destinations::{self, BackupDestination, DestinationKind, RemoteBackup};
+use crate::utils::archive::{self, file_mode, ArchiveError, ArchiveFormat, ExtractOptions};
+use crate::utils::crypto::{self, CryptoError, KdfParams};
+use sha2::{Digest, Sha256};
+use tempfile::NamedTempFile;
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+// Hash manifest stored inside zip backups; skipped on restore
+const ZIP_MANIFEST_ENTRY: &str = ".backup-manifest.json";
+
+// Encrypted zip backups are stored as `<id>.enc`
+const ENCRYPTED_EXTENSION: &str = "enc";
+
+const PASSPHRASE_REQUIRED: &str = "Passphrase required for encrypted backup";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupFormat {
+    /// A self-contained zip of the whole server directory
+    #[default]
+    Zip,
+    /// A manifest pointing at deduplicated blobs in the backup store
+    Incremental,
+}
+
+/// Portable archive formats for `export_backup`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    #[serde(rename = "tar.gz")]
+    TarGz,
+    #[serde(rename = "zip")]
+    Zip,
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Backup {
@@ -14,6 +53,178 @@ pub struct Backup {
     pub name: String,
     pub created_at: String,
     pub size: u64,
+    pub profile: String,
+    pub pinned: bool,
+    pub format: BackupFormat,
+    pub encrypted: bool,
+    pub verification: Option<VerifyReport>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VerifyReport {
+    pub backup_id: String,
+    pub ok: bool,
+    pub checked: usize,
+    pub missing: Vec<String>,
+    pub corrupt: Vec<String>,
+    pub verified_at: String,
+}
+
+/// Grandfather-father-son retention rules. A `None` rule is disabled; when
+/// every rule is disabled nothing is pruned.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    pub keep_last: Option<u32>,
+    pub keep_daily: Option<u32>,
+    pub keep_weekly: Option<u32>,
+    pub keep_monthly: Option<u32>,
+    pub max_total_size: Option<u64>,
+}
+
+impl RetentionPolicy {
+    fn is_empty(&self) -> bool {
+        self.keep_last.is_none()
+            && self.keep_daily.is_none()
+            && self.keep_weekly.is_none()
+            && self.keep_monthly.is_none()
+            && self.max_total_size.is_none()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct VerificationSettings {
+    pub after_create: bool,
+    pub notify_discord: bool,
+}
+
+/// Passphrase encryption for zip backups. The passphrase itself is never
+/// stored; it is supplied per call or unlocked for the session.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct EncryptionSettings {
+    pub enabled: bool,
+    pub kdf: KdfParams,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HookStep {
+    pub command: String,
+    /// Log line substring that confirms the command finished
+    #[serde(default)]
+    pub wait_for: Option<String>,
+}
+
+/// Console commands sent to a running server around a backup, e.g. to flush
+/// the world to disk and pause autosave while files are copied.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ConsistencyHooks {
+    pub enabled: bool,
+    pub pre_backup: Vec<HookStep>,
+    pub post_backup: Vec<HookStep>,
+    pub timeout_secs: u64,
+    /// Abort the backup when a pre-backup step is not confirmed in time
+    pub require_confirmation: bool,
+}
+
+impl Default for ConsistencyHooks {
+    fn default() -> Self {
+        ConsistencyHooks {
+            enabled: false,
+            pre_backup: vec![
+                HookStep { command: "save-all".to_string(), wait_for: Some("Saved".to_string()) },
+                HookStep { command: "save-off".to_string(), wait_for: None },
+            ],
+            post_backup: vec![
+                HookStep { command: "save-on".to_string(), wait_for: None },
+            ],
+            timeout_secs: 30,
+            require_confirmation: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct BackupProfile {
+    pub format: BackupFormat,
+    pub retention: RetentionPolicy,
+    pub verification: VerificationSettings,
+    pub encryption: EncryptionSettings,
+    pub consistency: ConsistencyHooks,
+}
+
+/// An off-site location new backups are copied to, with its own retention.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DestinationConfig {
+    pub id: String,
+    pub name: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub kind: DestinationKind,
+    /// Profiles uploaded automatically after creation; empty means all
+    #[serde(default)]
+    pub profiles: Vec<String>,
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl DestinationConfig {
+    fn accepts(&self, profile: &str) -> bool {
+        self.profiles.is_empty() || self.profiles.iter().any(|p| p == profile)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct BackupConfig {
+    pub profiles: HashMap<String, BackupProfile>,
+    pub prune_interval_minutes: u64,
+    /// 0 disables scheduled verification
+    pub verify_interval_minutes: u64,
+    pub destinations: Vec<DestinationConfig>,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        let mut profiles = HashMap::new();
+        profiles.insert(DEFAULT_PROFILE.to_string(), BackupProfile::default());
+        BackupConfig {
+            profiles,
+            prune_interval_minutes: 60,
+            verify_interval_minutes: 0,
+            destinations: Vec::new(),
+        }
+    }
+}
+
+impl BackupConfig {
+    pub fn profile(&self, name: &str) -> BackupProfile {
+        self.profiles.get(name).cloned().unwrap_or_default()
+    }
+
+    fn destination(&self, id: &str) -> Result<&DestinationConfig, String> {
+        self.destinations
+            .iter()
+            .find(|d| d.id == id)
+            .ok_or_else(|| format!("Unknown backup destination: {}", id))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PruneReport {
+    pub profile: String,
+    pub deleted: Vec<Backup>,
+    pub kept: usize,
+    pub freed_bytes: u64,
+    pub ran_at: String,
 }
 
 fn get_backups_dir() -> Result<PathBuf, String> {
@@ -31,8 +242,102 @@ fn get_backups_dir() -> Result<PathBuf, String> {
     Ok(backups_dir)
 }
 
+fn get_backup_store() -> Result<BackupStore, String> {
+    BackupStore::open(get_backups_dir()?.join("store")).map_err(|e| e.to_string())
+}
+
+fn get_temp_dir() -> Result<PathBuf, String> {
+    let temp_dir = get_backups_dir()?.join(".tmp");
+    fs::create_dir_all(&temp_dir).map_err(|e| e.to_string())?;
+    Ok(temp_dir)
+}
+
+// Deletes a staging or decrypted archive when it goes out of scope
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+struct BackupLocation {
+    path: PathBuf,
+    format: BackupFormat,
+    encrypted: bool,
+}
+
+// Backup ids come from clients and are joined onto the backups directory,
+// so anything but a single plain file name is refused
+fn is_plain_backup_id(backup_id: &str) -> bool {
+    let mut components = Path::new(backup_id).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(std::path::Component::Normal(_)), None)
+    ) && !backup_id.contains(['/', '\\'])
+}
+
+// Finds the archive or manifest for `backup_id`
+fn locate_backup(backup_id: &str) -> Result<BackupLocation, String> {
+    if !is_plain_backup_id(backup_id) {
+        return Err(format!("Invalid backup id: {}", backup_id));
+    }
+    let backups_dir = get_backups_dir()?;
+
+    let candidates = [
+        ("zip", BackupFormat::Zip, false),
+        (ENCRYPTED_EXTENSION, BackupFormat::Zip, true),
+        (MANIFEST_EXTENSION, BackupFormat::Incremental, false),
+    ];
+    for (extension, format, encrypted) in candidates {
+        let path = backups_dir.join(format!("{}.{}", backup_id, extension));
+        if path.exists() {
+            return Ok(BackupLocation { path, format, encrypted });
+        }
+    }
+
+    Err("Backup file not found".to_string())
+}
+
+// An explicit passphrase wins over one unlocked for the profile
+fn resolve_passphrase(state: &AppState, profile: &str, passphrase: Option<String>) -> Option<String> {
+    passphrase.or_else(|| state.backup_passphrases.lock().unwrap().get(profile).cloned())
+}
+
+// Each call gets its own owner-only file, so a verify and a restore of the
+// same backup never share a plaintext copy
+fn decrypt_to_temp(location: &BackupLocation, backup_id: &str, passphrase: &str) -> Result<NamedTempFile, CryptoError> {
+    let temp_dir = get_temp_dir().map_err(|e| CryptoError::Io(std::io::Error::other(e)))?;
+    let temp = tempfile::Builder::new()
+        .prefix(&format!("{}.", backup_id))
+        .suffix(".zip")
+        .tempfile_in(temp_dir)?;
+    let reader = std::io::BufReader::new(fs::File::open(&location.path)?);
+    crypto::decrypt(reader, std::io::BufWriter::new(temp.as_file()), passphrase)?;
+    Ok(temp)
+}
+
+struct OpenedZip {
+    archive: zip::ZipArchive<fs::File>,
+    // Dropped after `archive` so the decrypted copy is closed before removal
+    _temp: Option<NamedTempFile>,
+}
+
+fn open_zip(location: &BackupLocation, backup_id: &str, passphrase: Option<&str>) -> Result<OpenedZip, String> {
+    let (path, temp) = if location.encrypted {
+        let passphrase = passphrase.ok_or(PASSPHRASE_REQUIRED)?;
+        let temp = decrypt_to_temp(location, backup_id, passphrase).map_err(|e| e.to_string())?;
+        (temp.path().to_path_buf(), Some(temp))
+    } else {
+        (location.path.clone(), None)
+    };
+
+    let file = fs::File::open(&path).map_err(|e| e.to_string())?;
+    let archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+    Ok(OpenedZip { archive, _temp: temp })
+}
+
 fn get_server_path(state: &State<'_, AppState>) -> Result<String, String> {
-    use rusqlite::Connection;
     let db_path = state.db_path.lock().unwrap();
     let conn = Connection::open(db_path.as_str()).map_err(|e| e.to_string())?;
     
@@ -45,164 +350,1848 @@ fn get_server_path(state: &State<'_, AppState>) -> Result<String, String> {
     path.map_err(|_| "Server path not configured".to_string())
 }
 
-fn zip_directory(source: &Path, output: &Path) -> Result<(), String> {
-    let file = fs::File::create(output).map_err(|e| e.to_string())?;
-    let mut zip = ZipWriter::new(file);
-    let options = FileOptions::default()
-        .compression_method(CompressionMethod::Deflated)
-        .unix_permissions(0o755);
-
-    let walkdir = walkdir::WalkDir::new(source);
-    let it = walkdir.into_iter().filter_map(|e| e.ok());
+pub fn load_backup_config(db_path: &str) -> Result<BackupConfig, String> {
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
 
-    for entry in it {
-        let path = entry.path();
-        let name = path.strip_prefix(source).unwrap();
+    let config_str: Result<String, _> = conn.query_row(
+        "SELECT value FROM config WHERE key = 'backup_config'",
+        [],
+        |row| row.get(0)
+    );
 
-        if path.is_file() {
-            zip.start_file(name.to_string_lossy().to_string(), options).map_err(|e| e.to_string())?;
-            let mut f = fs::File::open(path).map_err(|e| e.to_string())?;
-            let mut buffer = Vec::new();
-            f.read_to_end(&mut buffer).map_err(|e| e.to_string())?;
-            zip.write_all(&buffer).map_err(|e| e.to_string())?;
-        } else if !name.as_os_str().is_empty() {
-            zip.add_directory(name.to_string_lossy().to_string(), options).map_err(|e| e.to_string())?;
-        }
+    match config_str {
+        Ok(json_str) => serde_json::from_str(&json_str).map_err(|e| e.to_string()),
+        Err(_) => Ok(BackupConfig::default()),
     }
+}
 
-    zip.finish().map_err(|e| e.to_string())?;
+// Per-backup metadata kept in the `backups` table, keyed by backup id
+struct IndexEntry {
+    profile: String,
+    pinned: bool,
+    verification: Option<VerifyReport>,
+}
+
+fn load_backup_index(db_path: &str) -> Result<HashMap<String, IndexEntry>, String> {
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT id, profile, pinned, verification FROM backups")
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            let verification: Option<String> = row.get(3)?;
+            Ok((row.get::<_, String>(0)?, IndexEntry {
+                profile: row.get(1)?,
+                pinned: row.get(2)?,
+                verification: verification.and_then(|v| serde_json::from_str(&v).ok()),
+            }))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<HashMap<_, _>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(rows)
+}
+
+fn register_backup(db_path: &str, backup: &Backup) -> Result<(), String> {
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO backups (id, profile, pinned, created_at) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(id) DO UPDATE SET profile = excluded.profile, pinned = excluded.pinned",
+        rusqlite::params![&backup.id, &backup.profile, backup.pinned, &backup.created_at],
+    ).map_err(|e| e.to_string())?;
     Ok(())
 }
 
-#[tauri::command]
-pub async fn create_backup(name: Option<String>, state: State<'_, AppState>) -> Result<Backup, String> {
-    let server_path = get_server_path(&state)?;
-    let server_dir = PathBuf::from(&server_path);
-    
-    if !server_dir.exists() {
-        return Err("Server directory does not exist".to_string());
-    }
-    
-    let backups_dir = get_backups_dir()?;
-    let timestamp = Utc::now();
-    let backup_name = name.unwrap_or_else(|| format!("backup_{}", timestamp.format("%Y%m%d_%H%M%S")));
-    let backup_id = format!("{}_{}", timestamp.timestamp(), backup_name);
-    let backup_file = backups_dir.join(format!("{}.zip", backup_id));
-    
-    // Create zip backup
-    zip_directory(&server_dir, &backup_file)?;
-    
-    let size = fs::metadata(&backup_file).map_err(|e| e.to_string())?.len();
-    
-    Ok(Backup {
-        id: backup_id,
-        name: backup_name,
-        created_at: timestamp.to_rfc3339(),
-        size,
-    })
+fn record_verification(db_path: &str, backup: &Backup, report: &VerifyReport) -> Result<(), String> {
+    register_backup(db_path, backup)?;
+    let report_str = serde_json::to_string(report).map_err(|e| e.to_string())?;
+
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE backups SET verification = ?1 WHERE id = ?2",
+        rusqlite::params![report_str, &backup.id],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
 }
 
-#[tauri::command]
-pub async fn list_backups() -> Result<Vec<Backup>, String> {
+// Backup ids are `<unix timestamp>_<name>`; returns the creation time and name
+fn parse_backup_id(backup_id: &str) -> (String, String) {
+    let parts: Vec<&str> = backup_id.split('_').collect();
+    let timestamp = parts
+        .first()
+        .and_then(|ts| ts.parse::<i64>().ok())
+        .and_then(|ts| DateTime::from_timestamp(ts, 0))
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_else(|| Utc::now().to_rfc3339());
+
+    (timestamp, parts[1..].join("_"))
+}
+
+// Scans the backups directory and joins each archive with its index entry
+pub fn scan_backups(db_path: &str) -> Result<Vec<Backup>, String> {
     let backups_dir = get_backups_dir()?;
+    let index = load_backup_index(db_path)?;
     let mut backups = Vec::new();
-    
+
     for entry in fs::read_dir(backups_dir).map_err(|e| e.to_string())? {
         let entry = entry.map_err(|e| e.to_string())?;
         let path = entry.path();
-        
-        if path.extension().and_then(|s| s.to_str()) == Some("zip") {
-            let filename = path.file_stem().unwrap().to_string_lossy().to_string();
-            let metadata = fs::metadata(&path).map_err(|e| e.to_string())?;
-            
-            // Parse timestamp from filename
-            let parts: Vec<&str> = filename.split('_').collect();
-            let timestamp = if let Some(ts) = parts.first() {
-                if let Ok(ts_num) = ts.parse::<i64>() {
-                    DateTime::from_timestamp(ts_num, 0)
-                        .map(|dt| dt.to_rfc3339())
-                        .unwrap_or_else(|| Utc::now().to_rfc3339())
-                } else {
-                    Utc::now().to_rfc3339()
+
+        let (format, encrypted) = match path.extension().and_then(|s| s.to_str()) {
+            Some("zip") => (BackupFormat::Zip, false),
+            Some(ENCRYPTED_EXTENSION) => (BackupFormat::Zip, true),
+            Some(MANIFEST_EXTENSION) => (BackupFormat::Incremental, false),
+            _ => continue,
+        };
+
+        let filename = path.file_stem().unwrap().to_string_lossy().to_string();
+        // Incremental backups report the size of the tree they restore
+        let size = match format {
+            BackupFormat::Zip => fs::metadata(&path).map_err(|e| e.to_string())?.len(),
+            BackupFormat::Incremental => match Manifest::load(&path) {
+                Ok(manifest) => manifest.total_size(),
+                Err(e) => {
+                    eprintln!("[BACKUP] Skipping unreadable manifest {}: {}", path.display(), e);
+                    continue;
                 }
-            } else {
-                Utc::now().to_rfc3339()
-            };
-            
-            let name = parts[1..].join("_");
-            
-            backups.push(Backup {
-                id: filename,
-                name,
-                created_at: timestamp,
-                size: metadata.len(),
-            });
-        }
+            },
+        };
+
+        let (timestamp, name) = parse_backup_id(&filename);
+        let (profile, pinned, verification) = match index.get(&filename) {
+            Some(entry) => (entry.profile.clone(), entry.pinned, entry.verification.clone()),
+            None => (DEFAULT_PROFILE.to_string(), false, None),
+        };
+
+        backups.push(Backup {
+            id: filename,
+            name,
+            created_at: timestamp,
+            size,
+            profile,
+            pinned,
+            format,
+            encrypted,
+            verification,
+        });
     }
-    
+
     // Sort by creation date (newest first)
     backups.sort_by(|a, b| b.created_at.cmp(&a.created_at));
-    
+
     Ok(backups)
 }
 
-#[tauri::command]
-pub async fn restore_backup(backup_id: String, state: State<'_, AppState>) -> Result<bool, String> {
-    let server_path = get_server_path(&state)?;
-    let server_dir = PathBuf::from(&server_path);
-    let backups_dir = get_backups_dir()?;
-    let backup_file = backups_dir.join(format!("{}.zip", backup_id));
-    
-    if !backup_file.exists() {
-        return Err("Backup file not found".to_string());
+fn remove_backup(db_path: &str, backup_id: &str) -> Result<(), String> {
+    let location = locate_backup(backup_id)?;
+    fs::remove_file(&location.path).map_err(|e| e.to_string())?;
+
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM backups WHERE id = ?1", rusqlite::params![backup_id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Keeps the newest backup of each period (day, week, month) that starts after `cutoff`
+fn keep_per_period<F>(
+    candidates: &[(&Backup, DateTime<Utc>)],
+    cutoff: DateTime<Utc>,
+    period_key: F,
+    keep: &mut HashSet<String>,
+) where
+    F: Fn(&DateTime<Utc>) -> String,
+{
+    let mut seen = HashSet::new();
+    for (backup, created) in candidates {
+        if *created >= cutoff && seen.insert(period_key(created)) {
+            keep.insert(backup.id.clone());
+        }
     }
-    
-    // Delete existing server directory
-    if server_dir.exists() {
-        fs::remove_dir_all(&server_dir).map_err(|e| e.to_string())?;
+}
+
+/// Returns the backups of `profile` that `policy` no longer retains; backups of
+/// other profiles are ignored. `backups` must be sorted newest first. Pinned
+/// backups are never returned, but they do count towards `max_total_size`.
+fn select_expired(backups: &[Backup], profile: &str, policy: &RetentionPolicy, now: DateTime<Utc>) -> Vec<Backup> {
+    if policy.is_empty() {
+        return Vec::new();
     }
-    fs::create_dir_all(&server_dir).map_err(|e| e.to_string())?;
-    
-    // Extract zip
-    let file = fs::File::open(&backup_file).map_err(|e| e.to_string())?;
-    let mut archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
-    
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i).map_err(|e| e.to_string())?;
-        let outpath = server_dir.join(file.name());
-        
-        if file.is_dir() {
-            fs::create_dir_all(&outpath).map_err(|e| e.to_string())?;
-        } else {
-            if let Some(p) = outpath.parent() {
-                fs::create_dir_all(p).map_err(|e| e.to_string())?;
+
+    let backups: Vec<&Backup> = backups.iter().filter(|b| b.profile == profile).collect();
+    let candidates: Vec<(&Backup, DateTime<Utc>)> = backups
+        .iter()
+        .copied()
+        .filter(|b| !b.pinned)
+        .map(|b| {
+            let created = DateTime::parse_from_rfc3339(&b.created_at)
+                .map(|dt| dt.with_timezone(&Utc))
+                .unwrap_or(now);
+            (b, created)
+        })
+        .collect();
+
+    let has_count_rules = policy.keep_last.is_some()
+        || policy.keep_daily.is_some()
+        || policy.keep_weekly.is_some()
+        || policy.keep_monthly.is_some();
+
+    let mut keep: HashSet<String> = if has_count_rules {
+        HashSet::new()
+    } else {
+        candidates.iter().map(|(b, _)| b.id.clone()).collect()
+    };
+
+    if let Some(n) = policy.keep_last {
+        for (backup, _) in candidates.iter().take(n as usize) {
+            keep.insert(backup.id.clone());
+        }
+    }
+    if let Some(days) = policy.keep_daily {
+        let cutoff = now - Duration::days(days as i64);
+        keep_per_period(&candidates, cutoff, |t| t.format("%Y-%m-%d").to_string(), &mut keep);
+    }
+    if let Some(weeks) = policy.keep_weekly {
+        let cutoff = now - Duration::weeks(weeks as i64);
+        keep_per_period(&candidates, cutoff, |t| t.format("%G-W%V").to_string(), &mut keep);
+    }
+    if let Some(months) = policy.keep_monthly {
+        let cutoff = now.checked_sub_months(Months::new(months)).unwrap_or(now);
+        keep_per_period(&candidates, cutoff, |t| t.format("%Y-%m").to_string(), &mut keep);
+    }
+
+    if let Some(max_size) = policy.max_total_size {
+        let mut total: u64 = backups
+            .iter()
+            .filter(|b| b.pinned || keep.contains(&b.id))
+            .map(|b| b.size)
+            .sum();
+
+        // Drop the oldest retained backups first, but never the newest one
+        for (backup, _) in candidates.iter().skip(1).rev() {
+            if total <= max_size {
+                break;
+            }
+            if keep.remove(&backup.id) {
+                total = total.saturating_sub(backup.size);
             }
-            let mut outfile = fs::File::create(&outpath).map_err(|e| e.to_string())?;
-            std::io::copy(&mut file, &mut outfile).map_err(|e| e.to_string())?;
         }
-        
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            if let Some(mode) = file.unix_mode() {
-                fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))
-                    .map_err(|e| e.to_string())?;
+    }
+
+    candidates
+        .into_iter()
+        .filter(|(b, _)| !keep.contains(&b.id))
+        .map(|(b, _)| b.clone())
+        .collect()
+}
+
+pub fn prune_profile(db_path: &str, profile: &str) -> Result<PruneReport, String> {
+    let config = load_backup_config(db_path)?;
+    let policy = config.profile(profile).retention;
+
+    let backups = scan_backups(db_path)?;
+    let expired = select_expired(&backups, profile, &policy, Utc::now());
+    let mut deleted = Vec::new();
+    let mut freed_bytes = 0;
+
+    for backup in expired {
+        match remove_backup(db_path, &backup.id) {
+            Ok(()) => {
+                eprintln!("[BACKUP] Pruned {} (profile: {})", backup.id, profile);
+                freed_bytes += backup.size;
+                deleted.push(backup);
             }
+            Err(e) => eprintln!("[BACKUP] Failed to prune {}: {}", backup.id, e),
         }
     }
     
-    Ok(true)
+    if deleted.iter().any(|b| b.format == BackupFormat::Incremental) {
+        match collect_garbage() {
+            Ok(gc) => freed_bytes += gc.freed_bytes,
+            Err(e) => eprintln!("[BACKUP] Store garbage collection failed: {}", e),
+        }
+    }
+
+    Ok(PruneReport {
+        profile: profile.to_string(),
+        kept: backups.iter().filter(|b| b.profile == profile).count() - deleted.len(),
+        deleted,
+        freed_bytes,
+        ran_at: Utc::now().to_rfc3339(),
+    })
 }
 
-#[tauri::command]
-pub async fn delete_backup(backup_id: String) -> Result<bool, String> {
-    let backups_dir = get_backups_dir()?;
-    let backup_file = backups_dir.join(format!("{}.zip", backup_id));
-    
-    if !backup_file.exists() {
-        return Err("Backup file not found".to_string());
+// Removes store blobs that no remaining incremental backup references
+fn collect_garbage() -> Result<GcReport, String> {
+    let store = get_backup_store()?;
+    let report = store.gc(&get_backups_dir()?).map_err(|e| e.to_string())?;
+    eprintln!("[BACKUP] Store GC removed {} blobs ({} bytes)", report.removed_blobs, report.freed_bytes);
+    Ok(report)
+}
+
+pub fn prune_all_profiles(db_path: &str) -> Result<Vec<PruneReport>, String> {
+    let config = load_backup_config(db_path)?;
+
+    // Include profiles that only exist on disk so they fall back to the default policy
+    let mut profiles: Vec<String> = config.profiles.keys().cloned().collect();
+    for backup in scan_backups(db_path)? {
+        if !profiles.contains(&backup.profile) {
+            profiles.push(backup.profile);
+        }
+    }
+
+    profiles.iter().map(|profile| prune_profile(db_path, profile)).collect()
+}
+
+fn emit_prune_report(app_handle: &AppHandle, report: &PruneReport) {
+    if !report.deleted.is_empty() {
+        let _ = app_handle.emit("backup:pruned", report.clone());
+    }
+}
+
+/// Runs retention for every profile on the interval set in `backup_config`.
+pub fn spawn_retention_scheduler(db_path: String, app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let interval = load_backup_config(&db_path)
+                .map(|c| c.prune_interval_minutes)
+                .unwrap_or(60)
+                .max(1);
+            tokio::time::sleep(tokio::time::Duration::from_secs(interval * 60)).await;
+
+            match prune_all_profiles(&db_path) {
+                Ok(reports) => {
+                    for report in &reports {
+                        emit_prune_report(&app_handle, report);
+                    }
+                }
+                Err(e) => eprintln!("[BACKUP] Scheduled prune failed: {}", e),
+            }
+
+            // Destinations keep their own retention, independent of local copies
+            let destinations = load_backup_config(&db_path).map(|c| c.destinations).unwrap_or_default();
+            for destination in destinations.iter().filter(|d| d.enabled) {
+                if let Err(e) = prune_remote(&db_path, destination).await {
+                    eprintln!("[BACKUP] Scheduled prune of {} failed: {}", destination.name, e);
+                }
+            }
+        }
+    });
+}
+
+fn hash_reader<R: Read>(reader: &mut R) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(reader, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn verify_zip(backup_id: &str, backup_file: &Path) -> VerifyReport {
+    let mut missing = Vec::new();
+    let mut corrupt = Vec::new();
+    let mut checked = 0;
+
+    let archive = fs::File::open(backup_file)
+        .map_err(|e| e.to_string())
+        .and_then(|f| zip::ZipArchive::new(f).map_err(|e| e.to_string()));
+
+    match archive {
+        Ok(mut archive) => {
+            // Older backups have no manifest; they are checked by CRC only.
+            // One that is present but unreadable is corruption, not an old backup.
+            let manifest: Option<Manifest> = match archive.by_name(ZIP_MANIFEST_ENTRY) {
+                Ok(mut f) => {
+                    let mut data = Vec::new();
+                    let parsed = f
+                        .read_to_end(&mut data)
+                        .map_err(|e| e.to_string())
+                        .and_then(|_| serde_json::from_slice(&data).map_err(|e| e.to_string()));
+                    match parsed {
+                        Ok(manifest) => Some(manifest),
+                        Err(e) => {
+                            corrupt.push(format!("{}: {}", ZIP_MANIFEST_ENTRY, e));
+                            None
+                        }
+                    }
+                }
+                Err(_) => None,
+            };
+            let expected: HashMap<String, String> = manifest
+                .map(|m| {
+                    m.entries
+                        .into_iter()
+                        .filter_map(|e| e.sha256.map(|h| (e.path, h)))
+                        .collect()
+                })
+                .unwrap_or_default();
+            let mut seen = HashSet::new();
+
+            for i in 0..archive.len() {
+                let mut file = match archive.by_index(i) {
+                    Ok(file) => file,
+                    Err(e) => {
+                        corrupt.push(format!("entry #{}: {}", i, e));
+                        continue;
+                    }
+                };
+                let name = file.name().to_string();
+                if file.is_dir() || name == ZIP_MANIFEST_ENTRY {
+                    continue;
+                }
+                checked += 1;
+
+                // Reading an entry to the end validates its CRC
+                match hash_reader(&mut file) {
+                    Ok(hash) => {
+                        if let Some(expected_hash) = expected.get(&name) {
+                            if *expected_hash != hash {
+                                corrupt.push(format!("{}: hash mismatch", name));
+                            }
+                        }
+                    }
+                    Err(e) => corrupt.push(format!("{}: {}", name, e)),
+                }
+                seen.insert(name);
+            }
+
+            for path in expected.keys() {
+                if !seen.contains(path) {
+                    missing.push(path.clone());
+                }
+            }
+        }
+        Err(e) => corrupt.push(format!("archive: {}", e)),
+    }
+
+    VerifyReport {
+        backup_id: backup_id.to_string(),
+        ok: missing.is_empty() && corrupt.is_empty(),
+        checked,
+        missing,
+        corrupt,
+        verified_at: Utc::now().to_rfc3339(),
+    }
+}
+
+fn verify_incremental(backup_id: &str, manifest_file: &Path) -> Result<VerifyReport, String> {
+    let manifest = Manifest::load(manifest_file).map_err(|e| e.to_string())?;
+    let store = get_backup_store()?;
+    let mut missing = Vec::new();
+    let mut corrupt = Vec::new();
+    let mut checked = 0;
+
+    for entry in manifest.entries.iter().filter(|e| !e.is_dir) {
+        checked += 1;
+        let Some(hash) = entry.sha256.as_deref() else {
+            corrupt.push(format!("{}: no hash in manifest", entry.path));
+            continue;
+        };
+        if !store.contains(hash) {
+            missing.push(entry.path.clone());
+            continue;
+        }
+
+        let result = store
+            .open_blob(hash)
+            .map_err(|e| e.to_string())
+            .and_then(|mut blob| hash_reader(&mut blob).map_err(|e| e.to_string()));
+        match result {
+            Ok(actual) if actual == hash => {}
+            Ok(_) => corrupt.push(format!("{}: hash mismatch", entry.path)),
+            Err(e) => corrupt.push(format!("{}: {}", entry.path, e)),
+        }
+    }
+
+    Ok(VerifyReport {
+        backup_id: backup_id.to_string(),
+        ok: missing.is_empty() && corrupt.is_empty(),
+        checked,
+        missing,
+        corrupt,
+        verified_at: Utc::now().to_rfc3339(),
+    })
+}
+
+/// Checks a backup end to end and records the result in the backup index.
+pub async fn run_verification(db_path: &str, backup: &Backup, passphrase: Option<&str>) -> Result<VerifyReport, String> {
+    let location = locate_backup(&backup.id)?;
+    let report = match location.format {
+        BackupFormat::Zip if location.encrypted => {
+            let passphrase = passphrase.ok_or(PASSPHRASE_REQUIRED)?;
+            // Decryption authenticates every chunk before the zip itself is checked
+            match decrypt_to_temp(&location, &backup.id, passphrase) {
+                Ok(temp) => verify_zip(&backup.id, temp.path()),
+                Err(CryptoError::Corrupt) => VerifyReport {
+                    backup_id: backup.id.clone(),
+                    ok: false,
+                    checked: 0,
+                    missing: Vec::new(),
+                    corrupt: vec![format!("archive: {}", CryptoError::Corrupt)],
+                    verified_at: Utc::now().to_rfc3339(),
+                },
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        BackupFormat::Zip => verify_zip(&backup.id, &location.path),
+        BackupFormat::Incremental => verify_incremental(&backup.id, &location.path)?,
+    };
+    record_verification(db_path, backup, &report)?;
+
+    if !report.ok {
+        eprintln!(
+            "[BACKUP] Verification failed for {}: {} missing, {} corrupt",
+            backup.id, report.missing.len(), report.corrupt.len()
+        );
+        let profile = load_backup_config(db_path)?.profile(&backup.profile);
+        if profile.verification.notify_discord {
+            let message = format!(
+                "Backup `{}` failed verification: {} missing, {} corrupt entries.",
+                backup.id, report.missing.len(), report.corrupt.len()
+            );
+            if let Err(e) = discord::notify_backup_failure(db_path, &message).await {
+                eprintln!("[BACKUP] Failed to notify Discord: {}", e);
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Re-verifies every backup whose last check is older than `verify_interval_minutes`.
+/// Encrypted backups are skipped unless their profile has been unlocked.
+pub fn spawn_verification_scheduler(db_path: String, app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let interval = load_backup_config(&db_path)
+                .map(|c| c.verify_interval_minutes)
+                .unwrap_or(0);
+            if interval == 0 {
+                // Disabled; check again later in case it gets enabled
+                tokio::time::sleep(tokio::time::Duration::from_secs(300)).await;
+                continue;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_secs(interval * 60)).await;
+
+            let backups = match scan_backups(&db_path) {
+                Ok(backups) => backups,
+                Err(e) => {
+                    eprintln!("[BACKUP] Scheduled verification failed: {}", e);
+                    continue;
+                }
+            };
+            let cutoff = Utc::now() - Duration::minutes(interval as i64);
+
+            for backup in backups {
+                let due = backup
+                    .verification
+                    .as_ref()
+                    .and_then(|v| DateTime::parse_from_rfc3339(&v.verified_at).ok())
+                    .map(|t| t.with_timezone(&Utc) < cutoff)
+                    .unwrap_or(true);
+                if due {
+                    let state = app_handle.state::<AppState>();
+                    let passphrase = resolve_passphrase(&state, &backup.profile, None);
+                    if backup.encrypted && passphrase.is_none() {
+                        continue;
+                    }
+                    if let Err(e) = run_verification(&db_path, &backup, passphrase.as_deref()).await {
+                        eprintln!("[BACKUP] Failed to verify {}: {}", backup.id, e);
+                    }
+                }
+            }
+        }
+    });
+}
+
+fn zip_directory(source: &Path, output: &Path, backup_id: &str, created_at: &str) -> Result<(), String> {
+    let file = fs::File::create(output).map_err(|e| e.to_string())?;
+    let mut zip = ZipWriter::new(file);
+
+    let walkdir = walkdir::WalkDir::new(source);
+    let it = walkdir.into_iter().filter_map(|e| e.ok());
+    let mut entries = Vec::new();
+
+    for entry in it {
+        let path = entry.path();
+        let name = path.strip_prefix(source).unwrap();
+        let metadata = entry.metadata().ok();
+        let mode = metadata.as_ref().and_then(file_mode);
+        let options = archive::zip_options(metadata.as_ref(), path.is_dir());
+
+        if path.is_file() {
+            zip.start_file(name.to_string_lossy().to_string(), options).map_err(|e| e.to_string())?;
+            let mut f = fs::File::open(path).map_err(|e| e.to_string())?;
+            let mut buffer = Vec::new();
+            f.read_to_end(&mut buffer).map_err(|e| e.to_string())?;
+            zip.write_all(&buffer).map_err(|e| e.to_string())?;
+            
+            entries.push(ManifestEntry {
+                path: name.to_string_lossy().to_string(),
+                is_dir: false,
+                size: buffer.len() as u64,
+                sha256: Some(hex::encode(Sha256::digest(&buffer))),
+                mode,
+            });
+        } else if !name.as_os_str().is_empty() {
+            zip.add_directory(name.to_string_lossy().to_string(), options).map_err(|e| e.to_string())?;
+        }
+    }
+
+    // Record content hashes so verify_backup can detect corruption beyond CRC
+    let manifest = Manifest {
+        id: backup_id.to_string(),
+        created_at: created_at.to_string(),
+        entries,
+    };
+    zip.start_file(ZIP_MANIFEST_ENTRY, archive::zip_options(None, false)).map_err(|e| e.to_string())?;
+    zip.write_all(&serde_json::to_vec(&manifest).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+
+    zip.finish().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Archives made by other tools often wrap the server in a single folder
+fn import_root(staging_dir: &Path) -> Result<PathBuf, String> {
+    let entries: Vec<fs::DirEntry> = fs::read_dir(staging_dir)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    
+    match entries.as_slice() {
+        [only] if only.path().is_dir() => Ok(only.path()),
+        _ => Ok(staging_dir.to_path_buf()),
+    }
+}
+
+// Backup names become part of file names
+fn sanitize_backup_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    if sanitized.is_empty() {
+        "imported".to_string()
+    } else {
+        sanitized
+    }
+}
+
+fn export_tar_gz(backup: &Backup, location: &BackupLocation, passphrase: Option<&str>, output: &Path) -> Result<(), String> {
+    let mut builder = archive::tar_gz_builder(output).map_err(|e| e.to_string())?;
+    let mtime = DateTime::parse_from_rfc3339(&backup.created_at)
+        .map(|t| t.timestamp().max(0) as u64)
+        .unwrap_or(0);
+    
+    match location.format {
+        BackupFormat::Incremental => {
+            let manifest = Manifest::load(&location.path).map_err(|e| e.to_string())?;
+            let store = get_backup_store()?;
+            for entry in &manifest.entries {
+                if entry.is_dir {
+                    archive::append_tar_entry(&mut builder, &entry.path, true, 0, entry.mode, mtime, std::io::empty())
+                } else {
+                    let hash = entry.sha256.as_deref().ok_or("Manifest entry has no hash")?;
+                    let blob = store.open_blob(hash).map_err(|e| e.to_string())?;
+                    archive::append_tar_entry(&mut builder, &entry.path, false, entry.size, entry.mode, mtime, blob)
+                }
+                .map_err(|e| e.to_string())?;
+            }
+        }
+        BackupFormat::Zip => {
+            let mut opened = open_zip(location, &backup.id, passphrase)?;
+            let zip = &mut opened.archive;
+            for i in 0..zip.len() {
+                let mut file = zip.by_index(i).map_err(|e| e.to_string())?;
+                if file.name() == ZIP_MANIFEST_ENTRY {
+                    continue;
+                }
+                let path = file
+                    .enclosed_name()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .ok_or_else(|| format!("Unsafe path in archive: {}", file.name()))?;
+                let (is_dir, size, mode) = (file.is_dir(), file.size(), file.unix_mode());
+                archive::append_tar_entry(&mut builder, &path, is_dir, size, mode, mtime, &mut file)
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    
+    archive::finish_tar_gz(builder).map_err(|e| e.to_string())
+}
+
+// Writes the archive or manifest for a new backup and returns its size
+fn write_backup(
+    server_dir: &Path,
+    backup_id: &str,
+    created_at: &str,
+    format: BackupFormat,
+    passphrase: Option<&str>,
+    kdf: KdfParams,
+) -> Result<u64, String> {
+    let backups_dir = get_backups_dir()?;
+    
+    let size = match format {
+        BackupFormat::Zip => match passphrase {
+            Some(passphrase) => {
+                // ZipWriter needs a seekable file, so stage the plain archive and encrypt it in chunks
+                let staging = TempFile(get_temp_dir()?.join(format!("{}.zip", backup_id)));
+                zip_directory(server_dir, &staging.0, backup_id, created_at)?;
+                let backup_file = backups_dir.join(format!("{}.{}", backup_id, ENCRYPTED_EXTENSION));
+                crypto::encrypt_file(&staging.0, &backup_file, passphrase, kdf)
+                    .map_err(|e| e.to_string())?;
+                fs::metadata(&backup_file).map_err(|e| e.to_string())?.len()
+            }
+            None => {
+                let backup_file = backups_dir.join(format!("{}.zip", backup_id));
+                zip_directory(server_dir, &backup_file, backup_id, created_at)?;
+                fs::metadata(&backup_file).map_err(|e| e.to_string())?.len()
+            }
+        },
+        BackupFormat::Incremental => {
+            let manifest_file = backups_dir.join(format!("{}.{}", backup_id, MANIFEST_EXTENSION));
+            let store = get_backup_store()?;
+            let manifest = store
+                .snapshot(server_dir, &manifest_file, backup_id, created_at)
+                .map_err(|e| e.to_string())?;
+            manifest.total_size()
+        }
+    };
+    
+    Ok(size)
+}
+
+// Archive name a backup is stored under at a destination. Incremental
+// backups are uploaded as a self-contained zip.
+fn remote_name(backup: &Backup) -> String {
+    let extension = if backup.encrypted { ENCRYPTED_EXTENSION } else { "zip" };
+    format!("{}.{}", backup.id, extension)
+}
+
+// Restores an incremental backup into a scratch directory and zips it
+fn materialize_zip(backup: &Backup) -> Result<TempFile, String> {
+    let location = locate_backup(&backup.id)?;
+    let manifest = Manifest::load(&location.path).map_err(|e| e.to_string())?;
+    let temp_dir = get_temp_dir()?;
+    let staging_dir = temp_dir.join(format!("{}.tree", backup.id));
+    let archive = TempFile(temp_dir.join(format!("{}.materialized.zip", backup.id)));
+
+    let result = get_backup_store()
+        .and_then(|store| store.restore(&manifest, &staging_dir).map_err(|e| e.to_string()))
+        .and_then(|_| zip_directory(&staging_dir, &archive.0, &backup.id, &backup.created_at));
+    let _ = fs::remove_dir_all(&staging_dir);
+    result.map(|_| archive)
+}
+
+fn open_configured_destination(destination: &DestinationConfig) -> Result<Box<dyn BackupDestination>, String> {
+    let state_dir = get_backups_dir()?.join(".uploads");
+    destinations::open_destination(&destination.kind, &state_dir).map_err(|e| e.to_string())
+}
+
+// Lists a destination's archives as backups so the local retention rules apply
+fn remote_backups(remotes: Vec<RemoteBackup>, index: &HashMap<String, IndexEntry>) -> Vec<Backup> {
+    let mut backups: Vec<Backup> = remotes
+        .into_iter()
+        .filter_map(|remote| {
+            let (id, extension) = remote.name.rsplit_once('.')?;
+            let encrypted = match extension {
+                "zip" => false,
+                ENCRYPTED_EXTENSION => true,
+                _ => return None,
+            };
+            let (created_at, name) = parse_backup_id(id);
+            let (profile, pinned) = match index.get(id) {
+                Some(entry) => (entry.profile.clone(), entry.pinned),
+                None => (DEFAULT_PROFILE.to_string(), false),
+            };
+
+            Some(Backup {
+                id: id.to_string(),
+                name,
+                created_at,
+                size: remote.size,
+                profile,
+                pinned,
+                format: BackupFormat::Zip,
+                encrypted,
+                verification: None,
+            })
+        })
+        .collect();
+
+    backups.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    backups
+}
+
+async fn list_destination(db_path: &str, target: &dyn BackupDestination) -> Result<Vec<Backup>, String> {
+    let remotes = target.list().await.map_err(|e| e.to_string())?;
+    Ok(remote_backups(remotes, &load_backup_index(db_path)?))
+}
+
+/// Copies `backup` to `destination`, emitting `backup:upload-progress` while it runs.
+pub async fn upload_to_destination(
+    app_handle: &AppHandle,
+    destination: &DestinationConfig,
+    backup: &Backup,
+) -> Result<RemoteBackup, String> {
+    let target = open_configured_destination(destination)?;
+    let (path, _materialized) = match backup.format {
+        BackupFormat::Zip => (locate_backup(&backup.id)?.path, None),
+        BackupFormat::Incremental => {
+            let archive = materialize_zip(backup)?;
+            (archive.0.clone(), Some(archive))
+        }
+    };
+    let sha256 = hash_file(&path).map_err(|e| e.to_string())?;
+
+    let progress_handle = app_handle.clone();
+    let (destination_id, backup_id) = (destination.id.clone(), backup.id.clone());
+    let progress = move |done: u64, total: u64| {
+        let _ = progress_handle.emit("backup:upload-progress", serde_json::json!({
+            "destination_id": destination_id,
+            "backup_id": backup_id,
+            "done": done,
+            "total": total,
+        }));
+    };
+
+    let remote = target
+        .upload(&path, &remote_name(backup), &sha256, &progress)
+        .await
+        .map_err(|e| e.to_string())?;
+    eprintln!("[BACKUP] Uploaded {} to {}", backup.id, destination.name);
+
+    let _ = app_handle.emit("backup:uploaded", serde_json::json!({
+        "destination_id": destination.id,
+        "backup_id": backup.id,
+        "remote": remote,
+    }));
+    Ok(remote)
+}
+
+/// Applies a destination's own retention policy to the archives stored there.
+pub async fn prune_remote(db_path: &str, destination: &DestinationConfig) -> Result<Vec<PruneReport>, String> {
+    let target = open_configured_destination(destination)?;
+    let backups = list_destination(db_path, target.as_ref()).await?;
+
+    let mut profiles: Vec<String> = Vec::new();
+    for backup in &backups {
+        if !profiles.contains(&backup.profile) {
+            profiles.push(backup.profile.clone());
+        }
+    }
+
+    let mut reports = Vec::new();
+    for profile in profiles {
+        let mut deleted = Vec::new();
+        let mut freed_bytes = 0;
+
+        for backup in select_expired(&backups, &profile, &destination.retention, Utc::now()) {
+            match target.delete(&remote_name(&backup)).await {
+                Ok(()) => {
+                    eprintln!("[BACKUP] Pruned {} from {}", backup.id, destination.name);
+                    freed_bytes += backup.size;
+                    deleted.push(backup);
+                }
+                Err(e) => eprintln!("[BACKUP] Failed to prune {} from {}: {}", backup.id, destination.name, e),
+            }
+        }
+
+        let kept = backups.iter().filter(|b| b.profile == profile).count() - deleted.len();
+        reports.push(PruneReport {
+            profile,
+            kept,
+            deleted,
+            freed_bytes,
+            ran_at: Utc::now().to_rfc3339(),
+        });
+    }
+
+    Ok(reports)
+}
+
+// Uploads a new backup to every destination that takes its profile, in the background
+fn spawn_uploads(db_path: &str, config: &BackupConfig, backup: &Backup, app_handle: &AppHandle) {
+    let targets = config
+        .destinations
+        .iter()
+        .filter(|d| d.enabled && d.accepts(&backup.profile));
+
+    for destination in targets {
+        let destination = destination.clone();
+        let backup = backup.clone();
+        let db_path = db_path.to_string();
+        let app_handle = app_handle.clone();
+
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = upload_to_destination(&app_handle, &destination, &backup).await {
+                eprintln!("[BACKUP] Upload of {} to {} failed: {}", backup.id, destination.name, e);
+                let _ = app_handle.emit("backup:upload-failed", serde_json::json!({
+                    "destination_id": destination.id,
+                    "backup_id": backup.id,
+                    "error": e,
+                }));
+                return;
+            }
+            if let Err(e) = prune_remote(&db_path, &destination).await {
+                eprintln!("[BACKUP] Prune of {} failed: {}", destination.name, e);
+            }
+        });
+    }
+}
+
+async fn run_hook_steps(service: &ServerService, steps: &[HookStep], timeout_secs: u64) -> Result<(), String> {
+    let timeout = std::time::Duration::from_secs(timeout_secs);
+    
+    for step in steps {
+        match &step.wait_for {
+            Some(pattern) => {
+                let confirmed = service
+                    .send_command_and_wait(&step.command, pattern, timeout)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                if !confirmed {
+                    return Err(format!("Timed out waiting for \"{}\" after `{}`", pattern, step.command));
+                }
+            }
+            None => {
+                service.send_command(&step.command).await.map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+async fn run_post_backup_hook(service: &ServerService, hooks: &ConsistencyHooks) {
+    if let Err(e) = run_hook_steps(service, &hooks.post_backup, hooks.timeout_secs).await {
+        eprintln!("[BACKUP] Post-backup hook failed: {}", e);
+    }
+}
+
+#[tauri::command]
+pub async fn create_backup(
+    name: Option<String>,
+    profile: Option<String>,
+    passphrase: Option<String>,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<Backup, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let detail = profile.as_ref().map(|p| format!("profile {}", p));
+    let result = take_backup(name, profile, passphrase, &state, &app_handle).await;
+    let target = result.as_ref().ok().map(|backup| backup.id.clone());
+    audit_log::audited(&db_path, "backup:create", target.as_deref(), detail.as_deref(), result)
+}
+
+async fn take_backup(
+    name: Option<String>,
+    profile: Option<String>,
+    passphrase: Option<String>,
+    state: &State<'_, AppState>,
+    app_handle: &AppHandle,
+) -> Result<Backup, String> {
+    let server_path = get_server_path(state)?;
+    let db_path = state.db_path.lock().unwrap().clone();
+    let profile = profile.unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+    let server_dir = PathBuf::from(&server_path);
+    
+    if !server_dir.exists() {
+        return Err("Server directory does not exist".to_string());
+    }
+    
+    let config = load_backup_config(&db_path)?;
+    let profile_config = config.profile(&profile);
+    let format = profile_config.format;
+    
+    let passphrase = if profile_config.encryption.enabled {
+        if format == BackupFormat::Incremental {
+            return Err("Encryption is only supported for zip backups".to_string());
+        }
+        Some(resolve_passphrase(state, &profile, passphrase).ok_or(PASSPHRASE_REQUIRED)?)
+    } else {
+        None
+    };
+    
+    let timestamp = Utc::now();
+    let backup_name = sanitize_backup_name(
+        &name.unwrap_or_else(|| format!("backup_{}", timestamp.format("%Y%m%d_%H%M%S"))),
+    );
+    let backup_id = format!("{}_{}", timestamp.timestamp(), backup_name);
+    
+    // Quiesce a running server so world files are not written mid-backup
+    let hooks = &profile_config.consistency;
+    let service = state.server_service.lock().unwrap().clone();
+    let hooked_service = match service {
+        Some(service) if hooks.enabled && service.is_running() => Some(service),
+        _ => None,
+    };
+    
+    if let Some(service) = &hooked_service {
+        if let Err(e) = run_hook_steps(service, &hooks.pre_backup, hooks.timeout_secs).await {
+            eprintln!("[BACKUP] Pre-backup hook failed: {}", e);
+            if hooks.require_confirmation {
+                run_post_backup_hook(service, hooks).await;
+                return Err(format!("Pre-backup hook failed: {}", e));
+            }
+        }
+    }
+    
+    let result = write_backup(
+        &server_dir,
+        &backup_id,
+        &timestamp.to_rfc3339(),
+        format,
+        passphrase.as_deref(),
+        profile_config.encryption.kdf,
+    );
+    
+    // Always re-enable saving, even when the backup failed
+    if let Some(service) = &hooked_service {
+        run_post_backup_hook(service, hooks).await;
+    }
+    let size = result?;
+    
+    let backup = Backup {
+        id: backup_id,
+        name: backup_name,
+        created_at: timestamp.to_rfc3339(),
+        size,
+        profile,
+        pinned: false,
+        format,
+        encrypted: passphrase.is_some(),
+        verification: None,
+    };
+    register_backup(&db_path, &backup)?;
+    
+    if profile_config.verification.after_create {
+        if let Err(e) = run_verification(&db_path, &backup, passphrase.as_deref()).await {
+            eprintln!("[BACKUP] Verification after backup failed: {}", e);
+        }
+    }
+    
+    // Apply the profile's retention policy now that a new backup exists
+    match prune_profile(&db_path, &backup.profile) {
+        Ok(report) => emit_prune_report(app_handle, &report),
+        Err(e) => eprintln!("[BACKUP] Prune after backup failed: {}", e),
+    }
+    
+    spawn_uploads(&db_path, &config, &backup, app_handle);
+    
+    Ok(backup)
+}
+
+#[tauri::command]
+pub async fn list_backups(state: State<'_, AppState>) -> Result<Vec<Backup>, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    scan_backups(&db_path)
+}
+
+fn find_backup(db_path: &str, backup_id: &str) -> Result<Backup, String> {
+    scan_backups(db_path)?
+        .into_iter()
+        .find(|b| b.id == backup_id)
+        .ok_or_else(|| "Backup file not found".to_string())
+}
+
+#[tauri::command]
+pub async fn restore_backup(
+    backup_id: String,
+    passphrase: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let result = restore_server_dir(&backup_id, passphrase, &state);
+    audit_log::audited(&db_path, "backup:restore", Some(&backup_id), None, result)
+}
+
+// Builds the restored tree next to the server directory and swaps it in only
+// once it is complete, so a restore that fails partway leaves the server as it was
+fn restore_via_staging(server_dir: &Path, fill: impl FnOnce(&Path) -> Result<(), String>) -> Result<(), String> {
+    let name = server_dir.file_name().ok_or("Invalid server path")?.to_string_lossy().to_string();
+    let staging = server_dir.with_file_name(format!(".{}.restoring", name));
+    let previous = server_dir.with_file_name(format!(".{}.previous", name));
+    
+    // A crash between the two renames below leaves only the previous tree
+    if previous.exists() && !server_dir.exists() {
+        fs::rename(&previous, server_dir).map_err(|e| e.to_string())?;
+    }
+    for leftover in [&staging, &previous] {
+        if leftover.exists() {
+            fs::remove_dir_all(leftover).map_err(|e| e.to_string())?;
+        }
+    }
+    
+    fs::create_dir_all(&staging).map_err(|e| e.to_string())?;
+    if let Err(e) = fill(&staging) {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+    
+    if server_dir.exists() {
+        if let Err(e) = fs::rename(server_dir, &previous) {
+            let _ = fs::remove_dir_all(&staging);
+            return Err(format!("Failed to move the server directory aside: {}", e));
+        }
+    }
+    if let Err(e) = fs::rename(&staging, server_dir) {
+        let _ = fs::rename(&previous, server_dir);
+        let _ = fs::remove_dir_all(&staging);
+        return Err(format!("Failed to move the restored files into place: {}", e));
+    }
+    if previous.exists() {
+        if let Err(e) = fs::remove_dir_all(&previous) {
+            eprintln!("[BACKUP] Failed to remove the replaced server files at {}: {}", previous.display(), e);
+        }
+    }
+    Ok(())
+}
+
+fn restore_server_dir(backup_id: &str, passphrase: Option<String>, state: &State<'_, AppState>) -> Result<bool, String> {
+    let server_path = get_server_path(state)?;
+    let server_dir = PathBuf::from(&server_path);
+    let db_path = state.db_path.lock().unwrap().clone();
+    let backup = find_backup(&db_path, backup_id)?;
+    let location = locate_backup(backup_id)?;
+    
+    if location.format == BackupFormat::Incremental {
+        let manifest = Manifest::load(&location.path).map_err(|e| e.to_string())?;
+        let store = get_backup_store()?;
+        // Missing blobs or unsafe paths are refused before anything is written
+        store.check(&manifest).map_err(|e| e.to_string())?;
+        restore_via_staging(&server_dir, |staging| store.restore(&manifest, staging).map_err(|e| e.to_string()))?;
+        return Ok(true);
+    }
+    
+    // Open (and decrypt) the archive before touching the server directory,
+    // so a wrong passphrase or unreadable backup leaves the server intact
+    let passphrase = resolve_passphrase(state, &backup.profile, passphrase);
+    let mut opened = open_zip(&location, backup_id, passphrase.as_deref())?;
+    
+    let options = ExtractOptions {
+        skip_entries: &[ZIP_MANIFEST_ENTRY],
+        ..Default::default()
+    };
+    restore_via_staging(&server_dir, |staging| {
+        archive::extract_zip(&mut opened.archive, staging, &options, &mut |_, _| {})
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    })?;
+    
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn export_backup(
+    backup_id: String,
+    path: String,
+    format: ExportFormat,
+    passphrase: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<u64, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let backup = find_backup(&db_path, &backup_id)?;
+    let location = locate_backup(&backup_id)?;
+    let passphrase = resolve_passphrase(&state, &backup.profile, passphrase);
+    let output = PathBuf::from(&path);
+    
+    let result = match format {
+        ExportFormat::TarGz => export_tar_gz(&backup, &location, passphrase.as_deref(), &output),
+        ExportFormat::Zip => match location.format {
+            // Exports are always plain archives so other tools can open them
+            BackupFormat::Zip if location.encrypted => {
+                let passphrase = passphrase.as_deref().ok_or(PASSPHRASE_REQUIRED)?;
+                crypto::decrypt_file(&location.path, &output, passphrase).map_err(|e| e.to_string())
+            }
+            BackupFormat::Zip => fs::copy(&location.path, &output).map(|_| ()).map_err(|e| e.to_string()),
+            BackupFormat::Incremental => materialize_zip(&backup)
+                .and_then(|archive| fs::copy(&archive.0, &output).map(|_| ()).map_err(|e| e.to_string())),
+        },
+    };
+    
+    if let Err(e) = result {
+        let _ = fs::remove_file(&output);
+        return Err(e);
+    }
+    fs::metadata(&output).map(|m| m.len()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn import_backup(
+    path: String,
+    name: Option<String>,
+    profile: Option<String>,
+    passphrase: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Backup, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let detail = format!("from {}", path);
+    let result = import_archive(&db_path, &path, name, profile, passphrase, &state);
+    let target = result.as_ref().ok().map(|backup| backup.id.clone());
+    audit_log::audited(&db_path, "backup:import", target.as_deref(), Some(&detail), result)
+}
+
+// Zips exported by the portal carry a manifest; its checksums must match
+// before the contents are trusted as a backup
+fn check_import(backup_id: &str, source: &Path) -> Result<(), String> {
+    let report = verify_zip(backup_id, source);
+    if report.ok {
+        return Ok(());
+    }
+    let problems: Vec<String> = report
+        .missing
+        .iter()
+        .map(|path| format!("{}: missing", path))
+        .chain(report.corrupt.iter().cloned())
+        .collect();
+    eprintln!("[BACKUP] Refused import of {}: {}", source.display(), problems.join(", "));
+    Err(format!(
+        "Archive failed verification: {} missing, {} corrupt ({})",
+        report.missing.len(),
+        report.corrupt.len(),
+        problems.first().map(String::as_str).unwrap_or_default()
+    ))
+}
+
+fn import_archive(
+    db_path: &str,
+    path: &str,
+    name: Option<String>,
+    profile: Option<String>,
+    passphrase: Option<String>,
+    state: &State<'_, AppState>,
+) -> Result<Backup, String> {
+    let profile = profile.unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+    let source = PathBuf::from(path);
+    let file_name = source
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or("Invalid archive path")?;
+    
+    let archive_format = ArchiveFormat::from_path(&source).ok_or_else(|| ArchiveError::UnknownFormat.to_string())?;
+    let stem = archive_format.stem(&file_name);
+    
+    let config = load_backup_config(db_path)?;
+    let profile_config = config.profile(&profile);
+    let format = profile_config.format;
+    let passphrase = if profile_config.encryption.enabled {
+        if format == BackupFormat::Incremental {
+            return Err("Encryption is only supported for zip backups".to_string());
+        }
+        Some(resolve_passphrase(state, &profile, passphrase).ok_or(PASSPHRASE_REQUIRED)?)
+    } else {
+        None
+    };
+    
+    let timestamp = Utc::now();
+    let backup_name = sanitize_backup_name(&name.unwrap_or_else(|| stem.to_string()));
+    let backup_id = format!("{}_{}", timestamp.timestamp(), backup_name);
+    let staging_dir = get_temp_dir()?.join(format!("import-{}", backup_id));
+    
+    // Unpack and validate first, then store it like any other backup of the profile
+    let result = (|| {
+        if archive_format == ArchiveFormat::Zip {
+            check_import(&backup_id, &source)?;
+        }
+        fs::create_dir_all(&staging_dir).map_err(|e| e.to_string())?;
+        let options = ExtractOptions {
+            skip_entries: &[ZIP_MANIFEST_ENTRY],
+            ..Default::default()
+        };
+        let report = archive::extract(&source, &staging_dir, &options, &mut |_, _| {}).map_err(|e| e.to_string())?;
+        if report.files == 0 {
+            return Err("Archive contains no files".to_string());
+        }
+        
+        write_backup(
+            &import_root(&staging_dir)?,
+            &backup_id,
+            &timestamp.to_rfc3339(),
+            format,
+            passphrase.as_deref(),
+            profile_config.encryption.kdf,
+        )
+    })();
+    let _ = fs::remove_dir_all(&staging_dir);
+    let size = result?;
+    
+    let backup = Backup {
+        id: backup_id,
+        name: backup_name,
+        created_at: timestamp.to_rfc3339(),
+        size,
+        profile,
+        pinned: false,
+        format,
+        encrypted: passphrase.is_some(),
+        verification: None,
+    };
+    register_backup(db_path, &backup)?;
+    eprintln!("[BACKUP] Imported {} as {}", path, backup.id);
+    
+    Ok(backup)
+}
+
+#[tauri::command]
+pub async fn delete_backup(backup_id: String, state: State<'_, AppState>) -> Result<bool, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let result = locate_backup(&backup_id).and_then(|location| {
+        remove_backup(&db_path, &backup_id)?;
+        if location.format == BackupFormat::Incremental {
+            collect_garbage()?;
+        }
+        Ok(true)
+    });
+    audit_log::audited(&db_path, "backup:delete", Some(&backup_id), None, result)
+}
+
+#[tauri::command]
+pub async fn set_backup_pinned(backup_id: String, pinned: bool, state: State<'_, AppState>) -> Result<bool, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let mut backup = find_backup(&db_path, &backup_id)?;
+    
+    backup.pinned = pinned;
+    register_backup(&db_path, &backup)?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn prune_backups(
+    profile: Option<String>,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<Vec<PruneReport>, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    
+    let reports = match profile {
+        Some(profile) => vec![prune_profile(&db_path, &profile)?],
+        None => prune_all_profiles(&db_path)?,
+    };
+    
+    for report in &reports {
+        emit_prune_report(&app_handle, report);
+    }
+    
+    Ok(reports)
+}
+
+#[tauri::command]
+pub async fn verify_backup(
+    backup_id: String,
+    passphrase: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<VerifyReport, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let backup = find_backup(&db_path, &backup_id)?;
+    let passphrase = resolve_passphrase(&state, &backup.profile, passphrase);
+    
+    run_verification(&db_path, &backup, passphrase.as_deref()).await
+}
+
+#[tauri::command]
+pub async fn browse_backup(
+    backup_id: String,
+    passphrase: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<ManifestEntry>, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let backup = find_backup(&db_path, &backup_id)?;
+    let location = locate_backup(&backup_id)?;
+    
+    if location.format == BackupFormat::Incremental {
+        return Ok(Manifest::load(&location.path).map_err(|e| e.to_string())?.entries);
+    }
+    
+    let passphrase = resolve_passphrase(&state, &backup.profile, passphrase);
+    let mut opened = open_zip(&location, &backup_id, passphrase.as_deref())?;
+    let mut entries = Vec::new();
+    
+    for i in 0..opened.archive.len() {
+        let file = opened.archive.by_index(i).map_err(|e| e.to_string())?;
+        if file.name() == ZIP_MANIFEST_ENTRY {
+            continue;
+        }
+        entries.push(ManifestEntry {
+            path: file.name().trim_end_matches('/').to_string(),
+            is_dir: file.is_dir(),
+            size: file.size(),
+            sha256: None,
+            mode: file.unix_mode(),
+        });
+    }
+    
+    Ok(entries)
+}
+
+#[tauri::command]
+pub async fn unlock_backup_profile(
+    profile: String,
+    passphrase: String,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    
+    // Check the passphrase against the newest encrypted backup of the profile, if any
+    let newest = scan_backups(&db_path)?
+        .into_iter()
+        .find(|b| b.profile == profile && b.encrypted);
+    if let Some(backup) = newest {
+        let location = locate_backup(&backup.id)?;
+        crypto::check_passphrase(&location.path, &passphrase).map_err(|e| e.to_string())?;
+    }
+    
+    state.backup_passphrases.lock().unwrap().insert(profile, passphrase);
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn lock_backup_profile(profile: String, state: State<'_, AppState>) -> Result<bool, String> {
+    state.backup_passphrases.lock().unwrap().remove(&profile);
+    Ok(true)
+}
+
+#[tauri::command]
+pub async fn gc_backup_store() -> Result<GcReport, String> {
+    collect_garbage()
+}
+
+#[tauri::command]
+pub async fn get_backup_config(state: State<'_, AppState>) -> Result<BackupConfig, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    load_backup_config(&db_path)
+}
+
+#[tauri::command]
+pub async fn save_backup_config(config: BackupConfig, state: State<'_, AppState>) -> Result<bool, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let result = (|| {
+        let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+        let config_str = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+        
+        conn.execute(
+            "INSERT OR REPLACE INTO config (key, value) VALUES ('backup_config', ?1)",
+            rusqlite::params![config_str],
+        ).map_err(|e| e.to_string())?;
+        
+        Ok(true)
+    })();
+    audit_log::audited(&db_path, "backup:configure", None, None, result)
+}
+
+#[tauri::command]
+pub async fn upload_backup(
+    backup_id: String,
+    destination_id: String,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<RemoteBackup, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let config = load_backup_config(&db_path)?;
+    let destination = config.destination(&destination_id)?;
+    let backup = find_backup(&db_path, &backup_id)?;
+    upload_to_destination(&app_handle, destination, &backup).await
+}
+
+#[tauri::command]
+pub async fn list_remote_backups(destination_id: String, state: State<'_, AppState>) -> Result<Vec<Backup>, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let config = load_backup_config(&db_path)?;
+    let target = open_configured_destination(config.destination(&destination_id)?)?;
+    list_destination(&db_path, target.as_ref()).await
+}
+
+#[tauri::command]
+pub async fn download_remote_backup(
+    destination_id: String,
+    backup_id: String,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<Backup, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let config = load_backup_config(&db_path)?;
+    let target = open_configured_destination(config.destination(&destination_id)?)?;
+
+    if !is_plain_backup_id(&backup_id) {
+        return Err(format!("Invalid backup id: {}", backup_id));
+    }
+    if locate_backup(&backup_id).is_ok() {
+        return Err("Backup already exists locally".to_string());
+    }
+    let backup = list_destination(&db_path, target.as_ref())
+        .await?
+        .into_iter()
+        .find(|b| b.id == backup_id)
+        .ok_or("Remote backup not found")?;
+
+    // Download next to the backups so the final rename stays on one filesystem
+    let file_name = remote_name(&backup);
+    let partial = TempFile(get_temp_dir()?.join(&file_name));
+    let progress_handle = app_handle.clone();
+    let progress = move |done: u64, total: u64| {
+        let _ = progress_handle.emit("backup:download-progress", serde_json::json!({
+            "destination_id": destination_id,
+            "backup_id": backup_id,
+            "done": done,
+            "total": total,
+        }));
+    };
+    target
+        .download(&file_name, &partial.0, &progress)
+        .await
+        .map_err(|e| e.to_string())?;
+    fs::rename(&partial.0, get_backups_dir()?.join(&file_name)).map_err(|e| e.to_string())?;
+
+    register_backup(&db_path, &backup)?;
+    find_backup(&db_path, &backup.id)
+}
+
+#[tauri::command]
+pub async fn delete_remote_backup(
+    destination_id: String,
+    backup_id: String,
+    state: State<'_, AppState>,
+) -> Result<bool, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let result = async {
+        let config = load_backup_config(&db_path)?;
+        let target = open_configured_destination(config.destination(&destination_id)?)?;
+
+        let backup = list_destination(&db_path, target.as_ref())
+            .await?
+            .into_iter()
+            .find(|b| b.id == backup_id)
+            .ok_or("Remote backup not found")?;
+        target.delete(&remote_name(&backup)).await.map_err(|e| e.to_string())?;
+        Ok(true)
+    }
+    .await;
+    let detail = format!("on destination {}", destination_id);
+    audit_log::audited(&db_path, "backup:delete-remote", Some(&backup_id), Some(&detail), result)
+}
+
+#[tauri::command]
+pub async fn prune_destination(destination_id: String, state: State<'_, AppState>) -> Result<Vec<PruneReport>, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let config = load_backup_config(&db_path)?;
+    prune_remote(&db_path, config.destination(&destination_id)?).await
+}
+
+/// Checks that a destination is reachable, returning how many backups it holds.
+#[tauri::command]
+pub async fn test_destination(destination: DestinationConfig) -> Result<usize, String> {
+    let target = open_configured_destination(&destination)?;
+    let remotes = target.list().await.map_err(|e| e.to_string())?;
+    Ok(remotes.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_restore_leaves_the_server_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = dir.path().join("server");
+        fs::create_dir_all(&server).unwrap();
+        fs::write(server.join("world.dat"), "live").unwrap();
+        
+        let result = restore_via_staging(&server, |staging| {
+            fs::write(staging.join("world.dat"), "partial").unwrap();
+            Err("blob missing".to_string())
+        });
+        
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(server.join("world.dat")).unwrap(), "live");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1, "staging was left behind");
+    }
+
+    #[test]
+    fn successful_restore_replaces_the_server() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = dir.path().join("server");
+        fs::create_dir_all(&server).unwrap();
+        fs::write(server.join("stale.dat"), "old").unwrap();
+        
+        restore_via_staging(&server, |staging| {
+            fs::write(staging.join("world.dat"), "restored").map_err(|e| e.to_string())
+        })
+        .unwrap();
+        
+        assert_eq!(fs::read_to_string(server.join("world.dat")).unwrap(), "restored");
+        assert!(!server.join("stale.dat").exists());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1, "the replaced tree was left behind");
+    }
+
+    #[test]
+    fn interrupted_swap_is_recovered() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = dir.path().join("server");
+        let previous = dir.path().join(".server.previous");
+        fs::create_dir_all(&previous).unwrap();
+        fs::write(previous.join("world.dat"), "live").unwrap();
+        
+        let result = restore_via_staging(&server, |_| Err("failed".to_string()));
+        
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(server.join("world.dat")).unwrap(), "live");
+    }
+
+    fn backup(id: &str, profile: &str, created_at: &str, size: u64, pinned: bool) -> Backup {
+        Backup {
+            id: id.to_string(),
+            name: format!("{}.zip", id),
+            created_at: created_at.to_string(),
+            size,
+            profile: profile.to_string(),
+            pinned,
+            format: BackupFormat::Zip,
+            encrypted: false,
+            verification: None,
+        }
+    }
+
+    // Backups of the "manual" profile, newest first, taken at the given times
+    fn series(times: &[&str]) -> Vec<Backup> {
+        times
+            .iter()
+            .enumerate()
+            .map(|(i, time)| backup(&format!("b{}", i), "manual", time, 10, false))
+            .collect()
+    }
+
+    fn expired_ids(backups: &[Backup], policy: &RetentionPolicy, now: &str) -> Vec<String> {
+        let now = DateTime::parse_from_rfc3339(now).unwrap().with_timezone(&Utc);
+        select_expired(backups, "manual", policy, now).into_iter().map(|b| b.id).collect()
+    }
+
+    const NOW: &str = "2024-03-15T12:00:00Z";
+
+    #[test]
+    fn nothing_expires_without_rules_or_backups() {
+        let backups = series(&["2024-03-15T00:00:00Z", "2020-01-01T00:00:00Z"]);
+        assert!(expired_ids(&backups, &RetentionPolicy::default(), NOW).is_empty());
+
+        let policy = RetentionPolicy { keep_last: Some(1), ..Default::default() };
+        assert!(expired_ids(&[], &policy, NOW).is_empty());
+    }
+
+    #[test]
+    fn keep_last_keeps_the_newest() {
+        let backups = series(&[
+            "2024-03-15T10:00:00Z",
+            "2024-03-15T09:00:00Z",
+            "2024-03-15T08:00:00Z",
+            "2024-03-15T07:00:00Z",
+        ]);
+        let policy = RetentionPolicy { keep_last: Some(2), ..Default::default() };
+        assert_eq!(expired_ids(&backups, &policy, NOW), ["b2", "b3"]);
+    }
+
+    #[test]
+    fn keep_daily_keeps_the_newest_of_each_recent_day() {
+        let backups = series(&[
+            "2024-03-15T06:00:00Z",
+            "2024-03-15T00:00:00Z",
+            "2024-03-14T18:00:00Z",
+            "2024-03-14T06:00:00Z",
+            "2024-03-13T12:00:00Z",
+            "2024-03-11T12:00:00Z",
+        ]);
+        let policy = RetentionPolicy { keep_daily: Some(2), ..Default::default() };
+        assert_eq!(expired_ids(&backups, &policy, NOW), ["b1", "b3", "b5"]);
+    }
+
+    #[test]
+    fn keep_weekly_buckets_by_iso_week() {
+        // 2024-03-14 and -12 are in week 11, 2024-03-08 and -04 in week 10
+        let backups = series(&[
+            "2024-03-14T00:00:00Z",
+            "2024-03-12T00:00:00Z",
+            "2024-03-08T00:00:00Z",
+            "2024-03-04T00:00:00Z",
+            "2024-02-20T00:00:00Z",
+        ]);
+        let policy = RetentionPolicy { keep_weekly: Some(2), ..Default::default() };
+        assert_eq!(expired_ids(&backups, &policy, NOW), ["b1", "b3", "b4"]);
+    }
+
+    #[test]
+    fn keep_monthly_buckets_by_calendar_month() {
+        let backups = series(&[
+            "2024-03-10T00:00:00Z",
+            "2024-03-01T00:00:00Z",
+            "2024-02-15T00:00:00Z",
+            "2024-02-01T00:00:00Z",
+            "2023-12-20T00:00:00Z",
+            "2023-10-05T00:00:00Z",
+        ]);
+        let policy = RetentionPolicy { keep_monthly: Some(3), ..Default::default() };
+        assert_eq!(expired_ids(&backups, &policy, NOW), ["b1", "b3", "b5"]);
+    }
+
+    #[test]
+    fn rules_are_combined() {
+        let backups = series(&[
+            "2024-03-15T10:00:00Z",
+            "2024-03-15T09:00:00Z",
+            "2024-03-14T09:00:00Z",
+            "2024-01-10T00:00:00Z",
+            "2023-06-01T00:00:00Z",
+        ]);
+        let policy = RetentionPolicy {
+            keep_last: Some(2),
+            keep_daily: Some(2),
+            keep_monthly: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(expired_ids(&backups, &policy, NOW), ["b4"]);
+    }
+
+    #[test]
+    fn pinned_backups_are_kept_but_count_towards_the_size_limit() {
+        let mut backups = series(&["2024-03-15T10:00:00Z", "2024-03-14T10:00:00Z", "2024-03-13T10:00:00Z"]);
+        backups.push(backup("pinned", "manual", "2020-01-01T00:00:00Z", 10, true));
+
+        let policy = RetentionPolicy { keep_last: Some(1), ..Default::default() };
+        assert_eq!(expired_ids(&backups, &policy, NOW), ["b1", "b2"]);
+
+        let policy = RetentionPolicy { max_total_size: Some(25), ..Default::default() };
+        assert_eq!(expired_ids(&backups, &policy, NOW), ["b1", "b2"]);
+    }
+
+    #[test]
+    fn size_limit_drops_the_oldest_but_never_the_newest() {
+        let backups = series(&[
+            "2024-03-15T10:00:00Z",
+            "2024-03-14T10:00:00Z",
+            "2024-03-13T10:00:00Z",
+            "2024-03-12T10:00:00Z",
+        ]);
+        let policy = RetentionPolicy { max_total_size: Some(25), ..Default::default() };
+        assert_eq!(expired_ids(&backups, &policy, NOW), ["b2", "b3"]);
+
+        let policy = RetentionPolicy { max_total_size: Some(0), ..Default::default() };
+        assert_eq!(expired_ids(&backups, &policy, NOW), ["b1", "b2", "b3"]);
+    }
+
+    #[test]
+    fn other_profiles_are_left_alone() {
+        let backups = vec![
+            backup("m0", "manual", "2024-03-15T10:00:00Z", 10, false),
+            backup("s0", "scheduled", "2024-03-15T09:00:00Z", 10, false),
+            backup("m1", "manual", "2024-03-14T10:00:00Z", 10, false),
+            backup("s1", "scheduled", "2024-03-14T09:00:00Z", 10, false),
+            backup("m2", "manual", "2024-03-13T10:00:00Z", 10, false),
+        ];
+        let policy = RetentionPolicy { keep_last: Some(1), max_total_size: Some(10), ..Default::default() };
+        assert_eq!(expired_ids(&backups, &policy, NOW), ["m1", "m2"]);
+    }
+
+    // A zip with the given entries and, if set, this manifest content
+    fn write_zip(path: &Path, entries: &[(&str, &str)], manifest: Option<&[u8]>) {
+        let mut zip = ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, content) in entries {
+            zip.start_file(*name, archive::zip_options(None, false)).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        if let Some(manifest) = manifest {
+            zip.start_file(ZIP_MANIFEST_ENTRY, archive::zip_options(None, false)).unwrap();
+            zip.write_all(manifest).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn manifest_for(entries: &[(&str, &str)]) -> Vec<u8> {
+        let manifest = Manifest {
+            id: "exported".to_string(),
+            created_at: "2024-03-15T10:00:00Z".to_string(),
+            entries: entries
+                .iter()
+                .map(|(path, content)| ManifestEntry {
+                    path: path.to_string(),
+                    is_dir: false,
+                    size: content.len() as u64,
+                    sha256: Some(hex::encode(Sha256::digest(content.as_bytes()))),
+                    mode: None,
+                })
+                .collect(),
+        };
+        serde_json::to_vec(&manifest).unwrap()
+    }
+
+    #[test]
+    fn exported_backups_pass_the_import_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = dir.path().join("server");
+        fs::create_dir_all(server.join("universe")).unwrap();
+        fs::write(server.join("universe/world.dat"), "world").unwrap();
+        fs::write(server.join("config.json"), "{}").unwrap();
+        let exported = dir.path().join("exported.zip");
+        zip_directory(&server, &exported, "exported", "2024-03-15T10:00:00Z").unwrap();
+
+        check_import("imported", &exported).unwrap();
+    }
+
+    #[test]
+    fn archives_without_a_manifest_are_checked_by_crc_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("other-tool.zip");
+        write_zip(&source, &[("world.dat", "world")], None);
+        check_import("imported", &source).unwrap();
+    }
+
+    #[test]
+    fn checksum_mismatches_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("tampered.zip");
+        let manifest = manifest_for(&[("world.dat", "world"), ("config.json", "{}")]);
+        write_zip(&source, &[("world.dat", "changed"), ("config.json", "{}")], Some(&manifest));
+
+        let error = check_import("imported", &source).unwrap_err();
+        assert!(error.contains("0 missing, 1 corrupt"), "{}", error);
+        assert!(error.contains("world.dat: hash mismatch"), "{}", error);
+    }
+
+    #[test]
+    fn missing_entries_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("partial.zip");
+        let manifest = manifest_for(&[("world.dat", "world"), ("config.json", "{}")]);
+        write_zip(&source, &[("config.json", "{}")], Some(&manifest));
+
+        let error = check_import("imported", &source).unwrap_err();
+        assert!(error.contains("1 missing, 0 corrupt"), "{}", error);
+        assert!(error.contains("world.dat: missing"), "{}", error);
+    }
+
+    #[test]
+    fn unreadable_manifests_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("garbled.zip");
+        write_zip(&source, &[("world.dat", "world")], Some(b"{not json"));
+
+        let error = check_import("imported", &source).unwrap_err();
+        assert!(error.contains(ZIP_MANIFEST_ENTRY), "{}", error);
+        assert!(!verify_zip("stored", &source).ok);
+    }
+
+    #[test]
+    fn backup_ids_cannot_leave_the_backups_directory() {
+        for id in ["1710496800_nightly", "1710496800_a.b", "imported"] {
+            assert!(is_plain_backup_id(id), "{}", id);
+        }
+        for id in ["", ".", "..", "../../../etc/passwd", "a/b", "a\\b", "/tmp/x", "./x"] {
+            assert!(!is_plain_backup_id(id), "{}", id);
+            assert_eq!(locate_backup(id).err().unwrap(), format!("Invalid backup id: {}", id));
+        }
+    }
+
+    #[test]
+    fn backup_names_become_plain_ids() {
+        for name in ["../../outside", "a/b\\c", "..", "nightly backup"] {
+            let id = format!("1710496800_{}", sanitize_backup_name(name));
+            assert!(is_plain_backup_id(&id), "{}", id);
+            assert!(!id.contains(".."), "{}", id);
+        }
     }
-    
-    fs::remove_file(&backup_file).map_err(|e| e.to_string())?;
-    Ok(true)
 }
@@ -2,6 +2,7 @@ use serde_json::{json, Value};
 use rusqlite::Connection;
 use tauri::State;
 use crate::AppState;
+use crate::services::audit_log;
 
 #[derive(serde::Serialize, serde::Deserialize, Clone)]
 struct DiscordConfig {
@@ -20,7 +21,12 @@ fn get_db_connection(state: &State<'_, AppState>) -> Result<Connection, String>
 
 #[tauri::command]
 pub async fn get_discord_config(state: State<'_, AppState>) -> Result<Value, String> {
-    let conn = get_db_connection(&state)?;
+    let db_path = state.db_path.lock().unwrap().clone();
+    load_discord_config(&db_path)
+}
+
+fn load_discord_config(db_path: &str) -> Result<Value, String> {
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
     
     // Try to get config from database
     let config_str: Result<String, _> = conn.query_row(
@@ -49,15 +55,19 @@ pub async fn get_discord_config(state: State<'_, AppState>) -> Result<Value, Str
 
 #[tauri::command]
 pub async fn save_discord_config(config: Value, state: State<'_, AppState>) -> Result<bool, String> {
-    let conn = get_db_connection(&state)?;
-    let config_str = serde_json::to_string(&config).map_err(|e| e.to_string())?;
-    
-    conn.execute(
-        "INSERT OR REPLACE INTO config (key, value) VALUES ('discord_config', ?1)",
-        rusqlite::params![config_str],
-    ).map_err(|e| e.to_string())?;
-    
-    Ok(true)
+    let db_path = state.db_path.lock().unwrap().clone();
+    let result = (|| {
+        let conn = get_db_connection(&state)?;
+        let config_str = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+        
+        conn.execute(
+            "INSERT OR REPLACE INTO config (key, value) VALUES ('discord_config', ?1)",
+            rusqlite::params![config_str],
+        ).map_err(|e| e.to_string())?;
+        
+        Ok(true)
+    })();
+    audit_log::audited(&db_path, "discord:configure", None, None, result)
 }
 
 #[tauri::command]
@@ -144,3 +154,15 @@ pub async fn notify_server_event(
     send_discord_message(&config.webhook_url, title, message, color).await?;
     Ok(())
 }
+
+// Sends a backup failure alert whenever the webhook is enabled
+pub async fn notify_backup_failure(db_path: &str, message: &str) -> Result<(), String> {
+    let config: DiscordConfig = serde_json::from_value(load_discord_config(db_path)?).map_err(|e| e.to_string())?;
+    
+    if !config.enabled || config.webhook_url.is_empty() {
+        return Ok(());
+    }
+    
+    send_discord_message(&config.webhook_url, "⚠️ Backup Verification Failed", message, 0xED4245).await?;
+    Ok(())
+}
@@ -1,5 +1,47 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::cmp::Ordering;
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use chrono::{DateTime, Utc};
+use rusqlite::Connection;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use tauri::{AppHandle, Emitter, State};
+use crate::AppState;
+use crate::services::audit_log;
+use crate::services::file_edit::{self, EditError, FileVersion, History, Revision, VersionedFile};
+use crate::services::file_ops::{self, BatchResult, DeletePlan, Trash, TrashEntry};
+use crate::services::file_transfer::{self, DownloadInfo, UploadSession};
+use crate::services::file_watcher::{self, FileWatcher, IgnoreRules};
+use crate::services::server_service::ServerService;
+use crate::utils::archive::{self, ArchiveError, ArchiveFormat, ExtractOptions, ExtractReport, OnConflict};
+use crate::utils::glob::glob_match;
+use crate::utils::sandbox::Sandbox;
+
+// Extensions the editor opens as text, carried over from the old `files:is-editable`
+const EDITABLE_EXTENSIONS: &[&str] = &[
+    "json", "txt", "md", "yaml", "yml", "xml", "properties",
+    "conf", "config", "ini", "log", "sh", "bat", "js", "ts",
+    "toml", "cfg", "csv", "html", "css",
+];
+
+// Files without a known extension are sniffed as text up to this size
+const MAX_SNIFF_SIZE: u64 = 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    Directory,
+    Text,
+    Image,
+    Audio,
+    Archive,
+    Executable,
+    Binary,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FileInfo {
@@ -8,54 +50,1043 @@ pub struct FileInfo {
     pub is_dir: bool,
     pub size: u64,
     pub modified: Option<String>,
+    pub created: Option<String>,
+    /// `rwxr-xr-x` style on Unix, `r--`/`rw-` elsewhere
+    pub permissions: String,
+    pub readonly: bool,
+    pub is_symlink: bool,
+    pub symlink_target: Option<String>,
+    pub extension: Option<String>,
+    pub mime_type: Option<String>,
+    pub kind: FileKind,
+    pub is_editable: bool,
+    pub is_protected: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Modified,
+    Created,
+    Kind,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ListOptions {
+    pub sort_by: SortKey,
+    pub descending: bool,
+    pub directories_first: bool,
+    pub offset: usize,
+    /// Page size; `None` returns everything after `offset`
+    pub limit: Option<usize>,
+}
+
+impl Default for ListOptions {
+    fn default() -> Self {
+        ListOptions {
+            sort_by: SortKey::Name,
+            descending: false,
+            directories_first: true,
+            offset: 0,
+            limit: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SearchQuery {
+    /// Directory to search from; the server root when empty
+    pub root: String,
+    /// Matched against file names, or against relative paths if it contains `/`
+    pub name_glob: Option<String>,
+    pub content: Option<String>,
+    pub case_sensitive: bool,
+    pub max_results: usize,
+    pub max_depth: usize,
+    /// Larger files are skipped by content search
+    pub max_file_size: u64,
+}
+
+impl Default for SearchQuery {
+    fn default() -> Self {
+        SearchQuery {
+            root: String::new(),
+            name_glob: None,
+            content: None,
+            case_sensitive: false,
+            max_results: 200,
+            max_depth: 16,
+            max_file_size: 5 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ContentMatch {
+    pub line: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub file: FileInfo,
+    pub matches: Vec<ContentMatch>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct FilesConfig {
+    /// Paths relative to the server directory that file commands may not modify or delete
+    pub protected_paths: Vec<String>,
+    /// Trashed items older than this are purged; 0 keeps them until purged by hand
+    pub trash_retention_days: u32,
+    /// Earlier versions kept per edited file; 0 disables edit history
+    pub history_limit: usize,
+    /// Paths the change watcher does not report; see `IgnoreRules`
+    pub watch_ignore: Vec<String>,
+}
+
+impl Default for FilesConfig {
+    fn default() -> Self {
+        FilesConfig {
+            protected_paths: Vec::new(),
+            trash_retention_days: 7,
+            history_limit: 20,
+            watch_ignore: file_watcher::DEFAULT_IGNORE.iter().map(|r| r.to_string()).collect(),
+        }
+    }
+}
+
+pub fn load_files_config(db_path: &str) -> Result<FilesConfig, String> {
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    let config_str: Result<String, _> = conn.query_row(
+        "SELECT value FROM config WHERE key = 'files_config'",
+        [],
+        |row| row.get(0)
+    );
+
+    match config_str {
+        Ok(json_str) => serde_json::from_str(&json_str).map_err(|e| e.to_string()),
+        Err(_) => Ok(FilesConfig::default()),
+    }
+}
+
+/// Builds the sandbox every file command resolves its paths through.
+pub fn open_sandbox(db_path: &str) -> Result<Sandbox, String> {
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let server_path: String = conn
+        .query_row(
+            "SELECT value FROM server_config WHERE key = 'server_path'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|_| "Server path not configured".to_string())?;
+
+    let config = load_files_config(db_path)?;
+    Sandbox::new(std::path::Path::new(&server_path), config.protected_paths).map_err(|e| e.to_string())
+}
+
+fn sandbox(state: &State<'_, AppState>) -> Result<Sandbox, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    open_sandbox(&db_path)
+}
+
+// One audit entry per item, so each path shows up with its own outcome
+fn audit_batch(db_path: &str, action: &str, results: &[BatchResult], detail: Option<&str>) {
+    for result in results {
+        audit_log::record(db_path, action, Some(&result.path), detail, result.error.as_deref());
+    }
+}
+
+fn get_trash_dir() -> Result<PathBuf, String> {
+    let home = std::env::var("HOME").map_err(|_| "HOME not set".to_string())?;
+    Ok(PathBuf::from(home)
+        .join(".local")
+        .join("share")
+        .join("com.hytale.servermanager")
+        .join("trash"))
+}
+
+pub fn open_trash() -> Result<Trash, String> {
+    Trash::open(get_trash_dir()?).map_err(|e| e.to_string())
+}
+
+fn get_history_dir() -> Result<PathBuf, String> {
+    let home = std::env::var("HOME").map_err(|_| "HOME not set".to_string())?;
+    Ok(PathBuf::from(home)
+        .join(".local")
+        .join("share")
+        .join("com.hytale.servermanager")
+        .join("file_history"))
+}
+
+pub fn open_history(db_path: &str) -> Result<History, String> {
+    let limit = load_files_config(db_path)?.history_limit;
+    History::open(get_history_dir()?, limit).map_err(|e| e.to_string())
+}
+
+/// Watches the server directory and emits batched `files:changed` events,
+/// moving to the new directory whenever the server path is changed and
+/// restarting with the new ignore rules when `files_config` is saved.
+pub fn spawn_file_watcher(db_path: String, service: Arc<ServerService>, app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut paths = service.subscribe_server_path();
+        let mut server_path = service.get_server_path().await.ok().flatten();
+
+        loop {
+            // Replaced on every path change; dropping the old one stops it
+            let _watcher = server_path.as_deref().and_then(|path| {
+                let ignore = load_files_config(&db_path).map(|c| c.watch_ignore).unwrap_or_default();
+                let app_handle = app_handle.clone();
+                let started = FileWatcher::start(Path::new(path), IgnoreRules::new(&ignore), move |changes| {
+                    let _ = app_handle.emit("files:changed", serde_json::json!({ "changes": changes }));
+                });
+                match started {
+                    Ok(watcher) => {
+                        eprintln!("[FILES] Watching {}", watcher.root().display());
+                        Some(watcher)
+                    }
+                    Err(e) => {
+                        eprintln!("[FILES] Failed to watch {}: {}", path, e);
+                        None
+                    }
+                }
+            });
+
+            if paths.changed().await.is_err() {
+                break;
+            }
+            server_path = paths.borrow_and_update().clone();
+        }
+    });
+}
+
+/// Purges old trash items hourly according to `files_config`.
+pub fn spawn_trash_purger(db_path: String) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let days = load_files_config(&db_path).map(|c| c.trash_retention_days).unwrap_or(7);
+            if days > 0 {
+                let cutoff = Utc::now() - chrono::Duration::days(days as i64);
+                match open_trash().and_then(|trash| trash.purge_before(cutoff).map_err(|e| e.to_string())) {
+                    Ok(0) => {}
+                    Ok(purged) => eprintln!("[FILES] Purged {} items from trash", purged),
+                    Err(e) => eprintln!("[FILES] Trash purge failed: {}", e),
+                }
+            }
+            tokio::time::sleep(tokio::time::Duration::from_secs(3600)).await;
+        }
+    });
+}
+
+fn format_time(time: std::io::Result<SystemTime>) -> Option<String> {
+    time.ok().map(|t| DateTime::<Utc>::from(t).to_rfc3339())
+}
+
+#[cfg(unix)]
+fn format_permissions(metadata: &fs::Metadata) -> String {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = metadata.permissions().mode();
+    (0..9)
+        .map(|i| {
+            let bit = 1 << (8 - i);
+            match (mode & bit != 0, i % 3) {
+                (false, _) => '-',
+                (true, 0) => 'r',
+                (true, 1) => 'w',
+                (true, _) => 'x',
+            }
+        })
+        .collect()
+}
+
+#[cfg(not(unix))]
+fn format_permissions(metadata: &fs::Metadata) -> String {
+    if metadata.permissions().readonly() { "r--" } else { "rw-" }.to_string()
+}
+
+fn mime_type(extension: &str) -> Option<&'static str> {
+    let mime = match extension {
+        "json" => "application/json",
+        "txt" | "log" | "conf" | "config" | "ini" | "cfg" => "text/plain",
+        "md" => "text/markdown",
+        "yaml" | "yml" => "application/yaml",
+        "toml" => "application/toml",
+        "xml" => "application/xml",
+        "properties" => "text/x-java-properties",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "ts" => "application/typescript",
+        "sh" => "application/x-sh",
+        "bat" | "cmd" => "application/x-msdos-program",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "zip" => "application/zip",
+        "gz" | "tgz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "jar" => "application/java-archive",
+        "exe" | "dll" => "application/vnd.microsoft.portable-executable",
+        "so" => "application/x-sharedlib",
+        "pdf" => "application/pdf",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+fn kind_for(extension: &str, mime: Option<&str>) -> FileKind {
+    match (extension, mime) {
+        ("zip" | "gz" | "tgz" | "tar" | "jar", _) => FileKind::Archive,
+        ("exe" | "dll" | "so", _) => FileKind::Executable,
+        (_, Some(m)) if m.starts_with("image/") => FileKind::Image,
+        (_, Some(m)) if m.starts_with("audio/") => FileKind::Audio,
+        _ if EDITABLE_EXTENSIONS.contains(&extension) => FileKind::Text,
+        (_, Some(m)) if m.starts_with("text/") => FileKind::Text,
+        _ => FileKind::Binary,
+    }
+}
+
+// Treats a file as text if its first block has no NUL bytes and is valid UTF-8
+fn looks_like_text(path: &Path) -> bool {
+    let mut buffer = [0u8; 4096];
+    let read = match fs::File::open(path).and_then(|mut f| f.read(&mut buffer)) {
+        Ok(read) => read,
+        Err(_) => return false,
+    };
+    let sample = &buffer[..read];
+    if sample.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => true,
+        // The block may end in the middle of a multi-byte character
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+fn file_info(sandbox: &Sandbox, path: &Path) -> Result<FileInfo, String> {
+    let link_metadata = fs::symlink_metadata(path).map_err(|e| e.to_string())?;
+    let is_symlink = link_metadata.file_type().is_symlink();
+    // Dangling links are described by the link itself
+    let metadata = if is_symlink { fs::metadata(path).unwrap_or(link_metadata) } else { link_metadata };
+
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .filter(|_| !metadata.is_dir());
+    let mime = extension.as_deref().and_then(mime_type);
+
+    let (kind, is_editable) = if metadata.is_dir() {
+        (FileKind::Directory, false)
+    } else {
+        let kind = match extension.as_deref() {
+            Some(ext) if mime.is_some() || EDITABLE_EXTENSIONS.contains(&ext) => kind_for(ext, mime),
+            _ if metadata.len() <= MAX_SNIFF_SIZE && looks_like_text(path) => FileKind::Text,
+            _ => FileKind::Binary,
+        };
+        (kind, kind == FileKind::Text)
+    };
+
+    Ok(FileInfo {
+        name,
+        path: path.to_string_lossy().to_string(),
+        is_dir: metadata.is_dir(),
+        size: metadata.len(),
+        modified: format_time(metadata.modified()),
+        created: format_time(metadata.created()),
+        permissions: format_permissions(&metadata),
+        readonly: metadata.permissions().readonly(),
+        is_symlink,
+        symlink_target: if is_symlink {
+            fs::read_link(path).ok().map(|t| t.to_string_lossy().to_string())
+        } else {
+            None
+        },
+        extension,
+        mime_type: mime.map(|m| m.to_string()).or_else(|| is_editable.then(|| "text/plain".to_string())),
+        kind,
+        is_editable,
+        is_protected: sandbox.is_protected(path),
+    })
+}
+
+fn compare_files(a: &FileInfo, b: &FileInfo, options: &ListOptions) -> Ordering {
+    if options.directories_first && a.is_dir != b.is_dir {
+        return b.is_dir.cmp(&a.is_dir);
+    }
+
+    let by_name = || a.name.to_lowercase().cmp(&b.name.to_lowercase());
+    let ordering = match options.sort_by {
+        SortKey::Name => by_name(),
+        SortKey::Size => a.size.cmp(&b.size).then_with(by_name),
+        SortKey::Modified => a.modified.cmp(&b.modified).then_with(by_name),
+        SortKey::Created => a.created.cmp(&b.created).then_with(by_name),
+        SortKey::Kind => a.extension.cmp(&b.extension).then_with(by_name),
+    };
+    if options.descending { ordering.reverse() } else { ordering }
 }
 
 #[tauri::command]
-pub async fn list_files(dir_path: String) -> Result<Vec<FileInfo>, String> {
-    use std::fs;
-    
-    let path = PathBuf::from(&dir_path);
+pub async fn list_files(
+    dir_path: String,
+    options: Option<ListOptions>,
+    state: State<'_, AppState>,
+) -> Result<Vec<FileInfo>, String> {
+    let options = options.unwrap_or_default();
+    let sandbox = sandbox(&state)?;
+    let path = sandbox.resolve(&dir_path).map_err(|e| e.to_string())?;
     if !path.exists() {
         return Err("Directory does not exist".to_string());
     }
-    
+
     let mut files = Vec::new();
-    
+
     for entry in fs::read_dir(path).map_err(|e| e.to_string())? {
-        let entry = entry.map_err(|e| e.to_string())?;
-        let metadata = entry.metadata().map_err(|e| e.to_string())?;
-        
-        files.push(FileInfo {
-            name: entry.file_name().to_string_lossy().to_string(),
-            path: entry.path().to_string_lossy().to_string(),
-            is_dir: metadata.is_dir(),
-            size: metadata.len(),
-            modified: None,
-        });
+        // An entry that vanished or cannot be read is left out rather than failing the listing
+        match entry.map_err(|e| e.to_string()).and_then(|entry| file_info(&sandbox, &entry.path())) {
+            Ok(info) => files.push(info),
+            Err(e) => eprintln!("[FILES] Skipping unreadable entry in {}: {}", dir_path, e),
+        }
     }
-    
-    Ok(files)
+
+    files.sort_by(|a, b| compare_files(a, b, &options));
+    let page = files
+        .into_iter()
+        .skip(options.offset)
+        .take(options.limit.unwrap_or(usize::MAX))
+        .collect();
+
+    Ok(page)
+}
+
+fn search_content(path: &Path, needle: &str, case_sensitive: bool) -> Vec<ContentMatch> {
+    const MAX_MATCHES_PER_FILE: usize = 20;
+    const MAX_LINE_LENGTH: usize = 300;
+
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut matches = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        // Stop at the first line that is not valid UTF-8
+        let Ok(line) = line else { break };
+        let found = if case_sensitive {
+            line.contains(needle)
+        } else {
+            line.to_lowercase().contains(needle)
+        };
+        if found {
+            matches.push(ContentMatch {
+                line: index + 1,
+                text: line.chars().take(MAX_LINE_LENGTH).collect(),
+            });
+            if matches.len() >= MAX_MATCHES_PER_FILE {
+                break;
+            }
+        }
+    }
+    matches
 }
 
 #[tauri::command]
-pub async fn read_file(file_path: String) -> Result<String, String> {
-    std::fs::read_to_string(&file_path).map_err(|e| e.to_string())
+pub async fn search_files(query: SearchQuery, state: State<'_, AppState>) -> Result<Vec<SearchHit>, String> {
+    if query.name_glob.is_none() && query.content.is_none() {
+        return Err("Provide a name pattern or content to search for".to_string());
+    }
+
+    let sandbox = sandbox(&state)?;
+    let root = sandbox.resolve(&query.root).map_err(|e| e.to_string())?;
+    let normalize = |s: &str| if query.case_sensitive { s.to_string() } else { s.to_lowercase() };
+    let pattern: Option<Vec<char>> = query.name_glob.as_deref().map(|g| normalize(g).chars().collect());
+    let match_path = query.name_glob.as_deref().is_some_and(|g| g.contains('/'));
+    let needle = query.content.as_deref().map(normalize);
+
+    let mut hits = Vec::new();
+    let walker = walkdir::WalkDir::new(&root)
+        .min_depth(1)
+        .max_depth(query.max_depth)
+        .follow_links(false);
+
+    for entry in walker.into_iter().filter_map(|e| e.ok()) {
+        if hits.len() >= query.max_results {
+            break;
+        }
+
+        if let Some(pattern) = &pattern {
+            let subject = if match_path {
+                sandbox.relative(entry.path())
+            } else {
+                entry.file_name().to_string_lossy().to_string()
+            };
+            if !glob_match(pattern, &normalize(&subject).chars().collect::<Vec<_>>()) {
+                continue;
+            }
+        }
+
+        let info = match file_info(&sandbox, entry.path()) {
+            Ok(info) => info,
+            Err(e) => {
+                eprintln!("[FILES] Skipping unreadable entry {}: {}", entry.path().display(), e);
+                continue;
+            }
+        };
+        let matches = match &needle {
+            Some(needle) => {
+                if !entry.file_type().is_file() || !info.is_editable || info.size > query.max_file_size {
+                    continue;
+                }
+                let matches = search_content(entry.path(), needle, query.case_sensitive);
+                if matches.is_empty() {
+                    continue;
+                }
+                matches
+            }
+            None => Vec::new(),
+        };
+
+        hits.push(SearchHit { file: info, matches });
+    }
+
+    Ok(hits)
+}
+
+/// Error from `write_file` and `revert_file`, tagged so the editor can offer a merge on conflict.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum WriteFileError {
+    Conflict { message: String, current: Option<VersionedFile> },
+    Failed { message: String },
+}
+
+impl std::fmt::Display for WriteFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteFileError::Conflict { message, .. } | WriteFileError::Failed { message } => f.write_str(message),
+        }
+    }
+}
+
+impl From<EditError> for WriteFileError {
+    fn from(error: EditError) -> Self {
+        let message = error.to_string();
+        match error {
+            EditError::Conflict { current, .. } => WriteFileError::Conflict { message, current: current.map(|c| *c) },
+            _ => WriteFileError::Failed { message },
+        }
+    }
+}
+
+impl From<String> for WriteFileError {
+    fn from(message: String) -> Self {
+        WriteFileError::Failed { message }
+    }
 }
 
 #[tauri::command]
-pub async fn write_file(file_path: String, content: String) -> Result<bool, String> {
-    std::fs::write(&file_path, content).map_err(|e| e.to_string())?;
-    Ok(true)
+pub async fn get_file_info(path: String, state: State<'_, AppState>) -> Result<FileInfo, String> {
+    let sandbox = sandbox(&state)?;
+    let target = sandbox.resolve_entry(&path).map_err(|e| e.to_string())?;
+    file_info(&sandbox, &target)
 }
 
+/// Returns the content with a version token to pass back to `write_file`.
 #[tauri::command]
-pub async fn delete_file(file_path: String) -> Result<bool, String> {
-    std::fs::remove_file(&file_path).map_err(|e| e.to_string())?;
-    Ok(true)
+pub async fn read_file(file_path: String, state: State<'_, AppState>) -> Result<VersionedFile, String> {
+    file_edit::read(&sandbox(&state)?, &file_path).map_err(|e| e.to_string())
+}
+
+/// With `expected_version`, the write is rejected if the file changed since that version was read.
+#[tauri::command]
+pub async fn write_file(
+    file_path: String,
+    content: String,
+    expected_version: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<FileVersion, WriteFileError> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let sandbox = open_sandbox(&db_path)?;
+    let history = open_history(&db_path)?;
+    let result = file_edit::write(&sandbox, Some(&history), &file_path, &content, expected_version.as_deref())
+        .map_err(WriteFileError::from);
+    audit_log::audited(&db_path, "files:write", Some(&file_path), None, result)
+}
+
+/// Earlier versions of a file, newest first.
+#[tauri::command]
+pub async fn list_file_revisions(file_path: String, state: State<'_, AppState>) -> Result<Vec<Revision>, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let sandbox = open_sandbox(&db_path)?;
+    let relative = sandbox.relative(&sandbox.resolve(&file_path).map_err(|e| e.to_string())?);
+    open_history(&db_path)?.list(&relative).map_err(|e| e.to_string())
 }
 
+/// Unified diff from a revision to the current content, or to the revision `against`.
 #[tauri::command]
-pub async fn create_dir(dir_path: String) -> Result<bool, String> {
-    std::fs::create_dir_all(&dir_path).map_err(|e| e.to_string())?;
+pub async fn diff_file_revision(
+    file_path: String,
+    revision_id: String,
+    against: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let sandbox = open_sandbox(&db_path)?;
+    let history = open_history(&db_path)?;
+    file_edit::diff(&sandbox, &history, &file_path, &revision_id, against.as_deref()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn revert_file(
+    file_path: String,
+    revision_id: String,
+    expected_version: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<FileVersion, WriteFileError> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let sandbox = open_sandbox(&db_path)?;
+    let history = open_history(&db_path)?;
+    let result = file_edit::revert(&sandbox, &history, &file_path, &revision_id, expected_version.as_deref())
+        .map_err(WriteFileError::from);
+    let detail = format!("to revision {}", revision_id);
+    audit_log::audited(&db_path, "files:revert", Some(&file_path), Some(&detail), result)
+}
+
+/// Moves a single file to the trash. Directories go through `request_delete` and `delete_files`.
+#[tauri::command]
+pub async fn delete_file(file_path: String, state: State<'_, AppState>) -> Result<bool, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let sandbox = open_sandbox(&db_path)?;
+    let result = file_ops::delete(&sandbox, Some(&open_trash()?), &file_path, None)
+        .map(|_| true)
+        .map_err(|e| e.to_string());
+    audit_log::audited(&db_path, "files:delete", Some(&file_path), None, result)
+}
+
+#[tauri::command]
+pub async fn create_dir(dir_path: String, state: State<'_, AppState>) -> Result<bool, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let path = open_sandbox(&db_path)?.resolve_writable(&dir_path).map_err(|e| e.to_string())?;
+    let result = fs::create_dir_all(&path).map(|_| true).map_err(|e| e.to_string());
+    audit_log::audited(&db_path, "files:mkdir", Some(&dir_path), None, result)
+}
+
+#[tauri::command]
+pub async fn begin_file_upload(
+    path: String,
+    size: u64,
+    sha256: String,
+    overwrite: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<UploadSession, String> {
+    let sandbox = sandbox(&state)?;
+    file_transfer::begin_upload(&sandbox, &path, size, &sha256, overwrite.unwrap_or(false)).map_err(|e| e.to_string())
+}
+
+/// `data` is base64; returns the number of bytes received so far.
+#[tauri::command]
+pub async fn upload_file_chunk(
+    path: String,
+    upload_id: String,
+    size: u64,
+    offset: u64,
+    data: String,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<u64, String> {
+    let bytes = BASE64.decode(data.as_bytes()).map_err(|e| e.to_string())?;
+    let sandbox = sandbox(&state)?;
+    let received = file_transfer::write_chunk(&sandbox, &path, &upload_id, size, offset, &bytes)
+        .map_err(|e| e.to_string())?;
+
+    let _ = app_handle.emit("files:upload-progress", serde_json::json!({
+        "upload_id": upload_id,
+        "path": path,
+        "received": received,
+        "total": size,
+    }));
+    Ok(received)
+}
+
+#[tauri::command]
+pub async fn finish_file_upload(
+    path: String,
+    upload_id: String,
+    size: u64,
+    sha256: String,
+    state: State<'_, AppState>,
+) -> Result<FileInfo, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let sandbox = open_sandbox(&db_path)?;
+    let result = file_transfer::finish_upload(&sandbox, &path, &upload_id, size, &sha256).map_err(|e| e.to_string());
+    let detail = format!("{} bytes", size);
+    let target = audit_log::audited(&db_path, "files:upload", Some(&path), Some(&detail), result)?;
+    file_info(&sandbox, &target)
+}
+
+#[tauri::command]
+pub async fn abort_file_upload(path: String, upload_id: String, state: State<'_, AppState>) -> Result<bool, String> {
+    let sandbox = sandbox(&state)?;
+    file_transfer::abort_upload(&sandbox, &path, &upload_id).map_err(|e| e.to_string())?;
     Ok(true)
 }
+
+#[tauri::command]
+pub async fn begin_file_download(path: String, state: State<'_, AppState>) -> Result<DownloadInfo, String> {
+    let sandbox = sandbox(&state)?;
+    file_transfer::begin_download(&sandbox, &path).map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DownloadChunk {
+    pub offset: u64,
+    /// Base64 encoded; empty once `offset` reaches the end of the file
+    pub data: String,
+    pub length: usize,
+}
+
+#[tauri::command]
+pub async fn download_file_chunk(
+    path: String,
+    offset: u64,
+    length: Option<usize>,
+    total: Option<u64>,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<DownloadChunk, String> {
+    let sandbox = sandbox(&state)?;
+    let data = file_transfer::read_chunk(&sandbox, &path, offset, length.unwrap_or(file_transfer::CHUNK_SIZE))
+        .map_err(|e| e.to_string())?;
+
+    let _ = app_handle.emit("files:download-progress", serde_json::json!({
+        "path": path,
+        "sent": offset + data.len() as u64,
+        "total": total,
+    }));
+    Ok(DownloadChunk {
+        offset,
+        length: data.len(),
+        data: BASE64.encode(&data),
+    })
+}
+
+#[tauri::command]
+pub async fn get_files_config(state: State<'_, AppState>) -> Result<FilesConfig, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    load_files_config(&db_path)
+}
+
+#[tauri::command]
+pub async fn save_files_config(config: FilesConfig, state: State<'_, AppState>) -> Result<bool, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let result = (|| {
+        let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+        let config_str = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+
+        conn.execute(
+            "INSERT OR REPLACE INTO config (key, value) VALUES ('files_config', ?1)",
+            rusqlite::params![config_str],
+        ).map_err(|e| e.to_string())?;
+
+        Ok(true)
+    })();
+    if result.is_ok() {
+        // The watcher reads its ignore rules when it starts
+        if let Some(service) = state.server_service.lock().unwrap().clone() {
+            service.reload_server_path();
+        }
+    }
+    audit_log::audited(&db_path, "files:configure", None, None, result)
+}
+
+#[tauri::command]
+pub async fn rename_file(path: String, new_name: String, state: State<'_, AppState>) -> Result<FileInfo, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let sandbox = open_sandbox(&db_path)?;
+    let result = file_ops::rename(&sandbox, &path, &new_name).map_err(|e| e.to_string());
+    let detail = format!("to {}", new_name);
+    let target = audit_log::audited(&db_path, "files:rename", Some(&path), Some(&detail), result)?;
+    file_info(&sandbox, &target)
+}
+
+#[tauri::command]
+pub async fn move_files(
+    paths: Vec<String>,
+    destination: String,
+    overwrite: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<Vec<BatchResult>, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let sandbox = open_sandbox(&db_path)?;
+    let overwrite = overwrite.unwrap_or(false);
+
+    let results: Vec<BatchResult> = paths
+        .iter()
+        .map(|path| {
+            let result = file_ops::move_into(&sandbox, path, &destination, overwrite)
+                .map(|target| Some(sandbox.relative(&target)));
+            BatchResult::from_result(path, result)
+        })
+        .collect();
+    audit_batch(&db_path, "files:move", &results, Some(&format!("to {}", destination)));
+    Ok(results)
+}
+
+#[tauri::command]
+pub async fn copy_files(
+    paths: Vec<String>,
+    destination: String,
+    overwrite: Option<bool>,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<Vec<BatchResult>, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let sandbox = open_sandbox(&db_path)?;
+    let overwrite = overwrite.unwrap_or(false);
+
+    // Progress covers the whole batch
+    let total: u64 = paths
+        .iter()
+        .filter_map(|p| sandbox.resolve(p).ok())
+        .map(|p| file_ops::tree_size(&p).1)
+        .sum();
+    let mut done = 0;
+    let mut results = Vec::new();
+
+    for path in &paths {
+        let mut copied_here = 0;
+        let result = file_ops::copy_into(&sandbox, path, &destination, overwrite, &mut |copied| {
+            copied_here = copied;
+            let _ = app_handle.emit("files:copy-progress", serde_json::json!({
+                "path": path,
+                "copied": done + copied,
+                "total": total,
+            }));
+        })
+        .map(|target| Some(sandbox.relative(&target)));
+        done += copied_here;
+        results.push(BatchResult::from_result(path, result));
+    }
+
+    audit_batch(&db_path, "files:copy", &results, Some(&format!("to {}", destination)));
+    Ok(results)
+}
+
+/// First step of deleting directories: returns what would be removed and a
+/// short-lived token to pass to `delete_files`.
+#[tauri::command]
+pub async fn request_delete(paths: Vec<String>, state: State<'_, AppState>) -> Result<DeletePlan, String> {
+    let sandbox = sandbox(&state)?;
+    file_ops::request_deletion(&sandbox, &paths).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_files(
+    paths: Vec<String>,
+    confirmation_token: Option<String>,
+    permanent: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<Vec<BatchResult>, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let sandbox = open_sandbox(&db_path)?;
+    let permanent = permanent.unwrap_or(false);
+    let trash = if permanent { None } else { Some(open_trash()?) };
+
+    let results: Vec<BatchResult> = paths
+        .iter()
+        .map(|path| {
+            let result = file_ops::delete(&sandbox, trash.as_ref(), path, confirmation_token.as_deref());
+            BatchResult::from_result(path, result)
+        })
+        .collect();
+
+    if let Some(token) = &confirmation_token {
+        file_ops::consume_confirmation(token);
+    }
+    audit_batch(&db_path, "files:delete", &results, permanent.then_some("permanently"));
+    Ok(results)
+}
+
+#[tauri::command]
+pub async fn list_trash() -> Result<Vec<TrashEntry>, String> {
+    open_trash()?.list().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn restore_from_trash(
+    ids: Vec<String>,
+    overwrite: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<Vec<BatchResult>, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let sandbox = open_sandbox(&db_path)?;
+    let trash = open_trash()?;
+
+    let results: Vec<BatchResult> = ids
+        .iter()
+        .map(|id| {
+            let result = trash
+                .restore(&sandbox, id, overwrite.unwrap_or(false))
+                .map(|target| Some(sandbox.relative(&target)));
+            BatchResult::from_result(id, result)
+        })
+        .collect();
+    audit_batch(&db_path, "files:trash-restore", &results, None);
+    Ok(results)
+}
+
+/// Permanently removes the given trash items, or everything when `ids` is omitted.
+#[tauri::command]
+pub async fn purge_trash(ids: Option<Vec<String>>, state: State<'_, AppState>) -> Result<usize, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let trash = open_trash()?;
+    let ids = match ids {
+        Some(ids) => ids,
+        None => trash.list().map_err(|e| e.to_string())?.into_iter().map(|e| e.id).collect(),
+    };
+
+    for id in &ids {
+        let result = trash.remove(id).map_err(|e| e.to_string());
+        audit_log::audited(&db_path, "files:trash-purge", Some(id), None, result)?;
+    }
+    Ok(ids.len())
+}
+
+/// Extracts a zip or tar.gz inside the server directory. Without a
+/// destination, it goes into a folder named after the archive next to it.
+#[tauri::command]
+pub async fn extract_archive(
+    path: String,
+    destination: Option<String>,
+    on_conflict: Option<OnConflict>,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<ExtractReport, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let detail = destination.as_ref().map(|d| format!("to {}", d));
+    let result = extract_into(&db_path, &path, destination, on_conflict, &app_handle);
+    audit_log::audited(&db_path, "files:extract", Some(&path), detail.as_deref(), result)
+}
+
+fn extract_into(
+    db_path: &str,
+    path: &str,
+    destination: Option<String>,
+    on_conflict: Option<OnConflict>,
+    app_handle: &AppHandle,
+) -> Result<ExtractReport, String> {
+    let sandbox = open_sandbox(db_path)?;
+    let source = sandbox.resolve(path).map_err(|e| e.to_string())?;
+    let format = ArchiveFormat::from_path(&source).ok_or_else(|| ArchiveError::UnknownFormat.to_string())?;
+
+    let target = match destination {
+        Some(destination) => sandbox.resolve_writable(&destination),
+        None => {
+            let file_name = source.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            let parent = source.parent().map(|p| sandbox.relative(p)).unwrap_or_default();
+            sandbox.resolve_writable(&format!("{}/{}", parent, format.stem(&file_name)))
+        }
+    }
+    .map_err(|e| e.to_string())?;
+    fs::create_dir_all(&target).map_err(|e| e.to_string())?;
+
+    let allow = |dest: &Path| !sandbox.is_protected(dest);
+    let options = ExtractOptions {
+        on_conflict: on_conflict.unwrap_or_default(),
+        allow: &allow,
+        ..Default::default()
+    };
+    let report = archive::extract(&source, &target, &options, &mut |done, total| {
+        let _ = app_handle.emit("files:extract-progress", serde_json::json!({
+            "path": path,
+            "done": done,
+            "total": total,
+        }));
+    })
+    .map_err(|e| e.to_string())?;
+
+    eprintln!("[FILES] Extracted {} files from {}", report.files, path);
+    Ok(report)
+}
+
+/// Packs the selected files and folders into a new archive. The format
+/// defaults to the one matching `output`'s extension, then zip.
+#[tauri::command]
+pub async fn compress_paths(
+    paths: Vec<String>,
+    output: String,
+    format: Option<ArchiveFormat>,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<FileInfo, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let sandbox = open_sandbox(&db_path)?;
+    let detail = paths.join(", ");
+    let result = compress_into(&sandbox, &paths, &output, format, &app_handle);
+    let target = audit_log::audited(&db_path, "files:compress", Some(&output), Some(&detail), result)?;
+    file_info(&sandbox, &target)
+}
+
+fn compress_into(
+    sandbox: &Sandbox,
+    paths: &[String],
+    output: &str,
+    format: Option<ArchiveFormat>,
+    app_handle: &AppHandle,
+) -> Result<PathBuf, String> {
+    let sources = paths
+        .iter()
+        .map(|p| sandbox.resolve(p))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    if sources.is_empty() {
+        return Err("Nothing to compress".to_string());
+    }
+
+    let target = sandbox.resolve_writable(output).map_err(|e| e.to_string())?;
+    if target.exists() {
+        return Err(format!("Already exists: {}", output));
+    }
+    let format = format
+        .or_else(|| ArchiveFormat::from_path(&target))
+        .unwrap_or(ArchiveFormat::Zip);
+
+    // Write under a temporary name so a failed run leaves nothing behind
+    let partial = target.with_file_name(format!(
+        ".{}.part",
+        target.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+    ));
+    let result = archive::compress(&sources, &partial, format, std::slice::from_ref(&target), &mut |done, total| {
+        let _ = app_handle.emit("files:compress-progress", serde_json::json!({
+            "path": output,
+            "done": done,
+            "total": total,
+        }));
+    })
+    .map_err(|e| e.to_string())
+    .and_then(|files| fs::rename(&partial, &target).map(|_| files).map_err(|e| e.to_string()));
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+
+    eprintln!("[FILES] Compressed {} files into {}", result?, output);
+    Ok(target)
+}
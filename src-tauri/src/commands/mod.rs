@@ -7,3 +7,4 @@ pub mod backup;
 pub mod discord;
 pub mod remote;
 pub mod download;
+pub mod audit;
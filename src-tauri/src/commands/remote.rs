@@ -1,15 +1,49 @@
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use rusqlite::Connection;
-use tauri::State;
+use rusqlite::{Connection, OptionalExtension};
+use tauri::{AppHandle, State};
 use crate::AppState;
+use crate::services::audit_log::{self, AuditFilter, Channel};
+use crate::remote_access::api_tokens::{self, ApiToken};
+use crate::remote_access::audit::RemoteAuditEntry;
+use crate::remote_access::{self, IpBan, IpFilter, IpRules, RemoteLimits, RemoteSession};
+use crate::remote_access::permissions::{self, Permission};
+use crate::remote_access::roles::{self, Role};
+use crate::remote_access::tls::{self, CertificateInfo, TlsConfig, TlsMode};
 use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct RemoteUser {
     pub id: String,
     pub username: String,
+    /// Granted to this user on top of their roles
     pub permissions: Vec<String>,
+    /// Taken away from this user whatever their roles grant
+    pub denied_permissions: Vec<String>,
+    /// Role ids
+    pub roles: Vec<String>,
+    /// What the user may actually do, resolved by the backend
+    pub effective_permissions: Vec<String>,
+    pub disabled: bool,
+    pub created_at: Option<String>,
+    pub last_login_at: Option<String>,
+    pub last_login_ip: Option<String>,
+    /// Wrong passwords since the last successful login
+    pub failed_logins: u32,
+    pub last_failed_login_at: Option<String>,
+}
+
+const USER_COLUMNS: &str =
+    "id, username, permissions, disabled, created_at, last_login_at, last_login_ip, failed_logins, last_failed_login_at, denied_permissions";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PermissionDenial {
+    pub user_id: String,
+    pub username: String,
+    pub command: String,
+    pub permission: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: String,
 }
 
 fn get_db_connection(state: &State<'_, AppState>) -> Result<Connection, String> {
@@ -17,6 +51,88 @@ fn get_db_connection(state: &State<'_, AppState>) -> Result<Connection, String>
     Connection::open(db_path.as_str()).map_err(|e| e.to_string())
 }
 
+// Roles and effective permissions are filled in by `with_access`
+fn user_from_row(row: &rusqlite::Row) -> rusqlite::Result<RemoteUser> {
+    let perms_str: String = row.get(2)?;
+    let denied_str: String = row.get(9)?;
+    Ok(RemoteUser {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        permissions: serde_json::from_str(&perms_str).unwrap_or_default(),
+        denied_permissions: serde_json::from_str(&denied_str).unwrap_or_default(),
+        roles: Vec::new(),
+        effective_permissions: Vec::new(),
+        disabled: row.get(3)?,
+        created_at: row.get(4)?,
+        last_login_at: row.get(5)?,
+        last_login_ip: row.get(6)?,
+        failed_logins: row.get(7)?,
+        last_failed_login_at: row.get(8)?,
+    })
+}
+
+fn with_access(conn: &Connection, mut user: RemoteUser) -> Result<RemoteUser, String> {
+    user.roles = roles::user_roles(conn, &user.id)?;
+    user.effective_permissions = roles::effective_permissions(conn, &user.id)?;
+    Ok(user)
+}
+
+fn load_user(conn: &Connection, user_id: &str) -> Result<RemoteUser, String> {
+    let user = conn
+        .query_row(
+            &format!("SELECT {} FROM remote_users WHERE id = ?1", USER_COLUMNS),
+            [user_id],
+            user_from_row,
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "User not found".to_string())?;
+    with_access(conn, user)
+}
+
+fn ensure_user_exists(conn: &Connection, user_id: &str) -> Result<(), String> {
+    let exists: i64 = conn
+        .query_row("SELECT COUNT(*) FROM remote_users WHERE id = ?1", [user_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if exists == 0 {
+        return Err("User not found".to_string());
+    }
+    Ok(())
+}
+
+// User, role and access changes go to the audit log with their outcome
+fn audited<T>(
+    state: &State<'_, AppState>,
+    action: &str,
+    target: Option<&str>,
+    detail: Option<&str>,
+    result: Result<T, String>,
+) -> Result<T, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    audit_log::audited(&db_path, action, target, detail, result)
+}
+
+// Usernames read better in the audit log than ids
+fn user_label(state: &State<'_, AppState>, user_id: &str) -> String {
+    get_db_connection(state)
+        .and_then(|conn| {
+            conn.query_row("SELECT username FROM remote_users WHERE id = ?1", [user_id], |row| row.get(0))
+                .map_err(|e| e.to_string())
+        })
+        .unwrap_or_else(|_| user_id.to_string())
+}
+
+fn access_detail(permissions: &[String], denied: Option<&[String]>, roles: Option<&[String]>) -> String {
+    let mut parts = vec![format!("permissions: {}", permissions.join(", "))];
+    if let Some(denied) = denied {
+        parts.push(format!("denied: {}", denied.join(", ")));
+    }
+    if let Some(roles) = roles {
+        parts.push(format!("roles: {}", roles.join(", ")));
+    }
+    parts.join("; ")
+}
+
 #[tauri::command]
 pub async fn get_remote_config(state: State<'_, AppState>) -> Result<Value, String> {
     let conn = get_db_connection(&state)?;
@@ -35,7 +151,11 @@ pub async fn get_remote_config(state: State<'_, AppState>) -> Result<Value, Stri
             Ok(json!({
                 "enabled": false,
                 "port": 9999,
+                "bind_address": "0.0.0.0",
                 "require_auth": true,
+                "limits": RemoteLimits::default(),
+                "ip_rules": IpRules::default(),
+                "tls": TlsConfig::default(),
                 "ipv4": "",
                 "ipv6": "",
                 "tunnelUrl": "",
@@ -46,93 +166,298 @@ pub async fn get_remote_config(state: State<'_, AppState>) -> Result<Value, Stri
 }
 
 #[tauri::command]
-pub async fn set_remote_enabled(enabled: bool, state: State<'_, AppState>) -> Result<bool, String> {
-    let conn = get_db_connection(&state)?;
+pub async fn set_remote_enabled(enabled: bool, state: State<'_, AppState>, app_handle: AppHandle) -> Result<bool, String> {
+    let result = async {
+        let conn = get_db_connection(&state)?;
+
+        let current = get_remote_config(state.clone()).await?;
+        let mut merged = current.as_object().cloned().unwrap_or_default();
+
+        merged.insert("enabled".to_string(), Value::Bool(enabled));
+        if !merged.contains_key("port") {
+            merged.insert("port".to_string(), json!(9999));
+        }
+        if !merged.contains_key("require_auth") {
+            merged.insert("require_auth".to_string(), json!(true));
+        }
+        if !merged.contains_key("methods") {
+            merged.insert("methods".to_string(), json!(["ip", "tunnel"]));
+        }
 
-    let current = get_remote_config(state.clone()).await?;
-    let mut merged = current.as_object().cloned().unwrap_or_default();
+        let merged_value = Value::Object(merged);
+        let config_str = serde_json::to_string(&merged_value).map_err(|e| e.to_string())?;
 
-    merged.insert("enabled".to_string(), Value::Bool(enabled));
-    if !merged.contains_key("port") {
-        merged.insert("port".to_string(), json!(9999));
+        conn.execute(
+            "INSERT OR REPLACE INTO config (key, value) VALUES ('remote_config', ?1)",
+            rusqlite::params![config_str],
+        ).map_err(|e| e.to_string())?;
+
+        // Start or stop listening to match
+        remote_access::apply_config(&app_handle).await?;
+
+        Ok(true)
     }
-    if !merged.contains_key("require_auth") {
-        merged.insert("require_auth".to_string(), json!(true));
+    .await;
+    audited(&state, if enabled { "remote:enable" } else { "remote:disable" }, None, None, result)
+}
+
+#[tauri::command]
+pub async fn set_remote_config(config: Value, state: State<'_, AppState>, app_handle: AppHandle) -> Result<bool, String> {
+    let keys = config.as_object().map(|o| o.keys().cloned().collect::<Vec<_>>().join(", "));
+    let result = async {
+        let conn = get_db_connection(&state)?;
+
+        let current = get_remote_config(state.clone()).await?;
+        let mut merged = current.as_object().cloned().unwrap_or_default();
+
+        if let Some(obj) = config.as_object() {
+            for (key, value) in obj {
+                merged.insert(key.clone(), value.clone());
+            }
+        }
+
+        let merged_value = Value::Object(merged);
+        // Refuse rules and limits the server could not load
+        if let Some(limits) = merged_value.get("limits") {
+            serde_json::from_value::<RemoteLimits>(limits.clone())
+                .map_err(|e| e.to_string())
+                .and_then(|limits| limits.validate())
+                .map_err(|e| format!("Invalid limits: {}", e))?;
+        }
+        if let Some(rules) = merged_value.get("ip_rules") {
+            let rules: IpRules = serde_json::from_value(rules.clone()).map_err(|e| format!("Invalid IP rules: {}", e))?;
+            IpFilter::new(&rules)?;
+        }
+        if let Some(tls_config) = merged_value.get("tls") {
+            let tls_config: TlsConfig =
+                serde_json::from_value(tls_config.clone()).map_err(|e| format!("Invalid TLS settings: {}", e))?;
+            if tls_config.enabled && tls_config.mode == TlsMode::Custom {
+                let cert_path = tls_config.cert_path.as_deref().ok_or("No certificate file set")?;
+                tls::fingerprint(std::path::Path::new(cert_path))?;
+            }
+        }
+        let config_str = serde_json::to_string(&merged_value).map_err(|e| e.to_string())?;
+
+        conn.execute(
+            "INSERT OR REPLACE INTO config (key, value) VALUES ('remote_config', ?1)",
+            rusqlite::params![config_str],
+        ).map_err(|e| e.to_string())?;
+
+        // Restarts the server if the address or port changed
+        remote_access::apply_config(&app_handle).await?;
+
+        Ok(true)
     }
-    if !merged.contains_key("methods") {
-        merged.insert("methods".to_string(), json!(["ip", "tunnel"]));
+    .await;
+    audited(&state, "remote:config", None, keys.as_deref(), result)
+}
+
+#[tauri::command]
+pub async fn get_remote_status(state: State<'_, AppState>) -> Result<Value, String> {
+    let running = state.remote_server.lock().await;
+    Ok(match running.as_ref() {
+        Some(server) => json!({
+            "running": true,
+            "bind_address": server.settings().bind_address,
+            "port": server.settings().port,
+            "clients": server.client_count(),
+            "tls": server.settings().tls.is_some(),
+            "fingerprint": server.settings().tls.as_ref().and_then(|t| tls::fingerprint(&t.cert_path).ok()),
+        }),
+        None => json!({ "running": false, "clients": 0 }),
+    })
+}
+
+fn certificate_info(mode: TlsMode, cert_path: &std::path::Path) -> Result<CertificateInfo, String> {
+    Ok(CertificateInfo {
+        mode,
+        cert_path: cert_path.to_string_lossy().into_owned(),
+        fingerprint: tls::fingerprint(cert_path)?,
+    })
+}
+
+/// The certificate the TLS settings point at, if there is one yet.
+#[tauri::command]
+pub async fn get_remote_certificate(state: State<'_, AppState>) -> Result<Option<CertificateInfo>, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let config = get_remote_config(state).await?;
+    let tls_config: TlsConfig = serde_json::from_value(config["tls"].clone()).unwrap_or_default();
+    let cert_path = match tls_config.mode {
+        TlsMode::SelfSigned => tls::self_signed_paths(&db_path).0,
+        TlsMode::Custom => match tls_config.cert_path.filter(|p| !p.is_empty()) {
+            Some(path) => path.into(),
+            None => return Ok(None),
+        },
+    };
+    if !cert_path.exists() {
+        return Ok(None);
     }
+    certificate_info(tls_config.mode, &cert_path).map(Some)
+}
 
-    let merged_value = Value::Object(merged);
-    let config_str = serde_json::to_string(&merged_value).map_err(|e| e.to_string())?;
-    
-    conn.execute(
-        "INSERT OR REPLACE INTO config (key, value) VALUES ('remote_config', ?1)",
-        rusqlite::params![config_str],
-    ).map_err(|e| e.to_string())?;
-    
+/// Replaces the self-signed certificate, e.g. after the addresses changed.
+/// Clients that pinned the old fingerprint have to accept the new one.
+#[tauri::command]
+pub async fn regenerate_remote_certificate(state: State<'_, AppState>) -> Result<CertificateInfo, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let config = get_remote_config(state.clone()).await?;
+    let tls_config: TlsConfig = serde_json::from_value(config["tls"].clone()).unwrap_or_default();
+    if tls_config.mode != TlsMode::SelfSigned {
+        return Err("Only a self-signed certificate can be regenerated".to_string());
+    }
+
+    let (cert_path, key_path) = tls::self_signed_paths(&db_path);
+    tls::generate_self_signed(&cert_path, &key_path, &remote_access::certificate_names(&config))?;
+
+    let running = state.remote_server.lock().await;
+    if let Some(server) = running.as_ref().filter(|s| s.settings().tls.is_some()) {
+        server.reload_certificate().await?;
+    }
+    certificate_info(TlsMode::SelfSigned, &cert_path)
+}
+
+/// Picks up a renewed certificate now rather than at the next file check.
+#[tauri::command]
+pub async fn reload_remote_certificate(state: State<'_, AppState>) -> Result<bool, String> {
+    let running = state.remote_server.lock().await;
+    let server = running.as_ref().ok_or("The remote server is not running")?;
+    server.reload_certificate().await?;
     Ok(true)
 }
 
 #[tauri::command]
-pub async fn set_remote_config(config: Value, state: State<'_, AppState>) -> Result<bool, String> {
-    let conn = get_db_connection(&state)?;
+pub async fn get_permission_catalogue() -> Result<Vec<Permission>, String> {
+    Ok(permissions::PERMISSIONS.to_vec())
+}
+
+/// Refused remote commands, newest first; a view of the `denied` audit log entries.
+#[tauri::command]
+pub async fn get_permission_denials(limit: Option<u32>, state: State<'_, AppState>) -> Result<Vec<PermissionDenial>, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let filter = AuditFilter {
+        channel: Some(Channel::Remote),
+        result: Some("denied".to_string()),
+        limit: Some(limit.unwrap_or(100)),
+        ..Default::default()
+    };
+
+    let denials = audit_log::query(&db_path, &filter)?
+        .into_iter()
+        .map(|entry| PermissionDenial {
+            user_id: entry.user_id.unwrap_or_default(),
+            username: entry.username,
+            permission: permissions::required_permission(&entry.action).map(str::to_string),
+            command: entry.action,
+            ip: entry.ip,
+            created_at: entry.created_at,
+        })
+        .collect();
+    Ok(denials)
+}
+
+#[tauri::command]
+pub async fn list_remote_sessions(include_revoked: Option<bool>, app_handle: AppHandle) -> Result<Vec<RemoteSession>, String> {
+    remote_access::list_sessions(&app_handle, include_revoked.unwrap_or(false)).await
+}
+
+#[tauri::command]
+pub async fn revoke_remote_session(session_id: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<bool, String> {
+    let result = async {
+        if !remote_access::revoke_session(&app_handle, &session_id, "Revoked by administrator").await? {
+            return Err("Session not found or already revoked".to_string());
+        }
+        Ok(true)
+    }
+    .await;
+    audited(&state, "sessions:revoke", Some(&session_id), None, result)
+}
 
-    let current = get_remote_config(state.clone()).await?;
-    let mut merged = current.as_object().cloned().unwrap_or_default();
+#[tauri::command]
+pub async fn list_remote_bans(state: State<'_, AppState>) -> Result<Vec<IpBan>, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    remote_access::limits::list_bans(&db_path)
+}
 
-    if let Some(obj) = config.as_object() {
-        for (key, value) in obj {
-            merged.insert(key.clone(), value.clone());
+#[tauri::command]
+pub async fn clear_remote_ban(ip: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<bool, String> {
+    let result = async {
+        if !remote_access::clear_ban(&app_handle, &ip).await? {
+            return Err("No ban for this IP".to_string());
         }
+        Ok(true)
     }
+    .await;
+    audited(&state, "bans:clear", Some(&ip), None, result)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CreatedApiToken {
+    pub token: ApiToken,
+    /// Shown only now; just its hash is stored
+    pub secret: String,
+}
 
-    let merged_value = Value::Object(merged);
-    let config_str = serde_json::to_string(&merged_value).map_err(|e| e.to_string())?;
+#[tauri::command]
+pub async fn list_api_tokens(user_id: Option<String>, state: State<'_, AppState>) -> Result<Vec<ApiToken>, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    api_tokens::list(&db_path, user_id.as_deref())
+}
 
-    conn.execute(
-        "INSERT OR REPLACE INTO config (key, value) VALUES ('remote_config', ?1)",
-        rusqlite::params![config_str],
-    ).map_err(|e| e.to_string())?;
+/// Issues a personal API token for the REST API. It never expires when
+/// `expires_in_days` is None.
+#[tauri::command]
+pub async fn create_api_token(
+    user_id: String,
+    name: String,
+    scopes: Vec<String>,
+    expires_in_days: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<CreatedApiToken, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let target = user_label(&state, &user_id);
+    let expiry = expires_in_days.map_or("never expires".to_string(), |days| format!("expires in {} days", days));
+    let detail = format!("{}; scopes: {}; {}", name, scopes.join(", "), expiry);
 
-    Ok(true)
+    let result = api_tokens::create(&db_path, &user_id, &name, &scopes, expires_in_days);
+    let (token, secret) = audited(&state, "tokens:create", Some(&target), Some(&detail), result)?;
+    eprintln!("[REMOTE] Created API token {} for {}", token.name, target);
+    Ok(CreatedApiToken { token, secret })
+}
+
+#[tauri::command]
+pub async fn revoke_api_token(token_id: String, state: State<'_, AppState>) -> Result<bool, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    let result = api_tokens::revoke(&db_path, &token_id).and_then(|revoked| {
+        if revoked {
+            Ok(true)
+        } else {
+            Err("Token not found or already revoked".to_string())
+        }
+    });
+    audited(&state, "tokens:revoke", Some(&token_id), None, result)
+}
+
+#[tauri::command]
+pub async fn get_remote_audit_log(limit: Option<u32>, state: State<'_, AppState>) -> Result<Vec<RemoteAuditEntry>, String> {
+    let db_path = state.db_path.lock().unwrap().clone();
+    remote_access::audit::list(&db_path, limit.unwrap_or(200))
 }
 
 #[tauri::command]
 pub async fn get_users(state: State<'_, AppState>) -> Result<Vec<RemoteUser>, String> {
     let conn = get_db_connection(&state)?;
     
-    // Create remote_users table if it doesn't exist
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS remote_users (
-            id TEXT PRIMARY KEY,
-            username TEXT NOT NULL UNIQUE,
-            password_hash TEXT NOT NULL,
-            permissions TEXT NOT NULL
-        )",
-        [],
-    ).map_err(|e| e.to_string())?;
-    
     let mut stmt = conn
-        .prepare("SELECT id, username, permissions FROM remote_users")
+        .prepare(&format!("SELECT {} FROM remote_users", USER_COLUMNS))
         .map_err(|e| e.to_string())?;
     
     let users = stmt
-        .query_map([], |row| {
-            let perms_str: String = row.get(2)?;
-            let permissions: Vec<String> = serde_json::from_str(&perms_str).unwrap_or_default();
-            
-            Ok(RemoteUser {
-                id: row.get(0)?,
-                username: row.get(1)?,
-                permissions,
-            })
-        })
+        .query_map([], user_from_row)
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
     
-    Ok(users)
+    users.into_iter().map(|user| with_access(&conn, user)).collect()
 }
 
 #[tauri::command]
@@ -140,48 +465,284 @@ pub async fn create_user(
     username: String,
     password: String,
     permissions: Vec<String>,
+    roles: Option<Vec<String>>,
     state: State<'_, AppState>
 ) -> Result<RemoteUser, String> {
-    let conn = get_db_connection(&state)?;
-    
-    // Create table if not exists
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS remote_users (
-            id TEXT PRIMARY KEY,
-            username TEXT NOT NULL UNIQUE,
-            password_hash TEXT NOT NULL,
-            permissions TEXT NOT NULL
-        )",
-        [],
-    ).map_err(|e| e.to_string())?;
-    
-    let id = Uuid::new_v4().to_string();
-    let password_hash = bcrypt::hash(&password, bcrypt::DEFAULT_COST).map_err(|e| e.to_string())?;
-    let permissions_str = serde_json::to_string(&permissions).map_err(|e| e.to_string())?;
-    
-    conn.execute(
-        "INSERT INTO remote_users (id, username, password_hash, permissions) VALUES (?1, ?2, ?3, ?4)",
-        rusqlite::params![&id, &username, &password_hash, &permissions_str],
-    ).map_err(|e| e.to_string())?;
-    
-    Ok(RemoteUser {
-        id,
-        username,
-        permissions,
-    })
+    let detail = access_detail(&permissions, None, roles.as_deref());
+    let result = async {
+        permissions::validate_grants(&permissions)?;
+        let conn = get_db_connection(&state)?;
+
+        let id = Uuid::new_v4().to_string();
+        let password_hash = bcrypt::hash(&password, bcrypt::DEFAULT_COST).map_err(|e| e.to_string())?;
+        let permissions_str = serde_json::to_string(&permissions).map_err(|e| e.to_string())?;
+        let created_at = chrono::Utc::now().to_rfc3339();
+
+        conn.execute(
+            "INSERT INTO remote_users (id, username, password_hash, permissions, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![&id, &username, &password_hash, &permissions_str, &created_at],
+        ).map_err(|e| e.to_string())?;
+        if let Some(roles) = roles {
+            roles::set_user_roles(&conn, &id, &roles)?;
+        }
+
+        load_user(&conn, &id)
+    }
+    .await;
+    audited(&state, "users:create", Some(&username), Some(&detail), result)
+}
+
+#[tauri::command]
+pub async fn update_user_permissions(
+    user_id: String,
+    permissions: Vec<String>,
+    denied_permissions: Option<Vec<String>>,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<RemoteUser, String> {
+    let target = user_label(&state, &user_id);
+    let detail = access_detail(&permissions, denied_permissions.as_deref(), None);
+    let result = async {
+        permissions::validate_grants(&permissions)?;
+        let conn = get_db_connection(&state)?;
+        ensure_user_exists(&conn, &user_id)?;
+        let permissions_str = serde_json::to_string(&permissions).map_err(|e| e.to_string())?;
+
+        conn.execute(
+            "UPDATE remote_users SET permissions = ?1 WHERE id = ?2",
+            rusqlite::params![permissions_str, user_id],
+        )
+        .map_err(|e| e.to_string())?;
+        if let Some(denied) = denied_permissions {
+            permissions::validate_grants(&denied)?;
+            let denied_str = serde_json::to_string(&denied).map_err(|e| e.to_string())?;
+            conn.execute(
+                "UPDATE remote_users SET denied_permissions = ?1 WHERE id = ?2",
+                rusqlite::params![denied_str, user_id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        // Connected clients get the new permissions on their next command
+        remote_access::refresh_permissions(&app_handle, std::slice::from_ref(&user_id)).await?;
+        load_user(&conn, &user_id)
+    }
+    .await;
+    audited(&state, "users:permissions", Some(&target), Some(&detail), result)
+}
+
+#[tauri::command]
+pub async fn set_user_roles(
+    user_id: String,
+    roles: Vec<String>,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<RemoteUser, String> {
+    let target = user_label(&state, &user_id);
+    let detail = access_detail(&[], None, Some(&roles));
+    let result = async {
+        let conn = get_db_connection(&state)?;
+        ensure_user_exists(&conn, &user_id)?;
+        roles::set_user_roles(&conn, &user_id, &roles)?;
+
+        remote_access::refresh_permissions(&app_handle, std::slice::from_ref(&user_id)).await?;
+        load_user(&conn, &user_id)
+    }
+    .await;
+    audited(&state, "users:roles", Some(&target), Some(&detail), result)
+}
+
+#[tauri::command]
+pub async fn reset_user_password(
+    user_id: String,
+    password: String,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<bool, String> {
+    let target = user_label(&state, &user_id);
+    let result = async {
+        if password.is_empty() {
+            return Err("Password cannot be empty".to_string());
+        }
+        let conn = get_db_connection(&state)?;
+        let password_hash = bcrypt::hash(&password, bcrypt::DEFAULT_COST).map_err(|e| e.to_string())?;
+
+        let rows_affected = conn
+            .execute(
+                "UPDATE remote_users SET password_hash = ?1, failed_logins = 0 WHERE id = ?2",
+                rusqlite::params![password_hash, user_id],
+            )
+            .map_err(|e| e.to_string())?;
+        if rows_affected == 0 {
+            return Err("User not found".to_string());
+        }
+
+        remote_access::revoke_user(&app_handle, &user_id, "Password was changed").await?;
+        Ok(true)
+    }
+    .await;
+    audited(&state, "users:reset-password", Some(&target), None, result)
+}
+
+#[tauri::command]
+pub async fn set_user_disabled(
+    user_id: String,
+    disabled: bool,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<RemoteUser, String> {
+    let target = user_label(&state, &user_id);
+    let result = async {
+        let conn = get_db_connection(&state)?;
+
+        let rows_affected = conn
+            .execute(
+                "UPDATE remote_users SET disabled = ?1 WHERE id = ?2",
+                rusqlite::params![disabled, user_id],
+            )
+            .map_err(|e| e.to_string())?;
+        if rows_affected == 0 {
+            return Err("User not found".to_string());
+        }
+
+        if disabled {
+            remote_access::revoke_user(&app_handle, &user_id, "Account was disabled").await?;
+        }
+        load_user(&conn, &user_id)
+    }
+    .await;
+    audited(&state, if disabled { "users:disable" } else { "users:enable" }, Some(&target), None, result)
+}
+
+#[tauri::command]
+pub async fn delete_user(user_id: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<bool, String> {
+    let target = user_label(&state, &user_id);
+    let result = async {
+        let conn = get_db_connection(&state)?;
+
+        let rows_affected = conn
+            .execute("DELETE FROM remote_users WHERE id = ?1", rusqlite::params![user_id])
+            .map_err(|e| e.to_string())?;
+
+        if rows_affected == 0 {
+            return Err("User not found".to_string());
+        }
+        conn.execute("DELETE FROM remote_user_roles WHERE user_id = ?1", rusqlite::params![user_id])
+            .map_err(|e| e.to_string())?;
+        api_tokens::delete_user_tokens(&conn, &user_id)?;
+
+        remote_access::revoke_user(&app_handle, &user_id, "Account was deleted").await?;
+        Ok(true)
+    }
+    .await;
+    audited(&state, "users:delete", Some(&target), None, result)
 }
 
 #[tauri::command]
-pub async fn delete_user(user_id: String, state: State<'_, AppState>) -> Result<bool, String> {
+pub async fn get_roles(state: State<'_, AppState>) -> Result<Vec<Role>, String> {
     let conn = get_db_connection(&state)?;
-    
-    let rows_affected = conn
-        .execute("DELETE FROM remote_users WHERE id = ?1", rusqlite::params![user_id])
+    roles::list_roles(&conn)
+}
+
+fn validate_role(name: &str, permissions: &[String]) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("Role name cannot be empty".to_string());
+    }
+    permissions::validate_grants(permissions)
+}
+
+fn load_role(conn: &Connection, role_id: &str) -> Result<Role, String> {
+    roles::list_roles(conn)?
+        .into_iter()
+        .find(|r| r.id == role_id)
+        .ok_or_else(|| "Role not found".to_string())
+}
+
+#[tauri::command]
+pub async fn create_role(
+    name: String,
+    description: Option<String>,
+    permissions: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<Role, String> {
+    let detail = access_detail(&permissions, None, None);
+    let result = async {
+        validate_role(&name, &permissions)?;
+        let conn = get_db_connection(&state)?;
+
+        let id = Uuid::new_v4().to_string();
+        let permissions_str = serde_json::to_string(&permissions).map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO remote_roles (id, name, description, permissions, builtin, created_at) VALUES (?1, ?2, ?3, ?4, 0, ?5)",
+            rusqlite::params![
+                &id,
+                name.trim(),
+                description.unwrap_or_default(),
+                &permissions_str,
+                chrono::Utc::now().to_rfc3339(),
+            ],
+        )
         .map_err(|e| e.to_string())?;
-    
-    if rows_affected == 0 {
-        return Err("User not found".to_string());
+
+        load_role(&conn, &id)
     }
-    
-    Ok(true)
+    .await;
+    audited(&state, "roles:create", Some(&name), Some(&detail), result)
+}
+
+#[tauri::command]
+pub async fn update_role(
+    role_id: String,
+    name: String,
+    description: Option<String>,
+    permissions: Vec<String>,
+    state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<Role, String> {
+    let detail = access_detail(&permissions, None, None);
+    let result = async {
+        validate_role(&name, &permissions)?;
+        let conn = get_db_connection(&state)?;
+
+        let permissions_str = serde_json::to_string(&permissions).map_err(|e| e.to_string())?;
+        let rows_affected = conn
+            .execute(
+                "UPDATE remote_roles SET name = ?1, description = ?2, permissions = ?3 WHERE id = ?4",
+                rusqlite::params![name.trim(), description.unwrap_or_default(), &permissions_str, &role_id],
+            )
+            .map_err(|e| e.to_string())?;
+        if rows_affected == 0 {
+            return Err("Role not found".to_string());
+        }
+
+        // Everyone holding the role is affected, connected or not
+        let holders = roles::users_with_role(&conn, &role_id)?;
+        remote_access::refresh_permissions(&app_handle, &holders).await?;
+        load_role(&conn, &role_id)
+    }
+    .await;
+    audited(&state, "roles:update", Some(&role_id), Some(&detail), result)
+}
+
+#[tauri::command]
+pub async fn delete_role(role_id: String, state: State<'_, AppState>, app_handle: AppHandle) -> Result<bool, String> {
+    let result = async {
+        let conn = get_db_connection(&state)?;
+
+        let role = load_role(&conn, &role_id)?;
+        if role.builtin {
+            return Err(format!("{} is a built-in role and cannot be deleted", role.name));
+        }
+
+        let holders = roles::users_with_role(&conn, &role_id)?;
+        conn.execute("DELETE FROM remote_user_roles WHERE role_id = ?1", rusqlite::params![role_id])
+            .map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM remote_roles WHERE id = ?1", rusqlite::params![role_id])
+            .map_err(|e| e.to_string())?;
+
+        remote_access::refresh_permissions(&app_handle, &holders).await?;
+        Ok(true)
+    }
+    .await;
+    audited(&state, "roles:delete", Some(&role_id), None, result)
 }
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use tauri::State;
 use crate::AppState;
+use crate::services::audit_log;
 use crate::services::server_service::{ServerService, ServerStatus};
 
 #[tauri::command]
@@ -18,7 +19,9 @@ pub async fn set_path(path: String, state: State<'_, AppState>) -> Result<bool,
         let guard = state.server_service.lock().unwrap();
         guard.as_ref().ok_or("Server service not initialized")?.clone()
     };
-    service.set_server_path(&path).await.map_err(|e: anyhow::Error| e.to_string())
+    let result = service.set_server_path(&path).await.map_err(|e: anyhow::Error| e.to_string());
+    let db_path = state.db_path.lock().unwrap().clone();
+    audit_log::audited(&db_path, "server:set-path", Some(&path), None, result)
 }
 
 #[tauri::command]
@@ -27,7 +30,9 @@ pub async fn start(state: State<'_, AppState>) -> Result<bool, String> {
         let guard = state.server_service.lock().unwrap();
         guard.as_ref().ok_or("Server service not initialized")?.clone()
     };
-    service.start_server().await.map_err(|e: anyhow::Error| e.to_string())
+    let result = service.start_server().await.map_err(|e: anyhow::Error| e.to_string());
+    let db_path = state.db_path.lock().unwrap().clone();
+    audit_log::audited(&db_path, "server:start", None, None, result)
 }
 
 #[tauri::command]
@@ -36,7 +41,9 @@ pub async fn stop(state: State<'_, AppState>) -> Result<bool, String> {
         let guard = state.server_service.lock().unwrap();
         guard.as_ref().ok_or("Server service not initialized")?.clone()
     };
-    service.stop_server().await.map_err(|e: anyhow::Error| e.to_string())
+    let result = service.stop_server().await.map_err(|e: anyhow::Error| e.to_string());
+    let db_path = state.db_path.lock().unwrap().clone();
+    audit_log::audited(&db_path, "server:stop", None, None, result)
 }
 
 #[tauri::command]
@@ -45,7 +52,9 @@ pub async fn restart(state: State<'_, AppState>) -> Result<bool, String> {
         let guard = state.server_service.lock().unwrap();
         guard.as_ref().ok_or("Server service not initialized")?.clone()
     };
-    service.restart_server().await.map_err(|e| e.to_string())
+    let result = service.restart_server().await.map_err(|e| e.to_string());
+    let db_path = state.db_path.lock().unwrap().clone();
+    audit_log::audited(&db_path, "server:restart", None, None, result)
 }
 
 #[tauri::command]
@@ -81,5 +90,7 @@ pub async fn send_server_command(command: String, state: State<'_, AppState>) ->
         let guard = state.server_service.lock().unwrap();
         guard.as_ref().ok_or("Server service not initialized")?.clone()
     };
-    service.send_command(&command).await.map_err(|e| e.to_string())
+    let result = service.send_command(&command).await.map_err(|e| e.to_string());
+    let db_path = state.db_path.lock().unwrap().clone();
+    audit_log::audited(&db_path, "server:send-command", None, Some(&command), result)
 }
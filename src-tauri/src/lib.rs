@@ -1,9 +1,11 @@
 // Modules
 mod commands;
+mod remote_access;
 mod services;
 mod utils;
 
 use tauri::Manager;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use services::server_service::ServerService;
 use rusqlite::Connection;
@@ -17,18 +19,23 @@ use commands::backup;
 use commands::discord;
 use commands::remote;
 use commands::download;
+use commands::audit;
 
 // State management
 pub struct AppState {
     pub db_path: Mutex<String>,
     pub server_service: Mutex<Option<Arc<ServerService>>>,
+    // Passphrases unlocked for encrypted backup profiles, kept in memory only
+    pub backup_passphrases: Mutex<HashMap<String, String>>,
+    // Held across start and stop so concurrent config changes apply in order
+    pub remote_server: tokio::sync::Mutex<Option<remote_access::RemoteServer>>,
 }
 
 // Initialize all database tables
 fn initialize_database(db_path: &str) -> Result<(), String> {
     let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
     
-    // Create config table (used by remote, discord, backup)
+    // Create config table (used by remote, discord, backup, files)
     conn.execute(
         "CREATE TABLE IF NOT EXISTS config (
             key TEXT PRIMARY KEY,
@@ -68,6 +75,162 @@ fn initialize_database(db_path: &str) -> Result<(), String> {
         [],
     ).map_err(|e| e.to_string())?;
     
+    // Create backups table (per-backup metadata used by backup retention)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS backups (
+            id TEXT PRIMARY KEY,
+            profile TEXT NOT NULL DEFAULT 'default',
+            pinned INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL,
+            verification TEXT
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+    
+    // Roles for remote users and who holds them
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS remote_roles (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            description TEXT NOT NULL DEFAULT '',
+            permissions TEXT NOT NULL,
+            builtin INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS remote_user_roles (
+            user_id TEXT NOT NULL,
+            role_id TEXT NOT NULL,
+            PRIMARY KEY (user_id, role_id)
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+    remote_access::roles::seed_builtin_roles(&conn)?;
+    
+    // Remote login sessions; revoked rows are the token revocation list
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS remote_sessions (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL,
+            refresh_hash TEXT NOT NULL,
+            previous_refresh_hash TEXT,
+            ip TEXT,
+            created_at TEXT NOT NULL,
+            last_used_at TEXT NOT NULL,
+            expires_at TEXT NOT NULL,
+            revoked_at TEXT,
+            revoked_reason TEXT
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+    
+    // IPs banned from the remote server after repeated failed logins
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS remote_ip_bans (
+            ip TEXT PRIMARY KEY,
+            reason TEXT NOT NULL,
+            banned_at TEXT NOT NULL,
+            expires_at TEXT NOT NULL
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+    
+    // Security events of the remote server, such as refused connections
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS remote_audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            event TEXT NOT NULL,
+            username TEXT,
+            ip TEXT,
+            detail TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+    
+    // Personal API tokens for the REST API; only a hash of each is kept
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS api_tokens (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL,
+            name TEXT NOT NULL,
+            token_hash TEXT NOT NULL,
+            scopes TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            expires_at TEXT,
+            last_used_at TEXT,
+            last_used_ip TEXT,
+            revoked_at TEXT
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+    
+    // Administrative actions, local and remote; triggers refuse edits and deletes
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            created_at TEXT NOT NULL,
+            channel TEXT NOT NULL,
+            username TEXT NOT NULL,
+            user_id TEXT,
+            ip TEXT,
+            action TEXT NOT NULL,
+            target TEXT,
+            detail TEXT,
+            result TEXT NOT NULL,
+            error TEXT
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS audit_log_created_at ON audit_log (created_at)",
+        [],
+    ).map_err(|e| e.to_string())?;
+    for (trigger, operation) in [("audit_log_no_update", "UPDATE"), ("audit_log_no_delete", "DELETE")] {
+        conn.execute(
+            &format!(
+                "CREATE TRIGGER IF NOT EXISTS {} BEFORE {} ON audit_log
+                 BEGIN SELECT RAISE(ABORT, 'audit_log is append-only'); END",
+                trigger, operation
+            ),
+            [],
+        ).map_err(|e| e.to_string())?;
+    }
+    
+    // Migration: Add verification column to backups (for old databases)
+    let verification_exists: Result<i64, _> = conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info('backups') WHERE name='verification'",
+        [],
+        |row| row.get(0),
+    );
+    if let Ok(0) = verification_exists {
+        conn.execute("ALTER TABLE backups ADD COLUMN verification TEXT", [])
+            .map_err(|e| e.to_string())?;
+    }
+    
+    // Migration: Add account state and login tracking to remote_users
+    for (column, definition) in [
+        ("created_at", "TEXT"),
+        ("disabled", "INTEGER NOT NULL DEFAULT 0"),
+        ("last_login_at", "TEXT"),
+        ("last_login_ip", "TEXT"),
+        ("failed_logins", "INTEGER NOT NULL DEFAULT 0"),
+        ("last_failed_login_at", "TEXT"),
+        ("denied_permissions", "TEXT NOT NULL DEFAULT '[]'"),
+    ] {
+        let column_exists: Result<i64, _> = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('remote_users') WHERE name=?1",
+            [column],
+            |row| row.get(0),
+        );
+        if let Ok(0) = column_exists {
+            conn.execute(&format!("ALTER TABLE remote_users ADD COLUMN {} {}", column, definition), [])
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    
     Ok(())
 }
 
@@ -80,6 +243,8 @@ pub fn run() {
         .manage(AppState {
             db_path: Mutex::new(String::new()),
             server_service: Mutex::new(None),
+            backup_passphrases: Mutex::new(HashMap::new()),
+            remote_server: tokio::sync::Mutex::new(None),
         })
         .setup(|app| {
             if cfg!(debug_assertions) {
@@ -107,7 +272,26 @@ pub fn run() {
             let server_service = ServerService::new(&db_path_str)
                 .map_err(|e| format!("Failed to create server service: {}", e))?
                 .with_app_handle(app.handle().clone());
-            *state.server_service.lock().unwrap() = Some(Arc::new(server_service));
+            let server_service = Arc::new(server_service);
+            *state.server_service.lock().unwrap() = Some(server_service.clone());
+            
+            // Start scheduled backup pruning
+            backup::spawn_retention_scheduler(db_path_str.clone(), app.handle().clone());
+            backup::spawn_verification_scheduler(db_path_str.clone(), app.handle().clone());
+            
+            // Start scheduled trash purging
+            files::spawn_trash_purger(db_path_str.clone());
+            
+            // Report changes in the server directory to the UI
+            files::spawn_file_watcher(db_path_str.clone(), server_service, app.handle().clone());
+            
+            // Start remote access if it was left enabled
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = remote_access::apply_config(&app_handle).await {
+                    eprintln!("[REMOTE] Failed to start remote access: {}", e);
+                }
+            });
 
             Ok(())
         })
@@ -134,8 +318,31 @@ pub fn run() {
             files::list_files,
             files::read_file,
             files::write_file,
+            files::list_file_revisions,
+            files::diff_file_revision,
+            files::revert_file,
             files::delete_file,
             files::create_dir,
+            files::search_files,
+            files::get_file_info,
+            files::rename_file,
+            files::move_files,
+            files::copy_files,
+            files::request_delete,
+            files::delete_files,
+            files::list_trash,
+            files::restore_from_trash,
+            files::purge_trash,
+            files::extract_archive,
+            files::compress_paths,
+            files::begin_file_upload,
+            files::upload_file_chunk,
+            files::finish_file_upload,
+            files::abort_file_upload,
+            files::begin_file_download,
+            files::download_file_chunk,
+            files::get_files_config,
+            files::save_files_config,
             
             // Config commands
             config::read_config,
@@ -145,7 +352,24 @@ pub fn run() {
             backup::create_backup,
             backup::list_backups,
             backup::restore_backup,
+            backup::export_backup,
+            backup::import_backup,
             backup::delete_backup,
+            backup::set_backup_pinned,
+            backup::prune_backups,
+            backup::gc_backup_store,
+            backup::verify_backup,
+            backup::browse_backup,
+            backup::unlock_backup_profile,
+            backup::lock_backup_profile,
+            backup::get_backup_config,
+            backup::save_backup_config,
+            backup::upload_backup,
+            backup::list_remote_backups,
+            backup::download_remote_backup,
+            backup::delete_remote_backup,
+            backup::prune_destination,
+            backup::test_destination,
             
             // Discord commands
             discord::get_discord_config,
@@ -156,9 +380,35 @@ pub fn run() {
             remote::get_remote_config,
             remote::set_remote_config,
             remote::set_remote_enabled,
+            remote::get_remote_status,
+            remote::get_remote_certificate,
+            remote::regenerate_remote_certificate,
+            remote::reload_remote_certificate,
+            remote::get_permission_catalogue,
+            remote::get_permission_denials,
+            remote::list_remote_sessions,
+            remote::revoke_remote_session,
+            remote::list_remote_bans,
+            remote::clear_remote_ban,
+            remote::list_api_tokens,
+            remote::create_api_token,
+            remote::revoke_api_token,
+            remote::get_remote_audit_log,
             remote::get_users,
             remote::create_user,
             remote::delete_user,
+            remote::update_user_permissions,
+            remote::reset_user_password,
+            remote::set_user_disabled,
+            remote::set_user_roles,
+            remote::get_roles,
+            remote::create_role,
+            remote::update_role,
+            remote::delete_role,
+            
+            // Audit commands
+            audit::get_audit_log,
+            audit::export_audit_log,
             
             // Download commands
             download::download_server,
@@ -0,0 +1,199 @@
+// Personal API tokens for the REST API, in `api_tokens`. A token belongs to a
+// remote user and carries scopes in the same grammar as permission grants; it
+// can do what both its scopes and its user currently allow, so taking a
+// permission away from the user also takes it from their tokens.
+//
+// Only a hash is stored. The token is shown once when created, formatted as
+// `hsp_<id>.<secret>` so it can be looked up by id and spotted by secret scanners.
+use chrono::{Duration, SecondsFormat, Utc};
+use rand::RngCore;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::permissions::{self, PERMISSIONS};
+use super::sessions::{self, Identity};
+
+const TOKEN_PREFIX: &str = "hsp_";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApiToken {
+    pub id: String,
+    pub name: String,
+    pub user_id: String,
+    pub username: Option<String>,
+    pub scopes: Vec<String>,
+    pub created_at: String,
+    /// Never expires when None
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub last_used_ip: Option<String>,
+    pub revoked_at: Option<String>,
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+const SELECT_TOKENS: &str = "SELECT t.id, t.name, t.user_id, u.username, t.scopes, t.created_at, t.expires_at,
+                                    t.last_used_at, t.last_used_ip, t.revoked_at, t.token_hash
+                             FROM api_tokens t LEFT JOIN remote_users u ON u.id = t.user_id";
+
+fn token_from_row(row: &rusqlite::Row) -> rusqlite::Result<ApiToken> {
+    let scopes: String = row.get(4)?;
+    Ok(ApiToken {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        user_id: row.get(2)?,
+        username: row.get(3)?,
+        scopes: serde_json::from_str(&scopes).unwrap_or_default(),
+        created_at: row.get(5)?,
+        expires_at: row.get(6)?,
+        last_used_at: row.get(7)?,
+        last_used_ip: row.get(8)?,
+        revoked_at: row.get(9)?,
+    })
+}
+
+/// Issues a token for a user; returns it along with the secret, which is not stored.
+pub fn create(
+    db_path: &str,
+    user_id: &str,
+    name: &str,
+    scopes: &[String],
+    expires_in_days: Option<u32>,
+) -> Result<(ApiToken, String), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Token name is required".to_string());
+    }
+    if scopes.is_empty() {
+        return Err("A token needs at least one scope".to_string());
+    }
+    permissions::validate_grants(scopes)?;
+
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let user_exists: bool = conn
+        .query_row("SELECT COUNT(*) > 0 FROM remote_users WHERE id = ?1", [user_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if !user_exists {
+        return Err("User not found".to_string());
+    }
+
+    let id = Uuid::new_v4().simple().to_string();
+    let mut secret = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut secret);
+    let token = format!("{}{}.{}", TOKEN_PREFIX, id, hex::encode(secret));
+    let expires_at = expires_in_days
+        .map(|days| (Utc::now() + Duration::days(i64::from(days))).to_rfc3339_opts(SecondsFormat::Secs, true));
+
+    conn.execute(
+        "INSERT INTO api_tokens (id, user_id, name, token_hash, scopes, created_at, expires_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        rusqlite::params![
+            id,
+            user_id,
+            name,
+            hash_token(&token),
+            serde_json::to_string(scopes).map_err(|e| e.to_string())?,
+            now(),
+            expires_at,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let created = conn
+        .query_row(
+            &format!("{} WHERE t.id = ?1", SELECT_TOKENS),
+            [&id],
+            token_from_row,
+        )
+        .map_err(|e| e.to_string())?;
+    Ok((created, token))
+}
+
+/// Tokens newest first, of one user or of everyone.
+pub fn list(db_path: &str, user_id: Option<&str>) -> Result<Vec<ApiToken>, String> {
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "{} WHERE ?1 IS NULL OR t.user_id = ?1 ORDER BY t.created_at DESC",
+            SELECT_TOKENS
+        ))
+        .map_err(|e| e.to_string())?;
+    let tokens = stmt
+        .query_map([user_id], token_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(tokens)
+}
+
+/// False if the token was unknown or already revoked.
+pub fn revoke(db_path: &str, token_id: &str) -> Result<bool, String> {
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let revoked = conn
+        .execute(
+            "UPDATE api_tokens SET revoked_at = ?1 WHERE id = ?2 AND revoked_at IS NULL",
+            rusqlite::params![now(), token_id],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(revoked > 0)
+}
+
+/// Deletes the tokens of a user who is being deleted.
+pub fn delete_user_tokens(conn: &Connection, user_id: &str) -> Result<(), String> {
+    conn.execute("DELETE FROM api_tokens WHERE user_id = ?1", [user_id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Checks a presented token and returns who it acts as, with the permissions
+/// its scopes and its user have in common.
+pub fn authenticate(db_path: &str, token: &str, ip: Option<&str>) -> Result<Identity, String> {
+    let (id, _) = token
+        .strip_prefix(TOKEN_PREFIX)
+        .and_then(|rest| rest.split_once('.'))
+        .ok_or("Invalid API token")?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let stored = conn
+        .query_row(
+            &format!("{} WHERE t.id = ?1", SELECT_TOKENS),
+            [id],
+            |row| Ok((token_from_row(row)?, row.get::<_, String>(10)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    let (stored, token_hash) = stored.ok_or("Invalid API token")?;
+    if token_hash != hash_token(token) {
+        return Err("Invalid API token".to_string());
+    }
+    if stored.revoked_at.is_some() {
+        return Err("API token has been revoked".to_string());
+    }
+    if stored.expires_at.is_some_and(|expires_at| expires_at <= now()) {
+        return Err("API token expired".to_string());
+    }
+
+    let mut identity = sessions::load_identity(&conn, &stored.user_id)?;
+    let scopes = stored.scopes;
+    identity.permissions = PERMISSIONS
+        .iter()
+        .map(|p| p.id)
+        .filter(|id| permissions::has_permission(&identity.permissions, id) && permissions::has_permission(&scopes, id))
+        .map(str::to_string)
+        .collect();
+
+    conn.execute(
+        "UPDATE api_tokens SET last_used_at = ?1, last_used_ip = COALESCE(?2, last_used_ip) WHERE id = ?3",
+        rusqlite::params![now(), ip, id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(identity)
+}
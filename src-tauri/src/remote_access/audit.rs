@@ -0,0 +1,63 @@
+// Security events of the remote server in `remote_audit_log`: connections
+// refused by the IP rules, bans and the connection cap. These happen before
+// anyone is authenticated and are not actions, so they stay out of the
+// administrative `audit_log`, which also holds refused commands of signed-in users.
+use chrono::{SecondsFormat, Utc};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RemoteAuditEntry {
+    pub id: i64,
+    pub event: String,
+    pub username: Option<String>,
+    pub ip: Option<String>,
+    pub detail: String,
+    pub created_at: String,
+}
+
+/// Appends an entry; failures are logged rather than passed on, since a
+/// refused connection must stay refused whether or not it could be recorded.
+pub fn record(db_path: &str, event: &str, username: Option<&str>, ip: Option<&str>, detail: &str) {
+    let result = Connection::open(db_path).and_then(|conn| {
+        conn.execute(
+            "INSERT INTO remote_audit_log (event, username, ip, detail, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![
+                event,
+                username,
+                ip,
+                detail,
+                Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            ],
+        )
+    });
+    if let Err(e) = result {
+        eprintln!("[REMOTE] Failed to write audit entry {}: {}", event, e);
+    }
+}
+
+/// Newest first.
+pub fn list(db_path: &str, limit: u32) -> Result<Vec<RemoteAuditEntry>, String> {
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, event, username, ip, detail, created_at FROM remote_audit_log
+             ORDER BY id DESC LIMIT ?1",
+        )
+        .map_err(|e| e.to_string())?;
+    let entries = stmt
+        .query_map([limit], |row| {
+            Ok(RemoteAuditEntry {
+                id: row.get(0)?,
+                event: row.get(1)?,
+                username: row.get(2)?,
+                ip: row.get(3)?,
+                detail: row.get(4)?,
+                created_at: row.get(5)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(entries)
+}
@@ -0,0 +1,274 @@
+// Maps the `command` events of the remote protocol onto the same command
+// functions the local UI invokes. Command names and positional `args` follow
+// the legacy RemoteSocketServer.ts so existing clients keep working.
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fs;
+use tauri::{AppHandle, Manager};
+
+use crate::commands::{backup, config, discord, download, files, server};
+use crate::services::audit_log;
+use crate::AppState;
+
+/// A failed command. `data` carries structured details, such as the current
+/// content of a file whose write was rejected.
+pub struct CommandError {
+    pub message: String,
+    pub data: Option<Value>,
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        CommandError { message, data: None }
+    }
+}
+
+impl From<files::WriteFileError> for CommandError {
+    fn from(error: files::WriteFileError) -> Self {
+        match &error {
+            files::WriteFileError::Conflict { message, .. } => CommandError {
+                message: message.clone(),
+                data: serde_json::to_value(&error).ok(),
+            },
+            files::WriteFileError::Failed { message } => CommandError { message: message.clone(), data: None },
+        }
+    }
+}
+
+fn arg<T: DeserializeOwned>(args: &[Value], index: usize, name: &str) -> Result<T, String> {
+    let value = args.get(index).cloned().unwrap_or(Value::Null);
+    serde_json::from_value(value).map_err(|_| format!("Invalid or missing argument: {}", name))
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value, CommandError> {
+    serde_json::to_value(value).map_err(|e| CommandError::from(e.to_string()))
+}
+
+fn done(message: &str) -> Result<Value, CommandError> {
+    Ok(json!({ "success": true, "message": message }))
+}
+
+#[derive(serde::Deserialize)]
+struct UploadedFile {
+    name: String,
+    content: String,
+}
+
+// `files:upload` from the legacy protocol: small files sent inline as base64
+fn upload_inline(db_path: &str, target_dir: &str, uploads: Vec<UploadedFile>) -> Result<Value, String> {
+    let sandbox = files::open_sandbox(db_path)?;
+    let mut uploaded = Vec::new();
+    let mut failed = Vec::new();
+
+    for upload in uploads {
+        let path = format!("{}/{}", target_dir.trim_end_matches('/'), upload.name);
+        let result = BASE64
+            .decode(upload.content.as_bytes())
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| {
+                let target = sandbox.resolve_writable(&path).map_err(|e| e.to_string())?;
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                fs::write(&target, bytes).map_err(|e| e.to_string())?;
+                Ok(sandbox.relative(&target))
+            });
+        audit_log::record(db_path, "files:upload", Some(&path), None, result.as_ref().err().map(String::as_str));
+        match result {
+            Ok(path) => uploaded.push(path),
+            Err(error) => failed.push(json!({ "path": path, "error": error })),
+        }
+    }
+
+    Ok(json!({ "success": failed.is_empty(), "uploaded": uploaded, "failed": failed }))
+}
+
+pub async fn execute(app_handle: &AppHandle, command: &str, args: &[Value]) -> Result<Value, CommandError> {
+    let state = app_handle.state::<AppState>();
+    let db_path = state.db_path.lock().unwrap().clone();
+
+    match command {
+        // Server
+        "server:start" => {
+            server::start(state).await?;
+            done("Server started")
+        }
+        "server:stop" => {
+            server::stop(state).await?;
+            done("Server stopped")
+        }
+        "server:restart" => {
+            server::restart(state).await?;
+            done("Server restarted")
+        }
+        "server:status" => {
+            let status = server::get_status(state).await?;
+            Ok(json!({
+                "status": if status.running { "running" } else { "stopped" },
+                "isRunning": status.running,
+                "pid": status.pid,
+            }))
+        }
+        "server:logs" => Ok(json!({ "logs": server::get_logs(state).await? })),
+        "server:path" => {
+            let server_path = server::get_path(state).await?.ok_or("Server path not configured".to_string())?;
+            Ok(json!({ "serverPath": server_path }))
+        }
+        "server:send-command" => {
+            server::send_server_command(arg(args, 0, "command")?, state).await?;
+            Ok(json!({ "success": true }))
+        }
+
+        // Config
+        "config:read" => Ok(config::read_config().await?),
+        "config:write" => {
+            config::write_config(arg(args, 0, "config")?).await?;
+            done("Config saved")
+        }
+        "config:system-resources" => Ok(download::get_system_resources().await?),
+
+        // Backups
+        "backup:create" => to_value(
+            backup::create_backup(arg(args, 0, "name")?, arg(args, 1, "profile")?, None, state, app_handle.clone()).await?,
+        ),
+        "backup:restore" => {
+            backup::restore_backup(arg(args, 0, "backupId")?, None, state).await?;
+            done("Backup restored")
+        }
+        "backup:list" => to_value(backup::list_backups(state).await?),
+        "backup:delete" => {
+            backup::delete_backup(arg(args, 0, "backupId")?, state).await?;
+            done("Backup deleted")
+        }
+
+        // Files
+        "files:list" => {
+            let dir: Option<String> = arg(args, 0, "directory")?;
+            to_value(files::list_files(dir.unwrap_or_default(), arg(args, 1, "options")?, state).await?)
+        }
+        "files:search" => to_value(files::search_files(arg(args, 0, "query")?, state).await?),
+        "files:info" => Ok(json!({ "info": files::get_file_info(arg(args, 0, "path")?, state).await? })),
+        "files:is-editable" => {
+            let info = files::get_file_info(arg(args, 0, "path")?, state).await?;
+            Ok(json!({ "isEditable": info.is_editable }))
+        }
+        "files:read" => to_value(files::read_file(arg(args, 0, "path")?, state).await?),
+        "files:download" => Ok(Value::String(files::read_file(arg(args, 0, "path")?, state).await?.content)),
+        "files:write" => to_value(
+            files::write_file(arg(args, 0, "path")?, arg(args, 1, "content")?, arg(args, 2, "expectedVersion")?, state)
+                .await?,
+        ),
+        "files:upload" => Ok(upload_inline(&db_path, &arg::<String>(args, 0, "targetDir")?, arg(args, 1, "files")?)?),
+        "files:delete" => {
+            files::delete_file(arg(args, 0, "path")?, state).await?;
+            done("File deleted")
+        }
+        "files:mkdir" => {
+            files::create_dir(arg(args, 0, "path")?, state).await?;
+            done("Directory created")
+        }
+        "files:rename" => to_value(files::rename_file(arg(args, 0, "path")?, arg(args, 1, "newName")?, state).await?),
+        "files:move" => to_value(
+            files::move_files(arg(args, 0, "paths")?, arg(args, 1, "destination")?, arg(args, 2, "overwrite")?, state).await?,
+        ),
+        "files:copy" => to_value(
+            files::copy_files(
+                arg(args, 0, "paths")?,
+                arg(args, 1, "destination")?,
+                arg(args, 2, "overwrite")?,
+                state,
+                app_handle.clone(),
+            )
+            .await?,
+        ),
+        "files:request-delete" => to_value(files::request_delete(arg(args, 0, "paths")?, state).await?),
+        "files:delete-many" => to_value(
+            files::delete_files(arg(args, 0, "paths")?, arg(args, 1, "confirmationToken")?, arg(args, 2, "permanent")?, state)
+                .await?,
+        ),
+        "files:extract" => to_value(
+            files::extract_archive(
+                arg(args, 0, "path")?,
+                arg(args, 1, "destination")?,
+                arg(args, 2, "onConflict")?,
+                state,
+                app_handle.clone(),
+            )
+            .await?,
+        ),
+        "files:compress" => to_value(
+            files::compress_paths(arg(args, 0, "paths")?, arg(args, 1, "output")?, arg(args, 2, "format")?, state, app_handle.clone())
+                .await?,
+        ),
+        "files:upload-begin" => to_value(
+            files::begin_file_upload(
+                arg(args, 0, "path")?,
+                arg(args, 1, "size")?,
+                arg(args, 2, "sha256")?,
+                arg(args, 3, "overwrite")?,
+                state,
+            )
+            .await?,
+        ),
+        "files:upload-chunk" => to_value(
+            files::upload_file_chunk(
+                arg(args, 0, "path")?,
+                arg(args, 1, "uploadId")?,
+                arg(args, 2, "size")?,
+                arg(args, 3, "offset")?,
+                arg(args, 4, "data")?,
+                state,
+                app_handle.clone(),
+            )
+            .await?,
+        ),
+        "files:upload-finish" => to_value(
+            files::finish_file_upload(
+                arg(args, 0, "path")?,
+                arg(args, 1, "uploadId")?,
+                arg(args, 2, "size")?,
+                arg(args, 3, "sha256")?,
+                state,
+            )
+            .await?,
+        ),
+        "files:upload-abort" => {
+            to_value(files::abort_file_upload(arg(args, 0, "path")?, arg(args, 1, "uploadId")?, state).await?)
+        }
+        "files:download-begin" => to_value(files::begin_file_download(arg(args, 0, "path")?, state).await?),
+        "files:download-chunk" => to_value(
+            files::download_file_chunk(
+                arg(args, 0, "path")?,
+                arg(args, 1, "offset")?,
+                arg(args, 2, "length")?,
+                arg(args, 3, "total")?,
+                state,
+                app_handle.clone(),
+            )
+            .await?,
+        ),
+
+        // Discord
+        "discord:view" => Ok(discord::get_discord_config(state).await?),
+        "discord:configure" => {
+            discord::save_discord_config(arg(args, 0, "config")?, state).await?;
+            done("Discord config saved")
+        }
+        "discord:test" => to_value(discord::test_webhook(state).await?),
+        "discord:send" => {
+            let online: Option<bool> = arg(args, 0, "isOnline")?;
+            let (event, message) = if online.unwrap_or(true) {
+                ("startup", "The server is online")
+            } else {
+                ("shutdown", "The server is offline")
+            };
+            discord::notify_server_event(&state, event, message).await?;
+            done("Discord notification sent")
+        }
+
+        _ => Err(format!("Command not implemented: {}", command).into()),
+    }
+}
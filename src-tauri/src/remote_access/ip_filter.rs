@@ -0,0 +1,210 @@
+// Allow and deny lists for remote connections, as single addresses or CIDR
+// ranges, from `remote_config.ip_rules`. A deny rule always wins; when there
+// are allow rules (or the LAN-only preset is on), an address must match one.
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// Loopback, private and link-local ranges, for the LAN-only preset.
+pub const LAN_RANGES: &[&str] = &[
+    "127.0.0.0/8",
+    "10.0.0.0/8",
+    "172.16.0.0/12",
+    "192.168.0.0/16",
+    "169.254.0.0/16",
+    "::1/128",
+    "fc00::/7",
+    "fe80::/10",
+];
+
+/// `remote_config.ip_rules`
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct IpRules {
+    /// Allows the LAN ranges, in addition to `allow`
+    pub lan_only: bool,
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Cidr, String> {
+        let invalid = || format!("Invalid IP or CIDR range: {}", rule);
+        let (address, prefix) = match rule.trim().split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix.parse::<u8>().map_err(|_| invalid())?)),
+            None => (rule.trim(), None),
+        };
+        let parsed = address.parse::<IpAddr>().map_err(|_| invalid())?;
+        let max = if parsed.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+        if prefix > max {
+            return Err(invalid());
+        }
+        // ::ffff:a.b.c.d/120 is the IPv4 range a.b.c.d/24, since peers are compared in IPv4 form
+        let address = parsed.to_canonical();
+        let prefix = match (parsed, address) {
+            (IpAddr::V6(_), IpAddr::V4(_)) if prefix >= 96 => prefix - 96,
+            (IpAddr::V6(_), IpAddr::V4(_)) => return Ok(Cidr { network: mask(parsed, prefix), prefix }),
+            _ => prefix,
+        };
+        // Host bits are dropped, so 192.168.1.7/24 means 192.168.1.0/24
+        Ok(Cidr { network: mask(address, prefix), prefix })
+    }
+}
+
+fn mask(address: IpAddr, prefix: u8) -> IpAddr {
+    match address {
+        IpAddr::V4(v4) => {
+            let bits = u32::from(v4) & u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            IpAddr::V4(bits.into())
+        }
+        IpAddr::V6(v6) => {
+            let bits = u128::from(v6) & u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            IpAddr::V6(bits.into())
+        }
+    }
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        ip.is_ipv4() == self.network.is_ipv4() && mask(ip, self.prefix) == self.network
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IpFilter {
+    allow: Vec<Cidr>,
+    deny: Vec<Cidr>,
+}
+
+impl IpFilter {
+    /// Parses the rules, failing on the first one that is not an address or range.
+    pub fn new(rules: &IpRules) -> Result<IpFilter, String> {
+        let lan = if rules.lan_only { LAN_RANGES } else { &[] };
+        let allow = lan
+            .iter()
+            .copied()
+            .chain(rules.allow.iter().map(String::as_str))
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+        let deny = rules.deny.iter().map(|r| r.parse()).collect::<Result<_, _>>()?;
+        Ok(IpFilter { allow, deny })
+    }
+
+    /// Why an address is refused, or Ok if it may connect.
+    pub fn check(&self, ip: IpAddr) -> Result<(), &'static str> {
+        if self.deny.iter().any(|c| c.contains(ip)) {
+            return Err("Address is on the deny list");
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|c| c.contains(ip)) {
+            return Err("Address is not on the allow list");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    fn filter(lan_only: bool, allow: &[&str], deny: &[&str]) -> IpFilter {
+        let strings = |rules: &[&str]| rules.iter().map(|r| r.to_string()).collect();
+        IpFilter::new(&IpRules { lan_only, allow: strings(allow), deny: strings(deny) }).unwrap()
+    }
+
+    #[test]
+    fn parses_addresses_and_ranges() {
+        let cidr: Cidr = "192.168.1.7/24".parse().unwrap();
+        assert_eq!(cidr, "192.168.1.0/24".parse().unwrap(), "host bits are dropped");
+        assert!(cidr.contains(ip("192.168.1.200")));
+        assert!(!cidr.contains(ip("192.168.2.1")));
+
+        let single: Cidr = " 10.1.2.3 ".parse().unwrap();
+        assert_eq!(single, "10.1.2.3/32".parse().unwrap());
+        assert!(single.contains(ip("10.1.2.3")));
+        assert!(!single.contains(ip("10.1.2.4")));
+
+        for invalid in ["10.0.0.0/33", "::/129", "10.0.0.0/x", "10.0.0", "example.com", ""] {
+            assert!(invalid.parse::<Cidr>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn zero_prefix_matches_its_whole_family() {
+        let v4: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(v4.contains(ip("203.0.113.9")));
+        assert!(!v4.contains(ip("2001:db8::1")));
+
+        let v6: Cidr = "::/0".parse().unwrap();
+        assert!(v6.contains(ip("2001:db8::1")));
+        assert!(!v6.contains(ip("203.0.113.9")));
+    }
+
+    #[test]
+    fn ipv6_ranges() {
+        let single: Cidr = "2001:db8::1/128".parse().unwrap();
+        assert!(single.contains(ip("2001:db8::1")));
+        assert!(!single.contains(ip("2001:db8::2")));
+
+        let range: Cidr = "2001:db8:abcd::5/48".parse().unwrap();
+        assert!(range.contains(ip("2001:db8:abcd:ffff::1")));
+        assert!(!range.contains(ip("2001:db8:abce::1")));
+    }
+
+    #[test]
+    fn ipv4_mapped_peers_match_ipv4_rules() {
+        // What a dual-stack listener reports for an IPv4 client
+        let mapped = ip("::ffff:192.168.1.20");
+        assert!("192.168.1.0/24".parse::<Cidr>().unwrap().contains(mapped));
+        assert!("::ffff:192.168.1.0/120".parse::<Cidr>().unwrap().contains(ip("192.168.1.20")));
+
+        let filter = filter(false, &[], &["192.168.1.20"]);
+        assert!(filter.check(mapped).is_err());
+    }
+
+    #[test]
+    fn deny_wins_over_allow() {
+        let filter = filter(false, &["10.0.0.0/8"], &["10.0.0.5"]);
+        assert!(filter.check(ip("10.0.0.4")).is_ok());
+        assert_eq!(filter.check(ip("10.0.0.5")), Err("Address is on the deny list"));
+        assert_eq!(filter.check(ip("11.0.0.1")), Err("Address is not on the allow list"));
+    }
+
+    #[test]
+    fn no_rules_allow_everyone() {
+        let filter = filter(false, &[], &[]);
+        assert!(filter.check(ip("203.0.113.9")).is_ok());
+        assert!(filter.check(ip("2001:db8::1")).is_ok());
+    }
+
+    #[test]
+    fn lan_preset_allows_only_local_ranges() {
+        let filter = filter(true, &["203.0.113.9"], &[]);
+        for local in ["127.0.0.1", "10.2.3.4", "172.31.255.255", "192.168.0.10", "169.254.1.1", "::1", "fd00::1", "fe80::1"] {
+            assert!(filter.check(ip(local)).is_ok(), "{}", local);
+        }
+        assert!(filter.check(ip("203.0.113.9")).is_ok(), "explicit allow rules still apply");
+        for public in ["172.32.0.1", "8.8.8.8", "2001:db8::1", "::ffff:8.8.8.8"] {
+            assert!(filter.check(ip(public)).is_err(), "{}", public);
+        }
+    }
+
+    #[test]
+    fn invalid_rules_are_refused() {
+        let rules = IpRules { lan_only: false, allow: vec!["10.0.0.0/8".to_string()], deny: vec!["nope".to_string()] };
+        assert!(IpFilter::new(&rules).is_err());
+    }
+}
@@ -0,0 +1,399 @@
+// Brute-force protection for remote logins. Every login attempt is checked
+// before the password is: an IP may only try so often per minute, a username
+// that keeps failing is locked out for exponentially longer, and an IP that
+// keeps failing is banned for a while. Bans live in `remote_ip_bans` so they
+// survive restarts and can be cleared by the admin; the counters are in memory.
+//
+// Usernames are tracked whether or not they exist, so a lockout does not give
+// away which accounts are real.
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+// Failures from one IP older than this no longer count towards a ban
+const FAILURE_WINDOW: Duration = Duration::from_secs(15 * 60);
+// Counters are pruned once this many IPs or usernames are tracked
+const MAX_TRACKED: usize = 10_000;
+
+/// `remote_config.limits`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct RemoteLimits {
+    /// Connections open at once: sockets, API requests and redirects alike
+    pub max_connections: usize,
+    /// Largest socket.io message or HTTP body accepted
+    pub max_request_bytes: u64,
+    /// Login attempts per IP per minute
+    pub login_attempts_per_minute: usize,
+    /// Consecutive failures before a username is locked out
+    pub lockout_threshold: u32,
+    /// First lockout; each further failure doubles it
+    pub lockout_base_secs: u64,
+    pub lockout_max_secs: u64,
+    /// Failures from one IP within 15 minutes before it is banned
+    pub ban_threshold: usize,
+    pub ban_duration_secs: u64,
+}
+
+impl Default for RemoteLimits {
+    fn default() -> Self {
+        RemoteLimits {
+            max_connections: 50,
+            // Room for a base64-encoded 8 MiB upload chunk
+            max_request_bytes: 16 * 1024 * 1024,
+            login_attempts_per_minute: 10,
+            lockout_threshold: 5,
+            lockout_base_secs: 30,
+            lockout_max_secs: 60 * 60,
+            ban_threshold: 20,
+            ban_duration_secs: 60 * 60,
+        }
+    }
+}
+
+// Below this an upload chunk no longer fits in a request
+const MIN_REQUEST_BYTES: u64 = 1024 * 1024;
+
+impl RemoteLimits {
+    /// Refuses values that would lock everyone out or make the server unusable.
+    pub fn validate(&self) -> Result<(), String> {
+        let at_least_one = [
+            ("max_connections", self.max_connections as u64),
+            ("login_attempts_per_minute", self.login_attempts_per_minute as u64),
+            ("lockout_threshold", u64::from(self.lockout_threshold)),
+            ("lockout_base_secs", self.lockout_base_secs),
+            ("ban_threshold", self.ban_threshold as u64),
+            ("ban_duration_secs", self.ban_duration_secs),
+        ];
+        if let Some((name, _)) = at_least_one.iter().find(|(_, value)| *value == 0) {
+            return Err(format!("{} must be at least 1", name));
+        }
+        if self.max_request_bytes < MIN_REQUEST_BYTES {
+            return Err(format!("max_request_bytes must be at least {}", MIN_REQUEST_BYTES));
+        }
+        if self.lockout_max_secs < self.lockout_base_secs {
+            return Err("lockout_max_secs must not be shorter than lockout_base_secs".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IpBan {
+    pub ip: String,
+    pub reason: String,
+    pub banned_at: String,
+    pub expires_at: String,
+}
+
+#[derive(Default)]
+struct IpRecord {
+    attempts: VecDeque<Instant>,
+    failures: VecDeque<Instant>,
+}
+
+#[derive(Default)]
+struct UserRecord {
+    failures: u32,
+    locked_until: Option<Instant>,
+}
+
+fn retry_message(what: &str, until: Instant) -> String {
+    let secs = until.saturating_duration_since(Instant::now()).as_secs().max(1);
+    format!("{}, try again in {}s", what, secs)
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// Forgets IPs with no attempt or failure recent enough to still count
+fn prune_ips(ips: &mut HashMap<IpAddr, IpRecord>, now: Instant) {
+    ips.retain(|_, r| {
+        r.attempts.back().into_iter().chain(r.failures.back()).any(|t| now.duration_since(*t) < FAILURE_WINDOW)
+    });
+}
+
+pub struct LoginGuard {
+    limits: RwLock<RemoteLimits>,
+    ips: Mutex<HashMap<IpAddr, IpRecord>>,
+    users: Mutex<HashMap<String, UserRecord>>,
+}
+
+impl LoginGuard {
+    pub fn new(limits: RemoteLimits) -> LoginGuard {
+        LoginGuard {
+            limits: RwLock::new(limits),
+            ips: Mutex::new(HashMap::new()),
+            users: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn limits(&self) -> RemoteLimits {
+        self.limits.read().unwrap().clone()
+    }
+
+    pub fn set_limits(&self, limits: RemoteLimits) {
+        *self.limits.write().unwrap() = limits;
+    }
+
+    /// Counts a login attempt and refuses it if the IP is over its rate or the username is locked out.
+    pub fn check_login(&self, ip: Option<IpAddr>, username: &str) -> Result<(), String> {
+        let limits = self.limits();
+        let now = Instant::now();
+
+        if let Some(locked_until) = self
+            .users
+            .lock()
+            .unwrap()
+            .get(&username.to_lowercase())
+            .and_then(|u| u.locked_until)
+            .filter(|until| *until > now)
+        {
+            return Err(retry_message("Too many failed logins for this account", locked_until));
+        }
+
+        if let Some(ip) = ip {
+            let mut ips = self.ips.lock().unwrap();
+            if ips.len() > MAX_TRACKED {
+                prune_ips(&mut ips, now);
+            }
+            let record = ips.entry(ip).or_default();
+            while record.attempts.front().is_some_and(|t| now.duration_since(*t) >= RATE_WINDOW) {
+                record.attempts.pop_front();
+            }
+            if record.attempts.len() >= limits.login_attempts_per_minute {
+                let until = record.attempts[0] + RATE_WINDOW;
+                return Err(retry_message("Too many login attempts", until));
+            }
+            record.attempts.push_back(now);
+        }
+        Ok(())
+    }
+
+    /// Records a failed login. Returns true when the IP has now earned a ban.
+    pub fn record_failure(&self, ip: Option<IpAddr>, username: &str) -> bool {
+        let limits = self.limits();
+        let now = Instant::now();
+
+        {
+            let mut users = self.users.lock().unwrap();
+            if users.len() > MAX_TRACKED {
+                users.retain(|_, u| u.locked_until.is_some_and(|t| t > now));
+            }
+            let user = users.entry(username.to_lowercase()).or_default();
+            user.failures += 1;
+            if user.failures >= limits.lockout_threshold {
+                let doublings = (user.failures - limits.lockout_threshold).min(16);
+                let secs = limits.lockout_base_secs.saturating_mul(1 << doublings).min(limits.lockout_max_secs);
+                user.locked_until = Some(now + Duration::from_secs(secs));
+                eprintln!("[REMOTE] Locked out username {} for {}s", username, secs);
+            }
+        }
+
+        ip.is_some_and(|ip| self.record_ip_failure(ip))
+    }
+
+    /// Counts a failure against an IP only, for attempts that name no user,
+    /// such as a bad API token. Returns true when the IP has now earned a ban.
+    pub fn record_ip_failure(&self, ip: IpAddr) -> bool {
+        let limits = self.limits();
+        let now = Instant::now();
+        let mut ips = self.ips.lock().unwrap();
+        if ips.len() > MAX_TRACKED {
+            prune_ips(&mut ips, now);
+        }
+        let record = ips.entry(ip).or_default();
+        while record.failures.front().is_some_and(|t| now.duration_since(*t) >= FAILURE_WINDOW) {
+            record.failures.pop_front();
+        }
+        record.failures.push_back(now);
+        if record.failures.len() >= limits.ban_threshold {
+            record.failures.clear();
+            return true;
+        }
+        false
+    }
+
+    pub fn record_success(&self, username: &str) {
+        self.users.lock().unwrap().remove(&username.to_lowercase());
+    }
+
+    /// Drops the counters of an IP whose ban was lifted, so it starts over.
+    pub fn forget_ip(&self, ip: IpAddr) {
+        self.ips.lock().unwrap().remove(&ip);
+    }
+}
+
+/// The active ban on an IP, if any.
+pub fn active_ban(db_path: &str, ip: IpAddr) -> Result<Option<IpBan>, String> {
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    conn.query_row(
+        "SELECT ip, reason, banned_at, expires_at FROM remote_ip_bans WHERE ip = ?1 AND expires_at > ?2",
+        rusqlite::params![ip.to_string(), timestamp(Utc::now())],
+        |row| {
+            Ok(IpBan {
+                ip: row.get(0)?,
+                reason: row.get(1)?,
+                banned_at: row.get(2)?,
+                expires_at: row.get(3)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+pub fn ban(db_path: &str, ip: IpAddr, reason: &str, duration: Duration) -> Result<IpBan, String> {
+    let now = Utc::now();
+    let ban = IpBan {
+        ip: ip.to_string(),
+        reason: reason.to_string(),
+        banned_at: timestamp(now),
+        expires_at: timestamp(now + chrono::Duration::from_std(duration).map_err(|e| e.to_string())?),
+    };
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT OR REPLACE INTO remote_ip_bans (ip, reason, banned_at, expires_at) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![ban.ip, ban.reason, ban.banned_at, ban.expires_at],
+    )
+    .map_err(|e| e.to_string())?;
+    eprintln!("[REMOTE] Banned {} until {}: {}", ban.ip, ban.expires_at, reason);
+    Ok(ban)
+}
+
+/// Bans that have not expired; expired ones are deleted on the way.
+pub fn list_bans(db_path: &str) -> Result<Vec<IpBan>, String> {
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let now = timestamp(Utc::now());
+    conn.execute("DELETE FROM remote_ip_bans WHERE expires_at <= ?1", [&now])
+        .map_err(|e| e.to_string())?;
+
+    let mut stmt = conn
+        .prepare("SELECT ip, reason, banned_at, expires_at FROM remote_ip_bans ORDER BY banned_at DESC")
+        .map_err(|e| e.to_string())?;
+    let bans = stmt
+        .query_map([], |row| {
+            Ok(IpBan {
+                ip: row.get(0)?,
+                reason: row.get(1)?,
+                banned_at: row.get(2)?,
+                expires_at: row.get(3)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(bans)
+}
+
+pub fn clear_ban(db_path: &str, ip: &str) -> Result<bool, String> {
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let rows = conn
+        .execute("DELETE FROM remote_ip_bans WHERE ip = ?1", [ip])
+        .map_err(|e| e.to_string())?;
+    Ok(rows > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard() -> LoginGuard {
+        LoginGuard::new(RemoteLimits {
+            login_attempts_per_minute: 100,
+            lockout_threshold: 3,
+            lockout_base_secs: 30,
+            lockout_max_secs: 100,
+            ban_threshold: 4,
+            ..Default::default()
+        })
+    }
+
+    fn locked_for(guard: &LoginGuard, username: &str) -> u64 {
+        let until = guard.users.lock().unwrap()[username].locked_until.unwrap();
+        // Rounded up, as the lockout was set a moment ago
+        until.saturating_duration_since(Instant::now()).as_secs() + 1
+    }
+
+    #[test]
+    fn lockout_doubles_up_to_the_maximum() {
+        let guard = guard();
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        for _ in 0..2 {
+            guard.record_failure(Some(ip), "Alice");
+        }
+        assert!(guard.check_login(None, "alice").is_ok());
+
+        guard.record_failure(None, "alice");
+        assert_eq!(locked_for(&guard, "alice"), 30);
+        assert!(guard.check_login(None, "ALICE").is_err());
+        guard.record_failure(None, "alice");
+        assert_eq!(locked_for(&guard, "alice"), 60);
+        guard.record_failure(None, "alice");
+        assert_eq!(locked_for(&guard, "alice"), 100);
+
+        // Other accounts are unaffected, and success starts over
+        assert!(guard.check_login(None, "bob").is_ok());
+        guard.record_success("alice");
+        assert!(guard.check_login(None, "alice").is_ok());
+    }
+
+    #[test]
+    fn ban_after_threshold_failures_from_one_ip() {
+        let guard = guard();
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let other: IpAddr = "10.0.0.2".parse().unwrap();
+
+        let results: Vec<bool> = (0..4).map(|i| guard.record_failure(Some(ip), &format!("user{}", i))).collect();
+        assert_eq!(results, [false, false, false, true]);
+        assert!(!guard.record_ip_failure(other));
+        // Counting starts over after a ban
+        assert!(!guard.record_ip_failure(ip));
+    }
+
+    #[test]
+    fn rate_limit_per_ip() {
+        let guard = LoginGuard::new(RemoteLimits { login_attempts_per_minute: 2, ..Default::default() });
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        assert!(guard.check_login(Some(ip), "a").is_ok());
+        assert!(guard.check_login(Some(ip), "b").is_ok());
+        assert!(guard.check_login(Some(ip), "c").is_err());
+        assert!(guard.check_login(Some("10.0.0.2".parse().unwrap()), "a").is_ok());
+    }
+
+    #[test]
+    fn stale_ips_are_pruned() {
+        let guard = guard();
+        let old = Instant::now() - FAILURE_WINDOW - Duration::from_secs(1);
+        {
+            let mut ips = guard.ips.lock().unwrap();
+            for i in 0..=MAX_TRACKED as u32 {
+                let record = ips.entry(IpAddr::from(i.to_be_bytes())).or_default();
+                record.failures.push_back(old);
+            }
+        }
+        guard.record_ip_failure("10.0.0.1".parse().unwrap());
+        assert_eq!(guard.ips.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn limits_that_lock_everyone_out_are_refused() {
+        assert!(RemoteLimits::default().validate().is_ok());
+        for limits in [
+            RemoteLimits { login_attempts_per_minute: 0, ..Default::default() },
+            RemoteLimits { ban_threshold: 0, ..Default::default() },
+            RemoteLimits { max_connections: 0, ..Default::default() },
+            RemoteLimits { lockout_threshold: 0, ..Default::default() },
+            RemoteLimits { max_request_bytes: 1024, ..Default::default() },
+            RemoteLimits { lockout_base_secs: 60, lockout_max_secs: 30, ..Default::default() },
+        ] {
+            assert!(limits.validate().is_err(), "{:?}", limits);
+        }
+    }
+}
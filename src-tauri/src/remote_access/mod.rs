@@ -0,0 +1,247 @@
+// Embedded remote access server, replacing the legacy RemoteSocketServer.ts.
+// It runs while `remote_config.enabled` is set and serves the same socket.io
+// protocol, backed by the command modules, plus a REST API for scripts.
+pub mod api_tokens;
+pub mod audit;
+mod dispatch;
+pub mod ip_filter;
+pub mod limits;
+pub mod permissions;
+mod rest;
+pub mod roles;
+mod server;
+mod sessions;
+mod stream;
+pub mod tls;
+mod tokens;
+
+pub use ip_filter::{IpFilter, IpRules};
+pub use limits::{IpBan, RemoteLimits};
+pub use server::{RemotePolicy, RemoteServer, RemoteSettings};
+pub use sessions::RemoteSession;
+pub use tls::TlsConfig;
+
+use rusqlite::Connection;
+use serde_json::Value;
+use std::net::IpAddr;
+use tauri::{AppHandle, Manager};
+
+use crate::AppState;
+
+pub const DEFAULT_PORT: u16 = 9999;
+pub const DEFAULT_BIND_ADDRESS: &str = "0.0.0.0";
+
+/// `remote_config` as the server uses it.
+pub struct RemoteConfig {
+    pub enabled: bool,
+    pub settings: RemoteSettings,
+    pub policy: RemotePolicy,
+}
+
+/// Names a self-signed certificate is issued for: loopback plus the addresses
+/// the user gave for reaching this machine.
+pub fn certificate_names(config: &Value) -> Vec<String> {
+    let mut names = vec!["localhost".to_string(), "127.0.0.1".to_string(), "::1".to_string()];
+    let tunnel_host = config["tunnelUrl"]
+        .as_str()
+        .and_then(|url| url.split("://").last())
+        .and_then(|rest| rest.split(['/', ':']).next());
+    let configured = [config["ipv4"].as_str(), config["ipv6"].as_str(), config["bind_address"].as_str(), tunnel_host];
+    for name in configured.into_iter().flatten().map(str::trim) {
+        let wildcard = name.parse::<IpAddr>().is_ok_and(|ip| ip.is_unspecified());
+        if !name.is_empty() && !wildcard && !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// Reads `remote_config`. Invalid IP rules are an error rather than ignored,
+/// so a typo cannot open the server to everyone; limits that would lock
+/// everyone out fall back to the defaults. With TLS on, a missing
+/// self-signed certificate is generated here.
+pub fn load_config(db_path: &str) -> Result<RemoteConfig, String> {
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let config: Value = conn
+        .query_row("SELECT value FROM config WHERE key = 'remote_config'", [], |row| row.get::<_, String>(0))
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or(Value::Null);
+
+    let limits: RemoteLimits = serde_json::from_value(config["limits"].clone())
+        .ok()
+        .filter(|limits: &RemoteLimits| limits.validate().is_ok())
+        .unwrap_or_default();
+    let ip_rules: IpRules = match &config["ip_rules"] {
+        Value::Null => IpRules::default(),
+        rules => serde_json::from_value(rules.clone()).map_err(|e| format!("Invalid IP rules: {}", e))?,
+    };
+    let enabled = config["enabled"].as_bool().unwrap_or(false);
+    let tls = if enabled {
+        let tls_config: TlsConfig = serde_json::from_value(config["tls"].clone()).unwrap_or_default();
+        tls::resolve(&tls_config, db_path, &certificate_names(&config))?
+    } else {
+        None
+    };
+    let settings = RemoteSettings {
+        bind_address: config["bind_address"]
+            .as_str()
+            .filter(|a| !a.is_empty())
+            .unwrap_or(DEFAULT_BIND_ADDRESS)
+            .to_string(),
+        port: config["port"]
+            .as_u64()
+            .and_then(|p| u16::try_from(p).ok())
+            .unwrap_or(DEFAULT_PORT),
+        max_request_bytes: limits.max_request_bytes,
+        tls,
+    };
+    Ok(RemoteConfig {
+        enabled,
+        settings,
+        policy: RemotePolicy { limits, ip_filter: IpFilter::new(&ip_rules)? },
+    })
+}
+
+/// Starts, stops or restarts the server to match `remote_config`. A server
+/// already listening with the right settings is left alone, so its clients
+/// stay connected; limits and IP rules are applied to it in place.
+pub async fn apply_config(app_handle: &AppHandle) -> Result<(), String> {
+    let state = app_handle.state::<AppState>();
+    let db_path = state.db_path.lock().unwrap().clone();
+    let config = load_config(&db_path)?;
+
+    let mut running = state.remote_server.lock().await;
+    if let Some(server) = running.take() {
+        if config.enabled && server.settings() == &config.settings {
+            server.set_policy(config.policy);
+            *running = Some(server);
+            return Ok(());
+        }
+        server.stop().await;
+    }
+
+    if config.enabled {
+        *running = Some(RemoteServer::start(config.settings, config.policy, db_path, app_handle.clone()).await?);
+    }
+    Ok(())
+}
+
+/// Revokes every session of a user and disconnects their sockets.
+pub async fn revoke_user(app_handle: &AppHandle, user_id: &str, reason: &str) -> Result<(), String> {
+    let state = app_handle.state::<AppState>();
+    let db_path = state.db_path.lock().unwrap().clone();
+    let revoked = sessions::revoke_user_sessions(&db_path, user_id, reason)?;
+
+    let running = state.remote_server.lock().await;
+    let disconnected = running.as_ref().map_or(0, |server| server.disconnect_user(user_id, reason));
+    if revoked > 0 || disconnected > 0 {
+        eprintln!("[REMOTE] Revoked {} session(s) of user {}: {}", revoked, user_id, reason);
+    }
+    Ok(())
+}
+
+/// Revokes one session and disconnects its sockets; false if it was not live.
+pub async fn revoke_session(app_handle: &AppHandle, session_id: &str, reason: &str) -> Result<bool, String> {
+    let state = app_handle.state::<AppState>();
+    let db_path = state.db_path.lock().unwrap().clone();
+    let revoked = sessions::revoke_session(&db_path, session_id, reason)?.is_some();
+
+    let running = state.remote_server.lock().await;
+    if let Some(server) = running.as_ref() {
+        server.disconnect_session(session_id, reason);
+    }
+    Ok(revoked)
+}
+
+/// Live sessions, marked with whether they have a socket connected.
+pub async fn list_sessions(app_handle: &AppHandle, include_revoked: bool) -> Result<Vec<RemoteSession>, String> {
+    let state = app_handle.state::<AppState>();
+    let db_path = state.db_path.lock().unwrap().clone();
+    let mut listed = sessions::list_sessions(&db_path, include_revoked)?;
+
+    let running = state.remote_server.lock().await;
+    if let Some(server) = running.as_ref() {
+        let connected = server.connected_sessions();
+        for session in &mut listed {
+            session.connected = connected.contains(&session.id);
+        }
+    }
+    Ok(listed)
+}
+
+/// Recomputes the effective permissions of users whose roles or grants
+/// changed and hands them to their live sessions.
+pub async fn refresh_permissions(app_handle: &AppHandle, user_ids: &[String]) -> Result<(), String> {
+    let state = app_handle.state::<AppState>();
+    let db_path = state.db_path.lock().unwrap().clone();
+    let updates = {
+        let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+        user_ids
+            .iter()
+            .map(|id| Ok((id, roles::effective_permissions(&conn, id)?)))
+            .collect::<Result<Vec<_>, String>>()?
+    };
+
+    let running = state.remote_server.lock().await;
+    if let Some(server) = running.as_ref() {
+        for (user_id, permissions) in &updates {
+            server.update_permissions(user_id, permissions);
+        }
+    }
+    Ok(())
+}
+
+/// Lifts a ban; the running server also forgets the IP's failed logins.
+pub async fn clear_ban(app_handle: &AppHandle, ip: &str) -> Result<bool, String> {
+    let state = app_handle.state::<AppState>();
+    let db_path = state.db_path.lock().unwrap().clone();
+    let cleared = limits::clear_ban(&db_path, ip)?;
+
+    if let Ok(ip) = ip.parse::<IpAddr>() {
+        let running = state.remote_server.lock().await;
+        if let Some(server) = running.as_ref() {
+            server.forget_ip(ip);
+        }
+    }
+    Ok(cleared)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_with(ip_rules: Value) -> Result<RemoteConfig, String> {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("app.db").to_string_lossy().to_string();
+        crate::initialize_database(&db_path).unwrap();
+        let config = serde_json::json!({ "enabled": false, "ip_rules": ip_rules });
+        Connection::open(&db_path)
+            .unwrap()
+            .execute(
+                "INSERT OR REPLACE INTO config (key, value) VALUES ('remote_config', ?1)",
+                [config.to_string()],
+            )
+            .unwrap();
+        load_config(&db_path)
+    }
+
+    #[test]
+    fn malformed_ip_rules_are_an_error() {
+        for rules in [
+            serde_json::json!("10.0.0.0/8"),
+            serde_json::json!({ "allow": "10.0.0.0/8" }),
+            serde_json::json!({ "deny": [42] }),
+            serde_json::json!({ "allow": ["10.0.0.0/33"] }),
+        ] {
+            assert!(load_with(rules.clone()).is_err(), "{}", rules);
+        }
+    }
+
+    #[test]
+    fn missing_ip_rules_allow_everyone() {
+        assert!(load_with(Value::Null).is_ok());
+        assert!(load_with(serde_json::json!({})).is_ok());
+        assert!(load_with(serde_json::json!({ "allow": ["10.0.0.0/8"] })).is_ok());
+    }
+}
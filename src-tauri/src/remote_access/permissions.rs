@@ -0,0 +1,187 @@
+// The permission catalogue for remote users and the command → permission map,
+// carried over from PermissionsManager.ts and RemoteSocketServer.ts.
+//
+// A grant is a permission id (`server.start`), a whole category (`files.*`)
+// or everything (`*`). Commands missing from the map are refused, so a new
+// command cannot be invoked remotely until it is given a permission.
+use serde::Serialize;
+
+use super::sessions::Identity;
+
+#[derive(Debug, Serialize, Clone, Copy)]
+pub struct Permission {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub category: &'static str,
+}
+
+const fn permission(id: &'static str, name: &'static str, description: &'static str, category: &'static str) -> Permission {
+    Permission { id, name, description, category }
+}
+
+pub const PERMISSIONS: &[Permission] = &[
+    permission("server.start", "Start server", "Start the Hytale server", "server"),
+    permission("server.stop", "Stop server", "Stop the Hytale server", "server"),
+    permission("server.restart", "Restart server", "Restart the Hytale server", "server"),
+    permission("server.status", "View status", "See whether the server is running", "server"),
+    permission("server.logs", "View logs", "Read the server console output", "server"),
+    permission("server.command", "Send commands", "Send console commands to the server", "server"),
+    permission("config.read", "Read configuration", "Read the server configuration (RAM, CPU)", "config"),
+    permission("config.write", "Change configuration", "Change the server configuration", "config"),
+    permission("backup.create", "Create backups", "Create server backups", "backup"),
+    permission("backup.restore", "Restore backups", "Restore the server from a backup", "backup"),
+    permission("backup.delete", "Delete backups", "Delete existing backups", "backup"),
+    permission("backup.list", "List backups", "See the list of backups", "backup"),
+    permission("files.list", "List files", "List files in the server directory", "files"),
+    permission("files.read", "Read files", "Read and download file contents", "files"),
+    permission("files.write", "Modify files", "Rename, move, copy, extract and compress files", "files"),
+    permission("files.upload", "Upload files", "Upload files and save edits", "files"),
+    permission("files.delete", "Delete files", "Delete files from the server directory", "files"),
+    permission("discord.config", "Configure Discord", "Configure the Discord integration", "discord"),
+];
+
+/// The permission a remote command needs, or None if it cannot be invoked remotely.
+pub fn required_permission(command: &str) -> Option<&'static str> {
+    Some(match command {
+        "server:start" => "server.start",
+        "server:stop" => "server.stop",
+        "server:restart" => "server.restart",
+        "server:status" | "server:path" => "server.status",
+        "server:logs" => "server.logs",
+        "server:send-command" => "server.command",
+        // Subscriptions to live events rather than commands
+        "stream:logs" => "server.logs",
+        "stream:status" | "stream:players" | "stream:metrics" => "server.status",
+
+        "config:read" | "config:system-resources" => "config.read",
+        "config:write" => "config.write",
+
+        "backup:create" => "backup.create",
+        "backup:restore" => "backup.restore",
+        "backup:list" => "backup.list",
+        "backup:delete" => "backup.delete",
+
+        "files:list" => "files.list",
+        "files:info" | "files:is-editable" | "files:read" | "files:download" | "files:search"
+        | "files:download-begin" | "files:download-chunk" => "files.read",
+        "files:write" | "files:upload" | "files:upload-begin" | "files:upload-chunk" | "files:upload-finish"
+        | "files:upload-abort" => "files.upload",
+        "files:mkdir" | "files:rename" | "files:move" | "files:copy" | "files:extract" | "files:compress" => "files.write",
+        "files:delete" | "files:request-delete" | "files:delete-many" => "files.delete",
+
+        "discord:view" | "discord:configure" | "discord:test" | "discord:send" => "discord.config",
+
+        _ => return None,
+    })
+}
+
+fn is_valid_grant(grant: &str) -> bool {
+    grant == "*"
+        || PERMISSIONS.iter().any(|p| p.id == grant)
+        || grant
+            .strip_suffix(".*")
+            .is_some_and(|category| PERMISSIONS.iter().any(|p| p.category == category))
+}
+
+/// Rejects grants that name no permission or category, so typos do not pass silently.
+pub fn validate_grants(grants: &[String]) -> Result<(), String> {
+    match grants.iter().find(|g| !is_valid_grant(g)) {
+        Some(unknown) => Err(format!("Unknown permission: {}", unknown)),
+        None => Ok(()),
+    }
+}
+
+pub fn has_permission(grants: &[String], required: &str) -> bool {
+    let category = required.split('.').next().unwrap_or_default();
+    grants
+        .iter()
+        .any(|g| g == "*" || g == required || g.strip_suffix(".*") == Some(category))
+}
+
+/// The one check in front of every remote command.
+pub fn authorize(identity: &Identity, command: &str) -> Result<(), String> {
+    let required = required_permission(command).ok_or_else(|| format!("Command not available remotely: {}", command))?;
+    if has_permission(&identity.permissions, required) {
+        Ok(())
+    } else {
+        Err(format!("Permission denied. Required: {}", required))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grants(grants: &[&str]) -> Vec<String> {
+        grants.iter().map(|g| g.to_string()).collect()
+    }
+
+    fn identity(permissions: &[&str]) -> Identity {
+        Identity { user_id: "u1".to_string(), username: "alice".to_string(), permissions: grants(permissions) }
+    }
+
+    #[test]
+    fn wildcard_grants_everything() {
+        let all = grants(&["*"]);
+        assert!(PERMISSIONS.iter().all(|p| has_permission(&all, p.id)));
+    }
+
+    #[test]
+    fn category_grants_only_that_category() {
+        let files = grants(&["files.*"]);
+        assert!(PERMISSIONS.iter().filter(|p| p.category == "files").all(|p| has_permission(&files, p.id)));
+        assert!(!has_permission(&files, "server.start"));
+        // A category is matched whole, not by prefix
+        assert!(!has_permission(&grants(&["file.*"]), "files.read"));
+        assert!(!has_permission(&grants(&["files"]), "files.read"));
+    }
+
+    #[test]
+    fn exact_grants_match_only_themselves() {
+        let read = grants(&["files.read", "server.status"]);
+        assert!(has_permission(&read, "files.read"));
+        assert!(has_permission(&read, "server.status"));
+        assert!(!has_permission(&read, "files.write"));
+        assert!(!has_permission(&[], "files.read"));
+    }
+
+    #[test]
+    fn unknown_grants_are_rejected() {
+        assert!(validate_grants(&grants(&["*", "files.*", "server.start"])).is_ok());
+        for unknown in ["files.everything", "nope.*", "server", "", "**"] {
+            assert!(validate_grants(&grants(&["server.start", unknown])).is_err(), "{}", unknown);
+        }
+    }
+
+    #[test]
+    fn authorize_maps_commands_to_permissions() {
+        let user = identity(&["files.read"]);
+        assert!(authorize(&user, "files:read").is_ok());
+        assert!(authorize(&user, "files:download-chunk").is_ok());
+        assert_eq!(authorize(&user, "files:delete"), Err("Permission denied. Required: files.delete".to_string()));
+    }
+
+    #[test]
+    fn unmapped_commands_are_refused_even_with_wildcard() {
+        let admin = identity(&["*"]);
+        for command in ["remote:set-config", "users:delete", "audit:export", ""] {
+            assert!(authorize(&admin, command).unwrap_err().starts_with("Command not available remotely"), "{}", command);
+        }
+    }
+
+    #[test]
+    fn mapped_commands_need_catalogued_permissions() {
+        let commands = [
+            "server:start", "server:stop", "server:restart", "server:status", "server:path", "server:logs",
+            "server:send-command", "stream:logs", "stream:status", "stream:players", "stream:metrics",
+            "config:read", "config:system-resources", "config:write", "backup:create", "backup:restore",
+            "backup:list", "backup:delete", "files:list", "files:read", "files:write", "files:mkdir",
+            "files:delete", "discord:configure",
+        ];
+        for command in commands {
+            let required = required_permission(command).unwrap_or_else(|| panic!("{} is not mapped", command));
+            assert!(PERMISSIONS.iter().any(|p| p.id == required), "{} needs unknown {}", command, required);
+        }
+    }
+}
@@ -0,0 +1,725 @@
+// Versioned REST API under `/api/v1`, for scripts and CI that have no
+// socket.io client. Every route stands for a remote command: the request is
+// turned into the command's positional args and goes through the same
+// permission check and `dispatch` as a socket `command` event.
+//
+//   curl -H "Authorization: Bearer hsp_..." https://host:9999/api/v1/server/status
+//
+// Requests authenticate with a personal API token; see `api_tokens`. Bad
+// tokens count towards the IP ban like failed logins. The routes are declared
+// once in `ROUTES`, from which both the router and the OpenAPI document at
+// `/api/v1/openapi.json` are built.
+use axum::body::Bytes;
+use axum::extract::{ConnectInfo, Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, on, MethodFilter};
+use axum::{Json, Router};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::services::audit_log::{self, Actor, Channel};
+use super::api_tokens;
+use super::dispatch;
+use super::limits;
+use super::permissions;
+use super::server::{self, RemoteContext};
+
+#[derive(Clone, Copy)]
+enum Verb {
+    Get,
+    Post,
+    Put,
+    Delete,
+}
+
+impl Verb {
+    fn filter(self) -> MethodFilter {
+        match self {
+            Verb::Get => MethodFilter::GET,
+            Verb::Post => MethodFilter::POST,
+            Verb::Put => MethodFilter::PUT,
+            Verb::Delete => MethodFilter::DELETE,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Verb::Get => "get",
+            Verb::Post => "post",
+            Verb::Put => "put",
+            Verb::Delete => "delete",
+        }
+    }
+}
+
+/// Where a command argument is read from.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Path,
+    Query,
+    /// A property of the JSON body
+    Field,
+    /// The whole JSON body
+    Body,
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    String,
+    Boolean,
+    Array,
+    Object,
+}
+
+impl Kind {
+    fn schema(self) -> Value {
+        match self {
+            Kind::String => json!({ "type": "string" }),
+            Kind::Boolean => json!({ "type": "boolean" }),
+            Kind::Array => json!({ "type": "array", "items": {} }),
+            Kind::Object => json!({ "type": "object" }),
+        }
+    }
+}
+
+/// One positional argument of the command, in order.
+struct Param {
+    name: &'static str,
+    source: Source,
+    kind: Kind,
+    required: bool,
+    description: &'static str,
+}
+
+const fn path(name: &'static str, description: &'static str) -> Param {
+    Param { name, source: Source::Path, kind: Kind::String, required: true, description }
+}
+
+const fn query(name: &'static str, kind: Kind, required: bool, description: &'static str) -> Param {
+    Param { name, source: Source::Query, kind, required, description }
+}
+
+const fn field(name: &'static str, kind: Kind, required: bool, description: &'static str) -> Param {
+    Param { name, source: Source::Field, kind, required, description }
+}
+
+const fn body(description: &'static str) -> Param {
+    Param { name: "body", source: Source::Body, kind: Kind::Object, required: true, description }
+}
+
+struct ApiRoute {
+    verb: Verb,
+    /// Relative to `/api/v1`, with `{name}` path parameters
+    path: &'static str,
+    command: &'static str,
+    summary: &'static str,
+    params: &'static [Param],
+}
+
+const fn route(
+    verb: Verb,
+    path: &'static str,
+    command: &'static str,
+    summary: &'static str,
+    params: &'static [Param],
+) -> ApiRoute {
+    ApiRoute { verb, path, command, summary, params }
+}
+
+const ROUTES: &[ApiRoute] = &[
+    // Server
+    route(Verb::Get, "/server/status", "server:status", "Whether the server is running", &[]),
+    route(Verb::Get, "/server/logs", "server:logs", "Recent console output", &[]),
+    route(Verb::Get, "/server/path", "server:path", "The server directory", &[]),
+    route(Verb::Post, "/server/start", "server:start", "Start the server", &[]),
+    route(Verb::Post, "/server/stop", "server:stop", "Stop the server", &[]),
+    route(Verb::Post, "/server/restart", "server:restart", "Restart the server", &[]),
+    route(
+        Verb::Post,
+        "/server/command",
+        "server:send-command",
+        "Send a console command",
+        &[field("command", Kind::String, true, "The command line, without a leading slash")],
+    ),
+    // Config
+    route(Verb::Get, "/config", "config:read", "Read the server configuration", &[]),
+    route(Verb::Put, "/config", "config:write", "Replace the server configuration", &[body("The configuration")]),
+    route(Verb::Get, "/config/system-resources", "config:system-resources", "Memory and CPUs of the host", &[]),
+    // Backups
+    route(Verb::Get, "/backups", "backup:list", "List backups", &[]),
+    route(
+        Verb::Post,
+        "/backups",
+        "backup:create",
+        "Create a backup",
+        &[
+            field("name", Kind::String, false, "Name of the backup"),
+            field("profile", Kind::String, false, "Backup profile to apply"),
+        ],
+    ),
+    route(Verb::Post, "/backups/{id}/restore", "backup:restore", "Restore a backup", &[path("id", "Backup id")]),
+    route(Verb::Delete, "/backups/{id}", "backup:delete", "Delete a backup", &[path("id", "Backup id")]),
+    // Files
+    route(
+        Verb::Get,
+        "/files",
+        "files:list",
+        "List a directory",
+        &[query("directory", Kind::String, false, "Directory relative to the server root")],
+    ),
+    route(
+        Verb::Delete,
+        "/files",
+        "files:delete",
+        "Delete a file or directory",
+        &[query("path", Kind::String, true, "Path relative to the server root")],
+    ),
+    route(
+        Verb::Get,
+        "/files/info",
+        "files:info",
+        "Details of a file",
+        &[query("path", Kind::String, true, "Path relative to the server root")],
+    ),
+    route(
+        Verb::Get,
+        "/files/content",
+        "files:read",
+        "Read a text file",
+        &[query("path", Kind::String, true, "Path relative to the server root")],
+    ),
+    route(
+        Verb::Put,
+        "/files/content",
+        "files:write",
+        "Write a text file",
+        &[
+            field("path", Kind::String, true, "Path relative to the server root"),
+            field("content", Kind::String, true, "The new content"),
+            field("expectedVersion", Kind::String, false, "Refuses the write if the file changed since this version"),
+        ],
+    ),
+    route(
+        Verb::Post,
+        "/files/search",
+        "files:search",
+        "Search file names and contents",
+        &[body("root, name_glob, content, case_sensitive, max_results, max_depth and max_file_size")],
+    ),
+    route(
+        Verb::Post,
+        "/files/upload",
+        "files:upload",
+        "Upload small files inline",
+        &[
+            field("targetDir", Kind::String, true, "Directory to upload into"),
+            field("files", Kind::Array, true, "Objects with a name and base64 content"),
+        ],
+    ),
+    route(
+        Verb::Post,
+        "/files/directory",
+        "files:mkdir",
+        "Create a directory",
+        &[field("path", Kind::String, true, "Path relative to the server root")],
+    ),
+    route(
+        Verb::Post,
+        "/files/rename",
+        "files:rename",
+        "Rename a file or directory",
+        &[
+            field("path", Kind::String, true, "Path relative to the server root"),
+            field("newName", Kind::String, true, "New name, without a directory"),
+        ],
+    ),
+    route(
+        Verb::Post,
+        "/files/move",
+        "files:move",
+        "Move files into a directory",
+        &[
+            field("paths", Kind::Array, true, "Paths to move"),
+            field("destination", Kind::String, true, "Directory to move them into"),
+            field("overwrite", Kind::Boolean, false, "Replace existing files"),
+        ],
+    ),
+    route(
+        Verb::Post,
+        "/files/copy",
+        "files:copy",
+        "Copy files into a directory",
+        &[
+            field("paths", Kind::Array, true, "Paths to copy"),
+            field("destination", Kind::String, true, "Directory to copy them into"),
+            field("overwrite", Kind::Boolean, false, "Replace existing files"),
+        ],
+    ),
+    route(
+        Verb::Post,
+        "/files/extract",
+        "files:extract",
+        "Extract an archive",
+        &[
+            field("path", Kind::String, true, "The archive"),
+            field("destination", Kind::String, false, "Directory to extract into; next to the archive by default"),
+            field("onConflict", Kind::String, false, "overwrite (default), skip or fail"),
+        ],
+    ),
+    route(
+        Verb::Post,
+        "/files/compress",
+        "files:compress",
+        "Compress files into an archive",
+        &[
+            field("paths", Kind::Array, true, "Paths to include"),
+            field("output", Kind::String, true, "Path of the archive to create"),
+            field("format", Kind::String, false, "zip or tar.gz"),
+        ],
+    ),
+];
+
+fn error_response(status: StatusCode, message: &str, data: Option<Value>) -> Response {
+    let mut body = json!({ "error": message });
+    if let Some(data) = data {
+        body["data"] = data;
+    }
+    (status, Json(body)).into_response()
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+}
+
+// Query strings are all text; flags are converted for the command
+fn query_value(param: &Param, raw: &str) -> Result<Value, String> {
+    match param.kind {
+        Kind::Boolean => raw
+            .parse::<bool>()
+            .map(Value::from)
+            .map_err(|_| format!("Invalid query parameter: {}", param.name)),
+        _ => Ok(Value::String(raw.to_string())),
+    }
+}
+
+fn command_args(
+    route: &ApiRoute,
+    path: &HashMap<String, String>,
+    query: &HashMap<String, String>,
+    body: Option<&Value>,
+) -> Result<Vec<Value>, String> {
+    route
+        .params
+        .iter()
+        .map(|param| {
+            let value = match param.source {
+                Source::Path => path.get(param.name).map(|v| Value::String(v.clone())),
+                Source::Query => query.get(param.name).map(|v| query_value(param, v)).transpose()?,
+                Source::Field => body.and_then(|b| b.get(param.name)).cloned(),
+                Source::Body => body.cloned(),
+            };
+            match value.filter(|v| !v.is_null()) {
+                Some(value) => Ok(value),
+                None if param.required => Err(format!("Missing parameter: {}", param.name)),
+                None => Ok(Value::Null),
+            }
+        })
+        .collect()
+}
+
+async fn handle(
+    route: &'static ApiRoute,
+    ctx: Arc<RemoteContext>,
+    addr: SocketAddr,
+    headers: HeaderMap,
+    path: HashMap<String, String>,
+    query: HashMap<String, String>,
+    body: Bytes,
+) -> Response {
+    let ip = addr.ip().to_canonical();
+    match limits::active_ban(&ctx.db_path, ip) {
+        Ok(Some(ban)) => return error_response(StatusCode::FORBIDDEN, &format!("Banned until {}", ban.expires_at), None),
+        Ok(None) => {}
+        Err(e) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, &e, None),
+    }
+
+    let Some(token) = bearer_token(&headers) else {
+        let mut response = error_response(StatusCode::UNAUTHORIZED, "Missing API token", None);
+        response.headers_mut().insert(header::WWW_AUTHENTICATE, header::HeaderValue::from_static("Bearer"));
+        return response;
+    };
+    let ip_text = ip.to_string();
+    let identity = match api_tokens::authenticate(&ctx.db_path, token, Some(&ip_text)) {
+        Ok(identity) => identity,
+        Err(error) => {
+            eprintln!("[REMOTE] API request from {} refused: {}", ip, error);
+            if ctx.guard.record_ip_failure(ip) {
+                server::ban_ip(&ctx, ip, "Too many invalid API tokens");
+            }
+            return error_response(StatusCode::UNAUTHORIZED, &error, None);
+        }
+    };
+
+    let body: Option<Value> = if body.is_empty() {
+        None
+    } else {
+        match serde_json::from_slice(&body) {
+            Ok(body) => Some(body),
+            Err(e) => return error_response(StatusCode::BAD_REQUEST, &format!("Invalid JSON body: {}", e), None),
+        }
+    };
+    let args = match command_args(route, &path, &query, body.as_ref()) {
+        Ok(args) => args,
+        Err(error) => return error_response(StatusCode::BAD_REQUEST, &error, None),
+    };
+
+    let actor = Actor {
+        channel: Channel::Remote,
+        username: identity.username.clone(),
+        user_id: Some(identity.user_id.clone()),
+        ip: Some(ip_text.clone()),
+    };
+    if let Err(error) = permissions::authorize(&identity, route.command) {
+        eprintln!("[REMOTE] Denied API {} for {}", route.command, identity.username);
+        audit_log::record_denied(&ctx.db_path, &actor, route.command, &error);
+        return error_response(StatusCode::FORBIDDEN, &error, None);
+    }
+
+    match audit_log::as_remote(actor, dispatch::execute(&ctx.app_handle, route.command, &args)).await {
+        Ok(data) => Json(data).into_response(),
+        Err(error) => {
+            eprintln!("[REMOTE] API {} failed for {}: {}", route.command, identity.username, error.message);
+            // Only a rejected write carries data: the current version of the file
+            let status = if error.data.is_some() { StatusCode::CONFLICT } else { StatusCode::BAD_REQUEST };
+            error_response(status, &error.message, error.data)
+        }
+    }
+}
+
+// `server:send-command` -> `serverSendCommand`
+fn operation_id(command: &str) -> String {
+    let mut id = String::new();
+    let mut upper = false;
+    for c in command.chars() {
+        if c == ':' || c == '-' {
+            upper = true;
+        } else if upper {
+            id.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            id.push(c);
+        }
+    }
+    id
+}
+
+fn operation(route: &ApiRoute) -> Value {
+    let parameters: Vec<Value> = route
+        .params
+        .iter()
+        .filter(|p| matches!(p.source, Source::Path | Source::Query))
+        .map(|p| {
+            json!({
+                "name": p.name,
+                "in": if p.source == Source::Path { "path" } else { "query" },
+                "required": p.required,
+                "description": p.description,
+                "schema": p.kind.schema(),
+            })
+        })
+        .collect();
+
+    let fields: Vec<&Param> = route.params.iter().filter(|p| p.source == Source::Field).collect();
+    let body_schema = match route.params.iter().find(|p| p.source == Source::Body) {
+        Some(body) => Some(json!({ "type": "object", "description": body.description })),
+        None if !fields.is_empty() => {
+            let properties: Map<String, Value> = fields
+                .iter()
+                .map(|p| {
+                    let mut schema = p.kind.schema();
+                    schema["description"] = Value::from(p.description);
+                    (p.name.to_string(), schema)
+                })
+                .collect();
+            let required: Vec<&str> = fields.iter().filter(|p| p.required).map(|p| p.name).collect();
+            Some(json!({ "type": "object", "properties": properties, "required": required }))
+        }
+        None => None,
+    };
+
+    let mut operation = json!({
+        "operationId": operation_id(route.command),
+        "summary": route.summary,
+        "tags": [route.command.split(':').next().unwrap_or_default()],
+        "x-command": route.command,
+        "x-permission": permissions::required_permission(route.command),
+        "responses": {
+            "200": { "description": "The command's result", "content": { "application/json": { "schema": {} } } },
+            "400": { "$ref": "#/components/responses/Failed" },
+            "401": { "$ref": "#/components/responses/Unauthorized" },
+            "403": { "$ref": "#/components/responses/Forbidden" },
+        },
+    });
+    if !parameters.is_empty() {
+        operation["parameters"] = Value::from(parameters);
+    }
+    if let Some(schema) = body_schema {
+        operation["requestBody"] = json!({ "required": true, "content": { "application/json": { "schema": schema } } });
+    }
+    if route.command == "files:write" {
+        operation["responses"]["409"] = json!({ "$ref": "#/components/responses/Failed" });
+    }
+    operation
+}
+
+/// The OpenAPI 3.0 document describing `ROUTES`.
+fn openapi() -> Value {
+    let mut paths = Map::new();
+    for route in ROUTES {
+        let item = paths.entry(route.path).or_insert_with(|| json!({}));
+        item[route.verb.as_str()] = operation(route);
+    }
+    let error = |description: &str| {
+        json!({
+            "description": description,
+            "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } },
+        })
+    };
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Hytale Server Portal API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Each operation runs the remote command named in x-command and needs the permission in x-permission, granted both to the user and to the token's scopes.",
+        },
+        "servers": [{ "url": "/api/v1" }],
+        "security": [{ "apiToken": [] }],
+        "paths": paths,
+        "components": {
+            "securitySchemes": {
+                "apiToken": { "type": "http", "scheme": "bearer", "description": "A personal API token (hsp_...)" },
+            },
+            "schemas": {
+                "Error": {
+                    "type": "object",
+                    "properties": { "error": { "type": "string" }, "data": {} },
+                    "required": ["error"],
+                },
+            },
+            "responses": {
+                "Failed": error("The command failed or its arguments were invalid"),
+                "Unauthorized": error("Missing, invalid, expired or revoked API token"),
+                "Forbidden": error("Not permitted for this token, or the address is banned"),
+            },
+        },
+    })
+}
+
+/// The `/api/v1` routes, to be nested into the remote server's router.
+pub(super) fn router(ctx: Arc<RemoteContext>) -> Router {
+    let document = openapi();
+    let mut router = Router::new().route(
+        "/openapi.json",
+        get(move || {
+            let document = document.clone();
+            async move { Json(document) }
+        }),
+    );
+
+    for route in ROUTES {
+        // axum writes path parameters as `:name`
+        let axum_path = route.path.replace('{', ":").replace('}', "");
+        router = router.route(
+            &axum_path,
+            on(
+                route.verb.filter(),
+                move |State(ctx): State<Arc<RemoteContext>>,
+                      ConnectInfo(addr): ConnectInfo<SocketAddr>,
+                      headers: HeaderMap,
+                      path: Option<Path<HashMap<String, String>>>,
+                      Query(query): Query<HashMap<String, String>>,
+                      body: Bytes| {
+                    handle(route, ctx, addr, headers, path.map(|Path(p)| p).unwrap_or_default(), query, body)
+                },
+            ),
+        );
+    }
+
+    router
+        .fallback(|| async { error_response(StatusCode::NOT_FOUND, "No such endpoint", None) })
+        .with_state(ctx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(kind: Kind) -> Value {
+        match kind {
+            Kind::String => json!("value"),
+            Kind::Boolean => json!(true),
+            Kind::Array => json!(["value"]),
+            Kind::Object => json!({ "key": "value" }),
+        }
+    }
+
+    // Every parameter of the route present, as a client following the document would send it
+    fn full_request(route: &ApiRoute) -> (HashMap<String, String>, HashMap<String, String>, Value) {
+        let mut path = HashMap::new();
+        let mut query = HashMap::new();
+        let mut body = Map::new();
+        for param in route.params {
+            match param.source {
+                Source::Path => {
+                    path.insert(param.name.to_string(), "value".to_string());
+                }
+                Source::Query => {
+                    let raw = if matches!(param.kind, Kind::Boolean) { "true" } else { "value" };
+                    query.insert(param.name.to_string(), raw.to_string());
+                }
+                Source::Field => {
+                    body.insert(param.name.to_string(), sample(param.kind));
+                }
+                Source::Body => {}
+            }
+        }
+        (path, query, Value::Object(body))
+    }
+
+    #[test]
+    fn every_route_needs_a_known_permission() {
+        for route in ROUTES {
+            assert!(
+                permissions::required_permission(route.command).is_some(),
+                "{} has no permission and would always be refused",
+                route.command
+            );
+        }
+        for item in openapi()["paths"].as_object().unwrap().values() {
+            for operation in item.as_object().unwrap().values() {
+                assert!(operation["x-permission"].is_string(), "{}", operation["x-command"]);
+            }
+        }
+    }
+
+    #[test]
+    fn every_route_is_dispatched() {
+        let dispatch = include_str!("dispatch.rs");
+        for route in ROUTES {
+            assert!(dispatch.contains(&format!("\"{}\" =>", route.command)), "{} is not dispatched", route.command);
+        }
+    }
+
+    #[test]
+    fn routes_are_unique() {
+        for (i, a) in ROUTES.iter().enumerate() {
+            for b in &ROUTES[i + 1..] {
+                assert!(
+                    a.path != b.path || a.verb.as_str() != b.verb.as_str(),
+                    "{} {} is declared twice",
+                    a.verb.as_str(),
+                    a.path
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn path_placeholders_match_path_params() {
+        for route in ROUTES {
+            let placeholders: Vec<&str> = route
+                .path
+                .split('/')
+                .filter_map(|s| s.strip_prefix('{').and_then(|s| s.strip_suffix('}')))
+                .collect();
+            let params: Vec<&str> = route.params.iter().filter(|p| p.source == Source::Path).map(|p| p.name).collect();
+            assert_eq!(placeholders, params, "{}", route.path);
+            assert!(route.params.iter().filter(|p| p.source == Source::Body).count() <= 1, "{}", route.path);
+        }
+    }
+
+    #[test]
+    fn documented_requests_produce_positional_args() {
+        for route in ROUTES {
+            let (path, query, mut body) = full_request(route);
+            if route.params.iter().any(|p| p.source == Source::Body) {
+                body = sample(Kind::Object);
+            }
+            let args = command_args(route, &path, &query, Some(&body)).unwrap_or_else(|e| panic!("{}: {}", route.path, e));
+            assert_eq!(args.len(), route.params.len(), "{}", route.path);
+            for (param, arg) in route.params.iter().zip(&args) {
+                match (param.source, param.kind) {
+                    (Source::Body, _) => assert_eq!(*arg, body, "{}", route.path),
+                    (Source::Path, _) => assert_eq!(*arg, json!("value"), "{}", route.path),
+                    (_, kind) => assert_eq!(*arg, sample(kind), "{} {}", route.path, param.name),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn missing_required_params_are_refused() {
+        for route in ROUTES {
+            for (i, param) in route.params.iter().enumerate() {
+                let (mut path, mut query, mut body) = full_request(route);
+                let has_body = route.params.iter().any(|p| p.source == Source::Body);
+                match param.source {
+                    Source::Path => drop(path.remove(param.name)),
+                    Source::Query => drop(query.remove(param.name)),
+                    Source::Field => drop(body.as_object_mut().unwrap().remove(param.name)),
+                    Source::Body => {}
+                }
+                let body = match param.source {
+                    Source::Body => None,
+                    _ if has_body => Some(sample(Kind::Object)),
+                    _ => Some(body),
+                };
+
+                match command_args(route, &path, &query, body.as_ref()) {
+                    Ok(args) => {
+                        assert!(!param.required, "{} accepted a request without {}", route.path, param.name);
+                        assert_eq!(args[i], Value::Null, "{} {}", route.path, param.name);
+                    }
+                    Err(e) => {
+                        assert!(param.required, "{} refused a request without optional {}", route.path, param.name);
+                        assert_eq!(e, format!("Missing parameter: {}", param.name));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn boolean_query_params_are_parsed() {
+        let flag = query("recursive", Kind::Boolean, false, "");
+        assert_eq!(query_value(&flag, "true").unwrap(), json!(true));
+        assert_eq!(query_value(&flag, "false").unwrap(), json!(false));
+        for raw in ["yes", "1", ""] {
+            assert_eq!(query_value(&flag, raw).unwrap_err(), "Invalid query parameter: recursive");
+        }
+        let text = query("q", Kind::String, true, "");
+        assert_eq!(query_value(&text, "true").unwrap(), json!("true"));
+    }
+
+    #[test]
+    fn null_fields_count_as_missing() {
+        let route = ROUTES.iter().find(|r| r.params.iter().any(|p| p.source == Source::Field && p.required)).unwrap();
+        let required = route.params.iter().find(|p| p.source == Source::Field && p.required).unwrap();
+        let (path, query, mut body) = full_request(route);
+        body[required.name] = Value::Null;
+        assert_eq!(
+            command_args(route, &path, &query, Some(&body)).unwrap_err(),
+            format!("Missing parameter: {}", required.name)
+        );
+    }
+}
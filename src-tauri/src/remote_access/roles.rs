@@ -0,0 +1,166 @@
+// Named sets of permissions in `remote_roles`. A user holds any number of roles
+// plus their own grants (`remote_users.permissions`) and denies
+// (`remote_users.denied_permissions`); what they may do is the union of roles
+// and grants, minus the denies. The built-in roles are the presets of
+// PermissionsManager.ts and can be edited but not deleted.
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use super::permissions::{self, PERMISSIONS};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Role {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub permissions: Vec<String>,
+    pub builtin: bool,
+}
+
+const BUILTIN_ROLES: &[(&str, &str, &str, &[&str])] = &[
+    ("admin", "Admin", "Full access", &["*"]),
+    (
+        "moderator",
+        "Moderator",
+        "Runs the server and makes backups, cannot change configuration or files",
+        &[
+            "server.start",
+            "server.stop",
+            "server.restart",
+            "server.status",
+            "server.logs",
+            "server.command",
+            "config.read",
+            "backup.create",
+            "backup.list",
+            "files.list",
+            "files.read",
+        ],
+    ),
+    (
+        "viewer",
+        "Viewer",
+        "Watches the server and sends console commands",
+        &["server.status", "server.logs", "server.command", "config.read", "backup.list", "files.list"],
+    ),
+];
+
+/// Adds the built-in roles if they are missing; edits made to them are kept.
+pub fn seed_builtin_roles(conn: &Connection) -> Result<(), String> {
+    for (id, name, description, grants) in BUILTIN_ROLES {
+        let grants = serde_json::to_string(grants).map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT OR IGNORE INTO remote_roles (id, name, description, permissions, builtin, created_at)
+             VALUES (?1, ?2, ?3, ?4, 1, ?5)",
+            rusqlite::params![id, name, description, grants, chrono::Utc::now().to_rfc3339()],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn parse_list(json: &str) -> Vec<String> {
+    serde_json::from_str(json).unwrap_or_default()
+}
+
+pub fn list_roles(conn: &Connection) -> Result<Vec<Role>, String> {
+    let mut stmt = conn
+        .prepare("SELECT id, name, description, permissions, builtin FROM remote_roles ORDER BY builtin DESC, name")
+        .map_err(|e| e.to_string())?;
+    let roles = stmt
+        .query_map([], |row| {
+            Ok(Role {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                description: row.get(2)?,
+                permissions: parse_list(&row.get::<_, String>(3)?),
+                builtin: row.get(4)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(roles)
+}
+
+pub fn user_roles(conn: &Connection, user_id: &str) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare("SELECT role_id FROM remote_user_roles WHERE user_id = ?1 ORDER BY role_id")
+        .map_err(|e| e.to_string())?;
+    let roles = stmt
+        .query_map([user_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(roles)
+}
+
+pub fn users_with_role(conn: &Connection, role_id: &str) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare("SELECT user_id FROM remote_user_roles WHERE role_id = ?1")
+        .map_err(|e| e.to_string())?;
+    let users = stmt
+        .query_map([role_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(users)
+}
+
+/// Replaces a user's roles; every role must exist.
+pub fn set_user_roles(conn: &Connection, user_id: &str, role_ids: &[String]) -> Result<(), String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM remote_user_roles WHERE user_id = ?1", [user_id])
+        .map_err(|e| e.to_string())?;
+    for role_id in role_ids {
+        let exists: i64 = tx
+            .query_row("SELECT COUNT(*) FROM remote_roles WHERE id = ?1", [role_id], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        if exists == 0 {
+            return Err(format!("Role not found: {}", role_id));
+        }
+        tx.execute(
+            "INSERT OR IGNORE INTO remote_user_roles (user_id, role_id) VALUES (?1, ?2)",
+            [user_id, role_id.as_str()],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// Expands grants and denies into the concrete permission ids they leave allowed.
+pub fn resolve(grants: &[String], denies: &[String]) -> Vec<String> {
+    PERMISSIONS
+        .iter()
+        .filter(|p| permissions::has_permission(grants, p.id) && !permissions::has_permission(denies, p.id))
+        .map(|p| p.id.to_string())
+        .collect()
+}
+
+/// What a user may do, from their roles, grants and denies.
+pub fn effective_permissions(conn: &Connection, user_id: &str) -> Result<Vec<String>, String> {
+    let (grants, denies): (String, String) = conn
+        .query_row(
+            "SELECT permissions, denied_permissions FROM remote_users WHERE id = ?1",
+            [user_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let mut grants = parse_list(&grants);
+    let mut stmt = conn
+        .prepare(
+            "SELECT r.permissions FROM remote_roles r
+             JOIN remote_user_roles ur ON ur.role_id = r.id
+             WHERE ur.user_id = ?1",
+        )
+        .map_err(|e| e.to_string())?;
+    for role_grants in stmt
+        .query_map([user_id], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?
+    {
+        grants.extend(parse_list(&role_grants.map_err(|e| e.to_string())?));
+    }
+
+    Ok(resolve(&grants, &parse_list(&denies)))
+}
@@ -0,0 +1,790 @@
+// The HTTP listener and socket.io namespace. A client connects, logs in with
+// `auth:login` (or reconnects with its access token in the handshake `auth`
+// payload) and then sends `command` events, each answered through its ack:
+//
+//   -> command { command: "server:status", args: [], requestId: "1" }
+//   <- ack     { requestId: "1", success: true, data: { ... } }
+//
+// Access tokens are short-lived; before one expires the client trades its
+// refresh token for a new pair with `auth:refresh`, on the same socket.
+// Live console output and events are subscribed to separately; see `stream`.
+//
+// The REST API for scripts is served from the same listener under `/api/v1`;
+// see `rest`.
+//
+// With TLS on, the same listener speaks HTTPS and an optional second one
+// redirects plain HTTP to it.
+use axum::extract::{ConnectInfo, DefaultBodyLimit, Request, State as HttpState};
+use axum::http::{header, HeaderMap, StatusCode, Uri};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::routing::get;
+use axum::Router;
+use axum_server::accept::{Accept, DefaultAcceptor};
+use axum_server::tls_rustls::{RustlsAcceptor, RustlsConfig};
+use axum_server::Handle;
+use futures_util::future::Either;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use socketioxide::extract::{AckSender, Data, SocketRef, State, TryData};
+use socketioxide::handler::ConnectHandler;
+use socketioxide::socket::{DisconnectReason, Sid};
+use socketioxide::SocketIo;
+use std::collections::{HashMap, HashSet};
+use std::future::Ready;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+use tower_http::cors::CorsLayer;
+
+use crate::services::audit_log::{self, Actor, Channel};
+use super::audit;
+use super::dispatch;
+use super::ip_filter::IpFilter;
+use super::limits::{self, IpBan, LoginGuard, RemoteLimits};
+use super::permissions;
+use super::rest;
+use super::sessions::{self, Identity, Sessions, Tokens};
+use super::stream::{self, StreamHub};
+use super::tls::{self, TlsSettings};
+
+/// Where the server listens, from `remote_config`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteSettings {
+    pub bind_address: String,
+    pub port: u16,
+    /// Fixed for the life of the listener, unlike the other limits
+    pub max_request_bytes: u64,
+    /// Certificate to serve HTTPS with; plain HTTP when None
+    pub tls: Option<TlsSettings>,
+}
+
+/// The parts of `remote_config` a running server picks up without restarting.
+pub struct RemotePolicy {
+    pub limits: RemoteLimits,
+    pub ip_filter: IpFilter,
+}
+
+// A refused address is written to the audit log at most this often
+const REFUSAL_LOG_INTERVAL: Duration = Duration::from_secs(60);
+// How often the certificate files are checked for a renewal
+const CERTIFICATE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+// Open connections get this long to finish when the server stops
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+/// Shared by every socket of one running server.
+pub struct RemoteContext {
+    pub db_path: String,
+    pub app_handle: AppHandle,
+    pub sessions: Sessions,
+    pub(super) guard: LoginGuard,
+    ip_filter: RwLock<IpFilter>,
+    // Sockets that have logged in, and as whom
+    clients: Mutex<HashMap<Sid, Client>>,
+    // Open TCP connections, counted by `GuardedAcceptor`
+    connections: AtomicUsize,
+    // When each refused address was last written to the audit log
+    refusals: Mutex<HashMap<IpAddr, Instant>>,
+    pub(super) streams: StreamHub,
+}
+
+#[derive(Clone)]
+pub(super) struct Client {
+    pub(super) identity: Identity,
+    session_id: String,
+    /// When the access token the socket authenticated with runs out
+    expires_at: i64,
+}
+
+impl RemoteContext {
+    fn client(&self, socket: &SocketRef) -> Option<Client> {
+        self.clients.lock().unwrap().get(&socket.id).cloned()
+    }
+
+    /// The socket's client, if it is logged in with a token that has not run out.
+    pub(super) fn live_client(&self, socket: &SocketRef) -> Result<Client, &'static str> {
+        match self.client(socket) {
+            None => Err("Not authenticated"),
+            Some(c) if c.expires_at <= chrono::Utc::now().timestamp() => Err("Token expired"),
+            Some(c) => Ok(c),
+        }
+    }
+
+    /// Checks an address against the IP rules, auditing refusals.
+    fn check_ip(&self, ip: IpAddr) -> Result<(), &'static str> {
+        let result = self.ip_filter.read().unwrap().check(ip);
+        if let Err(reason) = result {
+            self.audit_refusal(ip, reason);
+        }
+        result
+    }
+
+    // Polling clients retry constantly, so repeats of a refusal are only logged once a minute
+    fn audit_refusal(&self, ip: IpAddr, reason: &str) {
+        {
+            let mut refusals = self.refusals.lock().unwrap();
+            let now = Instant::now();
+            if refusals.get(&ip).is_some_and(|t| now.duration_since(*t) < REFUSAL_LOG_INTERVAL) {
+                return;
+            }
+            refusals.retain(|_, t| now.duration_since(*t) < REFUSAL_LOG_INTERVAL);
+            refusals.insert(ip, now);
+        }
+        eprintln!("[REMOTE] Refused connection from {}: {}", ip, reason);
+        audit::record(&self.db_path, "connection_denied", None, Some(&ip.to_string()), reason);
+    }
+
+    fn sign_in(&self, socket: &SocketRef, identity: Identity, session_id: String, expires_at: i64) {
+        self.clients
+            .lock()
+            .unwrap()
+            .insert(socket.id, Client { identity, session_id, expires_at });
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct HandshakeAuth {
+    token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LoginRequest {
+    username: String,
+    password: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RefreshRequest {
+    refresh_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CommandRequest {
+    command: String,
+    #[serde(default)]
+    args: Vec<Value>,
+    request_id: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CommandResponse {
+    request_id: String,
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+fn peer_address(socket: &SocketRef) -> Option<SocketAddr> {
+    socket
+        .req_parts()
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0)
+}
+
+// IPv4 clients of a dual-stack listener show up as IPv4-mapped IPv6 addresses
+pub(super) fn peer_ip(socket: &SocketRef) -> Option<IpAddr> {
+    peer_address(socket).map(|a| a.ip().to_canonical())
+}
+
+// Connections without a token are let through so they can log in; a token
+// that does not check out is refused before the socket joins the namespace.
+// Banned IPs are refused first.
+fn authenticate(
+    socket: SocketRef,
+    TryData(auth): TryData<HandshakeAuth>,
+    State(ctx): State<Arc<RemoteContext>>,
+) -> Result<(), String> {
+    let ip = peer_ip(&socket);
+    if let Some(ip) = ip {
+        if let Some(ban) = limits::active_ban(&ctx.db_path, ip)? {
+            ctx.audit_refusal(ip, "Banned");
+            return Err(format!("Banned until {}", ban.expires_at));
+        }
+    }
+
+    let Some(token) = auth.ok().and_then(|a| a.token).filter(|t| !t.is_empty()) else {
+        return Ok(());
+    };
+    let (identity, claims) = ctx.sessions.validate(&token)?;
+    eprintln!("[REMOTE] {} authenticated by token", identity.username);
+    ctx.sign_in(&socket, identity, claims.sid, claims.exp);
+    Ok(())
+}
+
+fn token_response(tokens: &Tokens, identity: &Identity) -> Value {
+    json!({
+        "success": true,
+        "token": tokens.access_token,
+        "refreshToken": tokens.refresh_token,
+        "expiresAt": tokens.expires_at,
+        "userData": identity,
+    })
+}
+
+fn on_connect(socket: SocketRef, State(ctx): State<Arc<RemoteContext>>) {
+    let identity = ctx.client(&socket).map(|c| c.identity);
+    eprintln!(
+        "[REMOTE] Client connected: {} from {}",
+        identity.as_ref().map_or("unauthenticated", |i| i.username.as_str()),
+        peer_address(&socket).map_or("unknown".to_string(), |a| a.to_string()),
+    );
+
+    let _ = socket.emit(
+        "welcome",
+        json!({
+            "message": "Connected to Hytale Server Portal",
+            "username": identity.as_ref().map(|i| &i.username),
+            "permissions": identity.as_ref().map(|i| &i.permissions),
+        }),
+    );
+
+    socket.on("auth:login", on_login);
+    socket.on("auth:refresh", on_refresh);
+    socket.on("auth:logout", on_logout);
+    socket.on("command", on_command);
+    socket.on("stream:subscribe", stream::on_subscribe);
+    socket.on("stream:unsubscribe", stream::on_unsubscribe);
+    socket.on_disconnect(|socket: SocketRef, reason: DisconnectReason, State(ctx): State<Arc<RemoteContext>>| {
+        ctx.streams.forget(socket.id);
+        if let Some(client) = ctx.clients.lock().unwrap().remove(&socket.id) {
+            eprintln!("[REMOTE] Client disconnected: {} ({:?})", client.identity.username, reason);
+        }
+    });
+}
+
+async fn on_login(
+    socket: SocketRef,
+    Data(request): Data<LoginRequest>,
+    ack: AckSender,
+    State(ctx): State<Arc<RemoteContext>>,
+) {
+    let username = request.username.clone();
+    let peer = peer_ip(&socket);
+    // Refused before bcrypt runs, so a flood costs no hashing
+    if let Err(error) = ctx.guard.check_login(peer, &username) {
+        eprintln!("[REMOTE] Login refused for {}: {}", username, error);
+        let _ = ack.send(json!({ "success": false, "error": error }));
+        return;
+    }
+
+    let ip = peer.map(|a| a.to_string());
+    let blocking_ctx = ctx.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let identity = sessions::authenticate(&blocking_ctx.db_path, &request.username, &request.password, ip.as_deref())?;
+        let (session_id, tokens) = blocking_ctx.sessions.create(&identity, ip.as_deref())?;
+        Ok::<_, String>((identity, session_id, tokens))
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|r| r);
+
+    let mut banned = false;
+    let response = match result {
+        Ok((identity, session_id, tokens)) => {
+            eprintln!("[REMOTE] Login successful: {}", identity.username);
+            ctx.guard.record_success(&username);
+            ctx.sign_in(&socket, identity.clone(), session_id, tokens.expires_at);
+            token_response(&tokens, &identity)
+        }
+        Err(error) => {
+            eprintln!("[REMOTE] Login failed for {}: {}", username, error);
+            banned = ctx.guard.record_failure(peer, &username);
+            json!({ "success": false, "error": error })
+        }
+    };
+    let _ = ack.send(response);
+    if banned {
+        ban_socket(&ctx, &socket, peer);
+    }
+}
+
+// Counts as an open connection until dropped
+struct ConnectionSlot(Arc<RemoteContext>);
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl RemoteContext {
+    /// Takes a connection slot for `ip`, or refuses it if the IP rules do
+    /// not allow it or `max_connections` are already open.
+    fn admit(self: &Arc<Self>, ip: IpAddr) -> Result<ConnectionSlot, &'static str> {
+        self.check_ip(ip)?;
+        let max = self.guard.limits().max_connections;
+        let taken = self
+            .connections
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |open| (open < max).then_some(open + 1));
+        if taken.is_err() {
+            self.audit_refusal(ip, "Connection limit reached");
+            return Err("Connection limit reached");
+        }
+        Ok(ConnectionSlot(self.clone()))
+    }
+}
+
+/// A TCP stream that holds its connection slot for as long as it is open.
+struct AdmittedStream {
+    stream: TcpStream,
+    _slot: ConnectionSlot,
+}
+
+impl AsyncRead for AdmittedStream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for AdmittedStream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+/// Admits or refuses each TCP connection as it is accepted, before any TLS
+/// handshake or HTTP request, then hands it to `inner` (plain or TLS).
+#[derive(Clone)]
+struct GuardedAcceptor<A> {
+    inner: A,
+    ctx: Arc<RemoteContext>,
+}
+
+impl<A: Accept<AdmittedStream, S>, S> Accept<TcpStream, S> for GuardedAcceptor<A> {
+    type Stream = A::Stream;
+    type Service = A::Service;
+    type Future = Either<A::Future, Ready<io::Result<(A::Stream, A::Service)>>>;
+
+    fn accept(&self, stream: TcpStream, service: S) -> Self::Future {
+        let admitted = stream
+            .peer_addr()
+            .and_then(|addr| self.ctx.admit(addr.ip().to_canonical()).map_err(io::Error::other));
+        match admitted {
+            Ok(slot) => Either::Left(self.inner.accept(AdmittedStream { stream, _slot: slot }, service)),
+            // Dropping the stream closes the connection
+            Err(e) => Either::Right(std::future::ready(Err(e))),
+        }
+    }
+}
+
+// Connections are filtered when accepted; this catches keep-alive connections
+// opened before the rules changed, on their next request
+async fn filter_ip(
+    HttpState(ctx): HttpState<Arc<RemoteContext>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Err(reason) = ctx.check_ip(addr.ip().to_canonical()) {
+        return (StatusCode::FORBIDDEN, reason).into_response();
+    }
+    next.run(request).await
+}
+
+// Sends plain HTTP requests to the same path on the HTTPS port
+async fn redirect_to_https(HttpState(port): HttpState<u16>, headers: HeaderMap, uri: Uri) -> Response {
+    let Some(host) = headers.get(header::HOST).and_then(|h| h.to_str().ok()) else {
+        return (StatusCode::BAD_REQUEST, "Missing Host header").into_response();
+    };
+    let host = host
+        .rsplit_once(':')
+        .filter(|(_, port)| port.parse::<u16>().is_ok())
+        .map_or(host, |(host, _)| host);
+    let path = uri.path_and_query().map_or("/", |p| p.as_str());
+    Redirect::permanent(&format!("https://{}:{}{}", host, port, path)).into_response()
+}
+
+// Reloads the certificate when either file changes, e.g. after a renewal.
+// Connections keep the certificate they were opened with.
+async fn watch_certificate(tls: TlsSettings, config: RustlsConfig) {
+    let mut stamp = tls::file_stamp(&tls);
+    let mut interval = tokio::time::interval(CERTIFICATE_CHECK_INTERVAL);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let current = tls::file_stamp(&tls);
+        if current.is_none() || current == stamp {
+            continue;
+        }
+        stamp = current;
+        match config.reload_from_pem_file(&tls.cert_path, &tls.key_path).await {
+            Ok(()) => eprintln!("[REMOTE] Reloaded TLS certificate from {}", tls.cert_path.display()),
+            Err(e) => eprintln!("[REMOTE] Failed to reload TLS certificate, keeping the current one: {}", e),
+        }
+    }
+}
+
+async fn listen(address: &str, port: u16) -> Result<std::net::TcpListener, String> {
+    tokio::net::TcpListener::bind((address, port))
+        .await
+        .and_then(|listener| listener.into_std())
+        .map_err(|e| format!("Failed to listen on {}:{}: {}", address, port, e))
+}
+
+fn log_exit(result: std::io::Result<()>) {
+    if let Err(e) = result {
+        eprintln!("[REMOTE] Server error: {}", e);
+    }
+}
+
+/// Bans an IP that failed too often, for the configured duration.
+pub(super) fn ban_ip(ctx: &RemoteContext, ip: IpAddr, reason: &str) -> Option<IpBan> {
+    let duration = Duration::from_secs(ctx.guard.limits().ban_duration_secs);
+    match limits::ban(&ctx.db_path, ip, reason, duration) {
+        Ok(ban) => {
+            audit::record(
+                &ctx.db_path,
+                "ip_banned",
+                None,
+                Some(&ban.ip),
+                &format!("{}, until {}", reason, ban.expires_at),
+            );
+            Some(ban)
+        }
+        Err(e) => {
+            eprintln!("[REMOTE] Failed to ban {}: {}", ip, e);
+            None
+        }
+    }
+}
+
+fn ban_socket(ctx: &RemoteContext, socket: &SocketRef, ip: Option<IpAddr>) {
+    let Some(ban) = ip.and_then(|ip| ban_ip(ctx, ip, "Too many failed logins")) else { return };
+    let _ = socket.emit("banned", json!({ "message": "Too many failed logins", "expiresAt": ban.expires_at }));
+    let _ = socket.clone().disconnect();
+}
+
+async fn on_refresh(
+    socket: SocketRef,
+    Data(request): Data<RefreshRequest>,
+    ack: AckSender,
+    State(ctx): State<Arc<RemoteContext>>,
+) {
+    let ip = peer_address(&socket).map(|a| a.ip().to_string());
+    let blocking_ctx = ctx.clone();
+    let result = tokio::task::spawn_blocking(move || blocking_ctx.sessions.refresh(&request.refresh_token, ip.as_deref()))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|r| r);
+
+    let response = match result {
+        Ok((identity, session_id, tokens)) => {
+            ctx.sign_in(&socket, identity.clone(), session_id, tokens.expires_at);
+            token_response(&tokens, &identity)
+        }
+        Err(error) => {
+            eprintln!("[REMOTE] Token refresh failed: {}", error);
+            json!({ "success": false, "error": error })
+        }
+    };
+    let _ = ack.send(response);
+}
+
+async fn on_logout(socket: SocketRef, ack: AckSender, State(ctx): State<Arc<RemoteContext>>) {
+    let client = ctx.clients.lock().unwrap().remove(&socket.id);
+    if let Some(client) = client {
+        if let Err(e) = sessions::revoke_session(&ctx.db_path, &client.session_id, "Logged out") {
+            eprintln!("[REMOTE] Failed to revoke session on logout: {}", e);
+        }
+        eprintln!("[REMOTE] {} logged out", client.identity.username);
+    }
+    let _ = ack.send(json!({ "success": true }));
+}
+
+async fn on_command(
+    socket: SocketRef,
+    Data(request): Data<CommandRequest>,
+    ack: AckSender,
+    State(ctx): State<Arc<RemoteContext>>,
+) {
+    let identity = match ctx.live_client(&socket) {
+        Ok(client) => client.identity,
+        Err(error) => {
+            let _ = ack.send(CommandResponse {
+                request_id: request.request_id,
+                success: false,
+                data: None,
+                error: Some(error.to_string()),
+            });
+            return;
+        }
+    };
+
+    let ip = peer_address(&socket).map(|a| a.ip().to_string());
+    let actor = Actor {
+        channel: Channel::Remote,
+        username: identity.username.clone(),
+        user_id: Some(identity.user_id.clone()),
+        ip: ip.clone(),
+    };
+
+    if let Err(error) = permissions::authorize(&identity, &request.command) {
+        eprintln!("[REMOTE] Denied {} for {}", request.command, identity.username);
+        audit_log::record_denied(&ctx.db_path, &actor, &request.command, &error);
+        let _ = ack.send(CommandResponse {
+            request_id: request.request_id,
+            success: false,
+            data: None,
+            error: Some(error),
+        });
+        return;
+    }
+
+    let executed = audit_log::as_remote(actor, dispatch::execute(&ctx.app_handle, &request.command, &request.args));
+    let response = match executed.await {
+        Ok(data) => CommandResponse { request_id: request.request_id, success: true, data: Some(data), error: None },
+        Err(error) => {
+            eprintln!("[REMOTE] {} failed for {}: {}", request.command, identity.username, error.message);
+            CommandResponse {
+                request_id: request.request_id,
+                success: false,
+                data: error.data,
+                error: Some(error.message),
+            }
+        }
+    };
+    let _ = ack.send(response);
+}
+
+/// A listening remote server. Dropping it without `stop` leaves it running.
+pub struct RemoteServer {
+    settings: RemoteSettings,
+    ctx: Arc<RemoteContext>,
+    io: SocketIo,
+    handle: Handle,
+    // The main listener and the HTTP redirect, if any
+    tasks: Vec<JoinHandle<()>>,
+    rustls: Option<RustlsConfig>,
+    // Stream publishers and the certificate watcher, aborted on stop
+    background: Vec<JoinHandle<()>>,
+}
+
+impl RemoteServer {
+    pub async fn start(
+        settings: RemoteSettings,
+        policy: RemotePolicy,
+        db_path: String,
+        app_handle: AppHandle,
+    ) -> Result<RemoteServer, String> {
+        let ctx = Arc::new(RemoteContext {
+            sessions: Sessions::open(&db_path)?,
+            db_path,
+            app_handle,
+            guard: LoginGuard::new(policy.limits),
+            ip_filter: RwLock::new(policy.ip_filter),
+            clients: Mutex::new(HashMap::new()),
+            connections: AtomicUsize::new(0),
+            refusals: Mutex::new(HashMap::new()),
+            streams: StreamHub::new(),
+        });
+
+        let (layer, io) = SocketIo::builder()
+            .max_payload(settings.max_request_bytes)
+            .with_state(ctx.clone())
+            .build_layer();
+        io.ns("/", on_connect.with(authenticate));
+
+        let app = Router::new()
+            .route("/health", get(|| async { "ok" }))
+            .nest("/api/v1", rest::router(ctx.clone()))
+            .layer(layer)
+            .layer(DefaultBodyLimit::max(settings.max_request_bytes as usize))
+            .layer(CorsLayer::permissive())
+            .layer(middleware::from_fn_with_state(ctx.clone(), filter_ip));
+
+        let rustls = match &settings.tls {
+            Some(tls) => {
+                // Both ring and aws-lc may be linked in, so rustls cannot pick one itself
+                let _ = rustls::crypto::ring::default_provider().install_default();
+                let config = RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path)
+                    .await
+                    .map_err(|e| format!("Failed to load TLS certificate: {}", e))?;
+                Some(config)
+            }
+            None => None,
+        };
+        let listener = listen(&settings.bind_address, settings.port).await?;
+        let redirect_port = settings.tls.as_ref().and_then(|tls| tls.redirect_port);
+        let redirect_listener = match redirect_port {
+            Some(port) => Some(listen(&settings.bind_address, port).await?),
+            None => None,
+        };
+
+        let handle = Handle::new();
+        let make_service = app.into_make_service_with_connect_info::<SocketAddr>();
+        let server_handle = handle.clone();
+        let mut tasks = vec![match rustls.clone() {
+            Some(config) => {
+                let acceptor = GuardedAcceptor { inner: RustlsAcceptor::new(config), ctx: ctx.clone() };
+                tokio::spawn(async move {
+                    log_exit(axum_server::from_tcp(listener).acceptor(acceptor).handle(server_handle).serve(make_service).await)
+                })
+            }
+            None => {
+                let acceptor = GuardedAcceptor { inner: DefaultAcceptor, ctx: ctx.clone() };
+                tokio::spawn(async move {
+                    log_exit(axum_server::from_tcp(listener).acceptor(acceptor).handle(server_handle).serve(make_service).await)
+                })
+            }
+        }];
+
+        if let Some(listener) = redirect_listener {
+            let redirect = Router::new()
+                .fallback(redirect_to_https)
+                .with_state(settings.port)
+                .layer(middleware::from_fn_with_state(ctx.clone(), filter_ip));
+            let make_service = redirect.into_make_service_with_connect_info::<SocketAddr>();
+            let server_handle = handle.clone();
+            let acceptor = GuardedAcceptor { inner: DefaultAcceptor, ctx: ctx.clone() };
+            tasks.push(tokio::spawn(async move {
+                log_exit(axum_server::from_tcp(listener).acceptor(acceptor).handle(server_handle).serve(make_service).await)
+            }));
+        }
+
+        let mut background = stream::spawn_publishers(ctx.clone(), io.clone());
+        if let (Some(tls), Some(config)) = (settings.tls.clone(), rustls.clone()) {
+            background.push(tokio::spawn(watch_certificate(tls, config)));
+        }
+
+        let scheme = if settings.tls.is_some() { "https" } else { "http" };
+        eprintln!("[REMOTE] Listening on {}://{}:{}", scheme, settings.bind_address, settings.port);
+        if let Some(port) = redirect_port {
+            eprintln!("[REMOTE] Redirecting http://{}:{} to HTTPS", settings.bind_address, port);
+        }
+        Ok(RemoteServer { settings, ctx, io, handle, tasks, rustls, background })
+    }
+
+    pub fn settings(&self) -> &RemoteSettings {
+        &self.settings
+    }
+
+    pub fn client_count(&self) -> usize {
+        self.io.sockets().map(|s| s.len()).unwrap_or(0)
+    }
+
+    /// Disconnects the sockets of clients matching `filter`, telling them why.
+    fn disconnect_where(&self, reason: &str, filter: impl Fn(&Client) -> bool) -> usize {
+        let sockets: Vec<Sid> = self
+            .ctx
+            .clients
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, client)| filter(client))
+            .map(|(sid, _)| *sid)
+            .collect();
+
+        for sid in &sockets {
+            if let Some(socket) = self.io.get_socket(*sid) {
+                let _ = socket.emit("session-revoked", json!({ "message": reason }));
+                let _ = socket.disconnect();
+            }
+        }
+        sockets.len()
+    }
+
+    /// Disconnects every socket of a user, after their sessions were revoked.
+    pub fn disconnect_user(&self, user_id: &str, reason: &str) -> usize {
+        self.disconnect_where(reason, |c| c.identity.user_id == user_id)
+    }
+
+    pub fn disconnect_session(&self, session_id: &str, reason: &str) -> usize {
+        self.disconnect_where(reason, |c| c.session_id == session_id)
+    }
+
+    /// Applies changed limits and IP rules; `max_request_bytes` only takes
+    /// effect on restart. Sockets from addresses the new rules refuse are dropped.
+    pub fn set_policy(&self, policy: RemotePolicy) {
+        self.ctx.guard.set_limits(policy.limits);
+        *self.ctx.ip_filter.write().unwrap() = policy.ip_filter;
+
+        for socket in self.io.sockets().unwrap_or_default() {
+            let Some(ip) = peer_ip(&socket) else { continue };
+            if let Err(reason) = self.ctx.check_ip(ip) {
+                let _ = socket.emit("connection-refused", json!({ "message": reason }));
+                let _ = socket.disconnect();
+            }
+        }
+    }
+
+    /// Lets an IP whose ban was lifted start over with clean counters.
+    pub fn forget_ip(&self, ip: IpAddr) {
+        self.ctx.guard.forget_ip(ip);
+    }
+
+    /// Ids of the sessions with a connected socket.
+    pub fn connected_sessions(&self) -> HashSet<String> {
+        self.ctx.clients.lock().unwrap().values().map(|c| c.session_id.clone()).collect()
+    }
+
+    /// Applies changed permissions to a user's connected sockets. Tokens carry
+    /// no permissions, so reconnects pick up the change from the database.
+    pub fn update_permissions(&self, user_id: &str, permissions: &[String]) {
+        for client in self.ctx.clients.lock().unwrap().values_mut() {
+            if client.identity.user_id == user_id {
+                client.identity.permissions = permissions.to_vec();
+            }
+        }
+    }
+
+    /// Loads the certificate files again, e.g. after it was regenerated.
+    pub async fn reload_certificate(&self) -> Result<(), String> {
+        let (Some(tls), Some(config)) = (&self.settings.tls, &self.rustls) else {
+            return Err("The remote server is not using TLS".to_string());
+        };
+        config
+            .reload_from_pem_file(&tls.cert_path, &tls.key_path)
+            .await
+            .map_err(|e| format!("Failed to load TLS certificate: {}", e))?;
+        eprintln!("[REMOTE] Reloaded TLS certificate from {}", tls.cert_path.display());
+        Ok(())
+    }
+
+    /// Tells clients, disconnects them and waits until the ports are released.
+    pub async fn stop(self) {
+        let _ = self.io.emit("server-shutdown", json!({ "message": "Server is shutting down" }));
+        let _ = self.io.disconnect();
+        for task in &self.background {
+            task.abort();
+        }
+        self.handle.graceful_shutdown(Some(SHUTDOWN_GRACE));
+        for task in self.tasks {
+            let _ = task.await;
+        }
+        eprintln!("[REMOTE] Stopped listening on {}:{}", self.settings.bind_address, self.settings.port);
+    }
+}
@@ -0,0 +1,405 @@
+// Remote logins against `remote_users` and the sessions they open. A login
+// opens a session in `remote_sessions` and hands out a short-lived access JWT
+// plus a refresh token. Refresh tokens rotate on every use; presenting one
+// that was already rotated away revokes the session, since only a stolen copy
+// could still hold it.
+//
+// `remote_sessions` doubles as the revocation list: an access token is only
+// accepted while the session it names is unrevoked and unexpired.
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use rand::RngCore;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::roles;
+use super::tokens::{Claims, SigningKey};
+
+const REFRESH_TTL_DAYS: i64 = 30;
+
+/// The remote user a socket or token acts as.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Identity {
+    pub user_id: String,
+    pub username: String,
+    /// Effective permission ids, already resolved from roles, grants and denies
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Tokens {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Unix timestamp at which the access token stops being accepted
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RemoteSession {
+    pub id: String,
+    pub user_id: String,
+    pub username: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: String,
+    pub last_used_at: String,
+    pub expires_at: String,
+    pub revoked_at: Option<String>,
+    pub revoked_reason: Option<String>,
+    /// Whether a socket of this session is connected right now
+    pub connected: bool,
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+// `<session id>.<secret>`, so the session can be found without a hash lookup
+fn new_refresh_token(session_id: &str) -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("{}.{}", session_id, hex::encode(bytes))
+}
+
+/// Loads who a user is now: refused if they were disabled or deleted since logging in.
+pub fn load_identity(conn: &Connection, user_id: &str) -> Result<Identity, String> {
+    let user: Option<(String, bool)> = conn
+        .query_row(
+            "SELECT username, disabled FROM remote_users WHERE id = ?1",
+            [user_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    let (username, disabled) = user.ok_or("User no longer exists")?;
+    if disabled {
+        return Err("Account is disabled".to_string());
+    }
+    Ok(Identity {
+        user_id: user_id.to_string(),
+        username,
+        permissions: roles::effective_permissions(conn, user_id)?,
+    })
+}
+
+/// Checks a username and password against `remote_users` and records the
+/// attempt on the user. bcrypt is slow on purpose, so call this from a blocking task.
+pub fn authenticate(db_path: &str, username: &str, password: &str, ip: Option<&str>) -> Result<Identity, String> {
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let user: Option<(String, String)> = conn
+        .query_row(
+            "SELECT id, password_hash FROM remote_users WHERE username = ?1",
+            [username],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    // Same message for unknown users and wrong passwords
+    let (user_id, password_hash) = user.ok_or("Invalid username or password")?;
+    let now = Utc::now().to_rfc3339();
+    if !bcrypt::verify(password, &password_hash).unwrap_or(false) {
+        conn.execute(
+            "UPDATE remote_users SET failed_logins = failed_logins + 1, last_failed_login_at = ?1 WHERE id = ?2",
+            rusqlite::params![now, user_id],
+        )
+        .map_err(|e| e.to_string())?;
+        return Err("Invalid username or password".to_string());
+    }
+    // Only revealed once the password checked out
+    let identity = load_identity(&conn, &user_id)?;
+
+    conn.execute(
+        "UPDATE remote_users SET failed_logins = 0, last_login_at = ?1, last_login_ip = ?2 WHERE id = ?3",
+        rusqlite::params![now, ip, user_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(identity)
+}
+
+/// Issues and checks tokens for one running remote server.
+pub struct Sessions {
+    db_path: String,
+    key: SigningKey,
+}
+
+impl Sessions {
+    pub fn open(db_path: &str) -> Result<Sessions, String> {
+        Ok(Sessions {
+            db_path: db_path.to_string(),
+            key: SigningKey::load_or_create(db_path)?,
+        })
+    }
+
+    fn conn(&self) -> Result<Connection, String> {
+        Connection::open(&self.db_path).map_err(|e| e.to_string())
+    }
+
+    /// Opens a session for a user who just logged in; returns its id and first tokens.
+    pub fn create(&self, identity: &Identity, ip: Option<&str>) -> Result<(String, Tokens), String> {
+        let conn = self.conn()?;
+        let now = Utc::now();
+        // Expired sessions are of no more use, not even for listing
+        conn.execute("DELETE FROM remote_sessions WHERE expires_at < ?1", [timestamp(now)])
+            .map_err(|e| e.to_string())?;
+
+        let session_id = Uuid::new_v4().to_string();
+        let refresh_token = new_refresh_token(&session_id);
+        conn.execute(
+            "INSERT INTO remote_sessions (id, user_id, refresh_hash, ip, created_at, last_used_at, expires_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5, ?6)",
+            rusqlite::params![
+                session_id,
+                identity.user_id,
+                hash_token(&refresh_token),
+                ip,
+                timestamp(now),
+                timestamp(now + Duration::days(REFRESH_TTL_DAYS)),
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+
+        let (access_token, expires_at) = self.key.issue(&identity.user_id, &identity.username, &session_id)?;
+        Ok((session_id, Tokens { access_token, refresh_token, expires_at }))
+    }
+
+    /// Trades a refresh token for a new pair.
+    pub fn refresh(&self, refresh_token: &str, ip: Option<&str>) -> Result<(Identity, String, Tokens), String> {
+        let conn = self.conn()?;
+        let (session_id, _) = refresh_token.split_once('.').ok_or("Invalid refresh token")?;
+        let session: Option<(String, Option<String>, String, Option<String>)> = conn
+            .query_row(
+                "SELECT user_id, previous_refresh_hash, expires_at, revoked_at FROM remote_sessions WHERE id = ?1",
+                [session_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+
+        let (user_id, previous_hash, expires_at, revoked_at) = session.ok_or("Invalid refresh token")?;
+        if revoked_at.is_some() {
+            return Err("Session has been revoked".to_string());
+        }
+        if expires_at <= timestamp(Utc::now()) {
+            return Err("Session expired".to_string());
+        }
+
+        let presented = hash_token(refresh_token);
+        let next = new_refresh_token(session_id);
+        // Rotating only if the stored hash still matches makes concurrent refreshes lose cleanly
+        let rotated = conn
+            .execute(
+                "UPDATE remote_sessions
+                 SET refresh_hash = ?1, previous_refresh_hash = ?2, last_used_at = ?3, ip = COALESCE(?4, ip)
+                 WHERE id = ?5 AND refresh_hash = ?2 AND revoked_at IS NULL",
+                rusqlite::params![hash_token(&next), presented, timestamp(Utc::now()), ip, session_id],
+            )
+            .map_err(|e| e.to_string())?;
+        if rotated == 0 {
+            if previous_hash.as_deref() == Some(presented.as_str()) {
+                revoke_session(&self.db_path, session_id, "Refresh token reused")?;
+                eprintln!("[REMOTE] Refresh token of session {} was reused, session revoked", session_id);
+                return Err("Refresh token was already used; session revoked".to_string());
+            }
+            return Err("Invalid refresh token".to_string());
+        }
+
+        let identity = load_identity(&conn, &user_id)?;
+        let (access_token, expires_at) = self.key.issue(&identity.user_id, &identity.username, session_id)?;
+        Ok((identity, session_id.to_string(), Tokens { access_token, refresh_token: next, expires_at }))
+    }
+
+    /// Checks an access token and that its session is still live.
+    pub fn validate(&self, access_token: &str) -> Result<(Identity, Claims), String> {
+        let claims = self.key.verify(access_token)?;
+        let conn = self.conn()?;
+        let live: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM remote_sessions
+                 WHERE id = ?1 AND user_id = ?2 AND revoked_at IS NULL AND expires_at > ?3",
+                rusqlite::params![claims.sid, claims.sub, timestamp(Utc::now())],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if !live {
+            return Err("Session has been revoked".to_string());
+        }
+
+        conn.execute(
+            "UPDATE remote_sessions SET last_used_at = ?1 WHERE id = ?2",
+            rusqlite::params![timestamp(Utc::now()), claims.sid],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok((load_identity(&conn, &claims.sub)?, claims))
+    }
+}
+
+/// Sessions that have not expired, newest first; revoked ones only when asked.
+pub fn list_sessions(db_path: &str, include_revoked: bool) -> Result<Vec<RemoteSession>, String> {
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT s.id, s.user_id, u.username, s.ip, s.created_at, s.last_used_at, s.expires_at,
+                    s.revoked_at, s.revoked_reason
+             FROM remote_sessions s LEFT JOIN remote_users u ON u.id = s.user_id
+             WHERE s.expires_at > ?1 AND (?2 OR s.revoked_at IS NULL)
+             ORDER BY s.created_at DESC",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let sessions = stmt
+        .query_map(rusqlite::params![timestamp(Utc::now()), include_revoked], |row| {
+            Ok(RemoteSession {
+                id: row.get(0)?,
+                user_id: row.get(1)?,
+                username: row.get(2)?,
+                ip: row.get(3)?,
+                created_at: row.get(4)?,
+                last_used_at: row.get(5)?,
+                expires_at: row.get(6)?,
+                revoked_at: row.get(7)?,
+                revoked_reason: row.get(8)?,
+                connected: false,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(sessions)
+}
+
+/// Returns the user the session belonged to, or None if it was unknown or already revoked.
+pub fn revoke_session(db_path: &str, session_id: &str, reason: &str) -> Result<Option<String>, String> {
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let user_id: Option<String> = conn
+        .query_row(
+            "SELECT user_id FROM remote_sessions WHERE id = ?1 AND revoked_at IS NULL",
+            [session_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if user_id.is_some() {
+        conn.execute(
+            "UPDATE remote_sessions SET revoked_at = ?1, revoked_reason = ?2 WHERE id = ?3",
+            rusqlite::params![timestamp(Utc::now()), reason, session_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(user_id)
+}
+
+/// Revokes every live session of a user; returns how many there were.
+pub fn revoke_user_sessions(db_path: &str, user_id: &str, reason: &str) -> Result<usize, String> {
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE remote_sessions SET revoked_at = ?1, revoked_reason = ?2 WHERE user_id = ?3 AND revoked_at IS NULL",
+        rusqlite::params![timestamp(Utc::now()), reason, user_id],
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (tempfile::TempDir, Sessions, Identity) {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("app.db").to_string_lossy().to_string();
+        crate::initialize_database(&db_path).unwrap();
+        Connection::open(&db_path)
+            .unwrap()
+            .execute(
+                "INSERT INTO remote_users (id, username, password_hash, permissions) VALUES ('u1', 'alice', 'x', '[]')",
+                [],
+            )
+            .unwrap();
+        let sessions = Sessions::open(&db_path).unwrap();
+        let identity = load_identity(&sessions.conn().unwrap(), "u1").unwrap();
+        (dir, sessions, identity)
+    }
+
+    fn revoked_reason(sessions: &Sessions, session_id: &str) -> Option<String> {
+        sessions
+            .conn()
+            .unwrap()
+            .query_row("SELECT revoked_reason FROM remote_sessions WHERE id = ?1", [session_id], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn refresh_rotates_the_token() {
+        let (_dir, sessions, identity) = setup();
+        let (session_id, first) = sessions.create(&identity, Some("10.0.0.2")).unwrap();
+        assert_eq!(sessions.validate(&first.access_token).unwrap().1.sid, session_id);
+
+        let (refreshed_as, refreshed_id, second) = sessions.refresh(&first.refresh_token, None).unwrap();
+        assert_eq!(refreshed_as.user_id, "u1");
+        assert_eq!(refreshed_id, session_id);
+        assert_ne!(second.refresh_token, first.refresh_token);
+        assert!(second.refresh_token.starts_with(&format!("{}.", session_id)));
+        sessions.validate(&second.access_token).unwrap();
+
+        let (_, _, third) = sessions.refresh(&second.refresh_token, None).unwrap();
+        sessions.validate(&third.access_token).unwrap();
+    }
+
+    #[test]
+    fn reusing_a_rotated_token_revokes_the_session() {
+        let (_dir, sessions, identity) = setup();
+        let (session_id, first) = sessions.create(&identity, None).unwrap();
+        let (_, _, second) = sessions.refresh(&first.refresh_token, None).unwrap();
+
+        let reused = sessions.refresh(&first.refresh_token, None).unwrap_err();
+        assert!(reused.contains("session revoked"), "{}", reused);
+        assert_eq!(revoked_reason(&sessions, &session_id).as_deref(), Some("Refresh token reused"));
+
+        // The legitimate holder is signed out as well
+        assert_eq!(sessions.refresh(&second.refresh_token, None).unwrap_err(), "Session has been revoked");
+        assert_eq!(sessions.validate(&second.access_token).unwrap_err(), "Session has been revoked");
+    }
+
+    #[test]
+    fn unknown_refresh_tokens_are_refused() {
+        let (_dir, sessions, identity) = setup();
+        let (session_id, _) = sessions.create(&identity, None).unwrap();
+        for token in ["", "no-separator", "missing.secret", &format!("{}.guess", session_id)] {
+            assert_eq!(sessions.refresh(token, None).unwrap_err(), "Invalid refresh token", "{}", token);
+        }
+        // A guess is not a reuse, so the session survives it
+        assert_eq!(revoked_reason(&sessions, &session_id), None);
+    }
+
+    #[test]
+    fn revoked_sessions_fail_validation() {
+        let (_dir, sessions, identity) = setup();
+        let (first_id, first) = sessions.create(&identity, None).unwrap();
+        let (_, second) = sessions.create(&identity, None).unwrap();
+
+        assert_eq!(revoke_session(&sessions.db_path, &first_id, "Signed out").unwrap().as_deref(), Some("u1"));
+        assert_eq!(revoke_session(&sessions.db_path, &first_id, "Again").unwrap(), None);
+        assert_eq!(sessions.validate(&first.access_token).unwrap_err(), "Session has been revoked");
+        sessions.validate(&second.access_token).unwrap();
+
+        assert_eq!(revoke_user_sessions(&sessions.db_path, "u1", "Password changed").unwrap(), 1);
+        assert_eq!(sessions.validate(&second.access_token).unwrap_err(), "Session has been revoked");
+        assert_eq!(list_sessions(&sessions.db_path, false).unwrap().len(), 0);
+        assert_eq!(list_sessions(&sessions.db_path, true).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn disabled_users_fail_validation_and_refresh() {
+        let (_dir, sessions, identity) = setup();
+        let (_, tokens) = sessions.create(&identity, None).unwrap();
+        sessions.conn().unwrap().execute("UPDATE remote_users SET disabled = 1 WHERE id = 'u1'", []).unwrap();
+        assert_eq!(sessions.validate(&tokens.access_token).unwrap_err(), "Account is disabled");
+        assert_eq!(sessions.refresh(&tokens.refresh_token, None).unwrap_err(), "Account is disabled");
+    }
+}
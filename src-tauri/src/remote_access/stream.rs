@@ -0,0 +1,504 @@
+// Live events for remote clients: console output, status changes, player
+// joins and leaves, and resource metrics. A client joins a room per topic and
+// gets what it missed from a short backlog in the ack:
+//
+//   -> stream:subscribe { topics: ["logs", "status"], since: 1042 }
+//   <- ack    { success: true, subscribed: ["logs", "status"], denied: [], events: [...] }
+//   <- stream { seq: 1043, topic: "logs", at: "...", data: { lines: [...] } }
+//
+// Sequence numbers are shared by all topics, so a client that reconnects asks
+// for everything after the last one it saw and skips replayed events it
+// already got live. Each topic needs the permission of `stream:<topic>`.
+//
+// Console lines are sent in batches. A socket whose send buffer is full misses
+// events instead of holding up the others; once it drains it is told what it
+// missed with `stream:lagged`, and one that stays full is disconnected.
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use socketioxide::extract::{AckSender, Data, SocketRef, State};
+use socketioxide::socket::Sid;
+use socketioxide::{SendError, SocketError, SocketIo};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, System};
+use tauri::Manager;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+use super::permissions;
+use super::server::{peer_ip, RemoteContext};
+use crate::services::audit_log::{self, Actor, Channel};
+use crate::AppState;
+
+// Console lines are held back this long to be sent together
+const LOG_BATCH_DELAY: Duration = Duration::from_millis(100);
+const LOG_BATCH_LINES: usize = 200;
+const METRICS_INTERVAL: Duration = Duration::from_secs(5);
+// A socket that cannot take events for this long is disconnected
+const SLOW_CONSUMER_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Join and leave lines as the server prints them, e.g. "[INFO] Steve joined the game"
+const JOIN_PHRASES: &[&str] = &[" joined the game", " joined the server", " has joined"];
+const LEAVE_PHRASES: &[&str] = &[" left the game", " left the server", " has left"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Topic {
+    Logs,
+    Status,
+    Players,
+    Metrics,
+}
+
+impl Topic {
+    pub const ALL: [Topic; 4] = [Topic::Logs, Topic::Status, Topic::Players, Topic::Metrics];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Topic::Logs => "logs",
+            Topic::Status => "status",
+            Topic::Players => "players",
+            Topic::Metrics => "metrics",
+        }
+    }
+
+    /// The socket.io room, which is also the command name permissions are checked against.
+    pub fn room(self) -> &'static str {
+        match self {
+            Topic::Logs => "stream:logs",
+            Topic::Status => "stream:status",
+            Topic::Players => "stream:players",
+            Topic::Metrics => "stream:metrics",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Topic> {
+        Topic::ALL.into_iter().find(|t| t.name() == name)
+    }
+
+    // Events kept for replay
+    fn backlog(self) -> usize {
+        match self {
+            Topic::Logs => 100,
+            Topic::Status => 10,
+            Topic::Players => 100,
+            Topic::Metrics => 60,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct StreamEvent {
+    pub seq: u64,
+    pub topic: &'static str,
+    pub at: String,
+    pub data: Value,
+}
+
+struct Lag {
+    first_missed: u64,
+    dropped: u64,
+    since: Instant,
+}
+
+/// Backlogs and delivery state, shared by the publishers and the sockets.
+pub struct StreamHub {
+    next_seq: AtomicU64,
+    backlogs: Mutex<HashMap<Topic, VecDeque<StreamEvent>>>,
+    lagging: Mutex<HashMap<Sid, Lag>>,
+    // Players seen joining since the server started
+    online: Mutex<BTreeSet<String>>,
+}
+
+impl StreamHub {
+    pub fn new() -> StreamHub {
+        StreamHub {
+            next_seq: AtomicU64::new(1),
+            backlogs: Mutex::new(HashMap::new()),
+            lagging: Mutex::new(HashMap::new()),
+            online: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    fn record(&self, topic: Topic, data: Value) -> StreamEvent {
+        let event = StreamEvent {
+            seq: self.next_seq.fetch_add(1, Ordering::SeqCst),
+            topic: topic.name(),
+            at: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            data,
+        };
+        let mut backlogs = self.backlogs.lock().unwrap();
+        let backlog = backlogs.entry(topic).or_default();
+        backlog.push_back(event.clone());
+        while backlog.len() > topic.backlog() {
+            backlog.pop_front();
+        }
+        event
+    }
+
+    /// Kept events of the topics after `since`, oldest first.
+    fn replay(&self, topics: &[Topic], since: Option<u64>) -> Vec<StreamEvent> {
+        let backlogs = self.backlogs.lock().unwrap();
+        let mut events: Vec<StreamEvent> = topics
+            .iter()
+            .filter_map(|topic| backlogs.get(topic))
+            .flatten()
+            .filter(|e| since.map_or(true, |since| e.seq > since))
+            .cloned()
+            .collect();
+        events.sort_by_key(|e| e.seq);
+        events
+    }
+
+    /// Sends an event to one socket without waiting on it.
+    fn deliver(&self, socket: &SocketRef, event: &StreamEvent) {
+        let mut lagging = self.lagging.lock().unwrap();
+        if let Some(lag) = lagging.get(&socket.id) {
+            let notice = json!({ "firstMissed": lag.first_missed, "dropped": lag.dropped });
+            if socket.emit("stream:lagged", notice).is_ok() {
+                lagging.remove(&socket.id);
+            }
+        }
+        // Nothing new goes out until the notice did, so the client hears of the gap first
+        let full = lagging.contains_key(&socket.id)
+            || matches!(
+                socket.emit("stream", event),
+                Err(SendError::Socket(SocketError::InternalChannelFull(_)))
+            );
+        if !full {
+            return;
+        }
+
+        let lag = lagging.entry(socket.id).or_insert_with(|| Lag {
+            first_missed: event.seq,
+            dropped: 0,
+            since: Instant::now(),
+        });
+        lag.dropped += 1;
+        if lag.since.elapsed() > SLOW_CONSUMER_TIMEOUT {
+            eprintln!("[REMOTE] Disconnecting {}: missed {} stream events", socket.id, lag.dropped);
+            lagging.remove(&socket.id);
+            let _ = socket.clone().disconnect();
+        }
+    }
+
+    pub fn forget(&self, sid: Sid) {
+        self.lagging.lock().unwrap().remove(&sid);
+    }
+}
+
+impl Default for StreamHub {
+    fn default() -> Self {
+        StreamHub::new()
+    }
+}
+
+/// Records an event and sends it to the sockets in the topic's room that may
+/// still see it. Sockets that logged out, let their token expire or lost the
+/// permission are taken out of the room.
+fn publish(ctx: &RemoteContext, io: &SocketIo, topic: Topic, data: Value) {
+    let event = ctx.streams.record(topic, data);
+    for socket in io.within(topic.room()).sockets().unwrap_or_default() {
+        let allowed = ctx
+            .live_client(&socket)
+            .is_ok_and(|client| permissions::authorize(&client.identity, topic.room()).is_ok());
+        if allowed {
+            ctx.streams.deliver(&socket, &event);
+        } else {
+            let _ = socket.leave(topic.room());
+        }
+    }
+}
+
+fn has_subscribers(io: &SocketIo, topic: Topic) -> bool {
+    io.within(topic.room()).sockets().is_ok_and(|s| !s.is_empty())
+}
+
+/// The player a join or leave line is about.
+fn player_event(line: &str) -> Option<(&'static str, String)> {
+    let events = [("join", JOIN_PHRASES), ("leave", LEAVE_PHRASES)];
+    for (event, phrases) in events {
+        for phrase in phrases {
+            let Some(index) = line.find(phrase) else { continue };
+            let name = line[..index]
+                .split_whitespace()
+                .last()
+                .unwrap_or_default()
+                .trim_matches(|c: char| !(c.is_alphanumeric() || c == '_'));
+            if !name.is_empty() {
+                return Some((event, name.to_string()));
+            }
+        }
+    }
+    None
+}
+
+fn publish_player(ctx: &RemoteContext, io: &SocketIo, event: &str, player: String) {
+    let online: Vec<String> = {
+        let mut online = ctx.streams.online.lock().unwrap();
+        match event {
+            "join" => online.insert(player.clone()),
+            _ => online.remove(&player),
+        };
+        online.iter().cloned().collect()
+    };
+    publish(ctx, io, Topic::Players, json!({ "event": event, "player": player, "online": online }));
+}
+
+fn flush_logs(ctx: &RemoteContext, io: &SocketIo, batch: &mut Vec<String>) {
+    if !batch.is_empty() {
+        publish(ctx, io, Topic::Logs, json!({ "lines": std::mem::take(batch) }));
+    }
+}
+
+// Follows the server's console and status for as long as the remote server runs
+async fn run_server_events(ctx: Arc<RemoteContext>, io: SocketIo) {
+    let service = ctx.app_handle.state::<AppState>().server_service.lock().unwrap().clone();
+    let Some(service) = service else {
+        eprintln!("[REMOTE] Server service unavailable, not streaming server events");
+        return;
+    };
+    let mut logs = service.subscribe_logs();
+    let mut statuses = service.subscribe_status();
+    let mut batch: Vec<String> = Vec::new();
+    let mut flush_at = tokio::time::Instant::now();
+
+    loop {
+        tokio::select! {
+            line = logs.recv() => match line {
+                Ok(line) => {
+                    if let Some((event, player)) = player_event(&line) {
+                        publish_player(&ctx, &io, event, player);
+                    }
+                    if batch.is_empty() {
+                        flush_at = tokio::time::Instant::now() + LOG_BATCH_DELAY;
+                    }
+                    batch.push(line);
+                    if batch.len() >= LOG_BATCH_LINES {
+                        flush_logs(&ctx, &io, &mut batch);
+                    }
+                }
+                // The console outpaced this task; say so in place of the lost lines
+                Err(RecvError::Lagged(skipped)) => {
+                    batch.push(format!("[PORTAL] {} console lines skipped", skipped));
+                }
+                Err(RecvError::Closed) => break,
+            },
+            status = statuses.recv() => match status {
+                Ok(status) => {
+                    flush_logs(&ctx, &io, &mut batch);
+                    if !status.running {
+                        ctx.streams.online.lock().unwrap().clear();
+                    }
+                    publish(&ctx, &io, Topic::Status, json!(status));
+                }
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            },
+            _ = tokio::time::sleep_until(flush_at), if !batch.is_empty() => {
+                flush_logs(&ctx, &io, &mut batch);
+            }
+        }
+    }
+}
+
+// Sampled only while someone is watching
+async fn run_metrics(ctx: Arc<RemoteContext>, io: SocketIo) {
+    let mut system = System::new();
+    let mut interval = tokio::time::interval(METRICS_INTERVAL);
+    loop {
+        interval.tick().await;
+        if !has_subscribers(&io, Topic::Metrics) {
+            continue;
+        }
+        let service = ctx.app_handle.state::<AppState>().server_service.lock().unwrap().clone();
+        let pid = match service {
+            Some(service) => service.get_status().await.ok().and_then(|status| status.pid),
+            None => None,
+        };
+
+        system.refresh_cpu();
+        system.refresh_memory();
+        // The start script's process and the server it launched
+        let server = pid.map(|pid| {
+            let pid = Pid::from_u32(pid);
+            system.refresh_processes();
+            let (cpu, memory) = system
+                .processes()
+                .values()
+                .filter(|p| p.pid() == pid || p.parent() == Some(pid))
+                .fold((0.0, 0), |(cpu, memory), p| (cpu + p.cpu_usage(), memory + p.memory()));
+            json!({ "pid": pid.as_u32(), "cpu": cpu, "memory": memory / (1024 * 1024) })
+        });
+        let players = ctx.streams.online.lock().unwrap().len();
+
+        publish(
+            &ctx,
+            &io,
+            Topic::Metrics,
+            json!({
+                "cpu": system.global_cpu_info().cpu_usage(),
+                "memory": {
+                    "total": system.total_memory() / (1024 * 1024),
+                    "used": system.used_memory() / (1024 * 1024),
+                },
+                "server": server,
+                "players": players,
+            }),
+        );
+    }
+}
+
+/// Starts the publishers; they run until aborted.
+pub(super) fn spawn_publishers(ctx: Arc<RemoteContext>, io: SocketIo) -> Vec<JoinHandle<()>> {
+    vec![
+        tokio::spawn(run_server_events(ctx.clone(), io.clone())),
+        tokio::spawn(run_metrics(ctx, io)),
+    ]
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct SubscribeRequest {
+    topics: Vec<String>,
+    /// Last sequence number the client saw; the whole backlog when missing
+    #[serde(default)]
+    since: Option<u64>,
+}
+
+pub(super) async fn on_subscribe(
+    socket: SocketRef,
+    Data(request): Data<SubscribeRequest>,
+    ack: AckSender,
+    State(ctx): State<Arc<RemoteContext>>,
+) {
+    let client = match ctx.live_client(&socket) {
+        Ok(client) => client,
+        Err(error) => {
+            let _ = ack.send(json!({ "success": false, "error": error }));
+            return;
+        }
+    };
+
+    let mut subscribed = Vec::new();
+    let mut denied = Vec::new();
+    for name in request.topics {
+        let Some(topic) = Topic::parse(&name) else {
+            denied.push(name);
+            continue;
+        };
+        if let Err(error) = permissions::authorize(&client.identity, topic.room()) {
+            let actor = Actor {
+                channel: Channel::Remote,
+                username: client.identity.username.clone(),
+                user_id: Some(client.identity.user_id.clone()),
+                ip: peer_ip(&socket).map(|ip| ip.to_string()),
+            };
+            audit_log::record_denied(&ctx.db_path, &actor, topic.room(), &error);
+            denied.push(name);
+            continue;
+        }
+        let _ = socket.join(topic.room());
+        subscribed.push(topic);
+    }
+
+    // Joined first, so an event published meanwhile is at worst sent twice
+    let events = ctx.streams.replay(&subscribed, request.since);
+    let _ = ack.send(json!({
+        "success": true,
+        "subscribed": subscribed.iter().map(|t| t.name()).collect::<Vec<_>>(),
+        "denied": denied,
+        "events": events,
+    }));
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct UnsubscribeRequest {
+    topics: Vec<String>,
+}
+
+pub(super) async fn on_unsubscribe(socket: SocketRef, Data(request): Data<UnsubscribeRequest>, ack: AckSender) {
+    for topic in request.topics.iter().filter_map(|name| Topic::parse(name)) {
+        let _ = socket.leave(topic.room());
+    }
+    let _ = ack.send(json!({ "success": true }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seqs(events: &[StreamEvent]) -> Vec<u64> {
+        events.iter().map(|e| e.seq).collect()
+    }
+
+    #[test]
+    fn join_and_leave_lines_name_the_player() {
+        let cases = [
+            ("[INFO] Steve joined the game", Some(("join", "Steve"))),
+            ("[12:00:01] [Server] <Alex_99> has joined", Some(("join", "Alex_99"))),
+            ("[INFO] Steve left the game", Some(("leave", "Steve"))),
+            ("Player 'Notch' left the server", Some(("leave", "Notch"))),
+            (" joined the game", None),
+            ("[INFO] Saving chunks for level 'world'", None),
+        ];
+        for (line, expected) in cases {
+            let parsed = player_event(line);
+            assert_eq!(parsed.as_ref().map(|(event, name)| (*event, name.as_str())), expected, "{}", line);
+        }
+    }
+
+    #[test]
+    fn replay_merges_topics_in_sequence_order() {
+        let hub = StreamHub::new();
+        hub.record(Topic::Logs, json!(1));
+        hub.record(Topic::Status, json!(2));
+        hub.record(Topic::Logs, json!(3));
+        hub.record(Topic::Metrics, json!(4));
+        hub.record(Topic::Status, json!(5));
+
+        let events = hub.replay(&[Topic::Status, Topic::Logs], None);
+        assert_eq!(seqs(&events), [1, 2, 3, 5]);
+        assert_eq!(events[1].topic, "status");
+        assert_eq!(events[3].data, json!(5));
+        assert!(hub.replay(&[Topic::Players], None).is_empty());
+    }
+
+    #[test]
+    fn replay_skips_what_the_client_has_seen() {
+        let hub = StreamHub::new();
+        for i in 0..5 {
+            hub.record(Topic::Logs, json!(i));
+        }
+        assert_eq!(seqs(&hub.replay(&[Topic::Logs], Some(3))), [4, 5]);
+        assert!(hub.replay(&[Topic::Logs], Some(5)).is_empty());
+        assert_eq!(seqs(&hub.replay(&[Topic::Logs], Some(0))), [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn backlogs_keep_only_the_newest_events() {
+        let hub = StreamHub::new();
+        let limit = Topic::Status.backlog() as u64;
+        for i in 0..limit + 5 {
+            hub.record(Topic::Status, json!(i));
+        }
+        hub.record(Topic::Logs, json!("other topic"));
+
+        let status = hub.replay(&[Topic::Status], None);
+        assert_eq!(status.len() as u64, limit);
+        assert_eq!(status.first().unwrap().seq, 6);
+        assert_eq!(status.last().unwrap().seq, limit + 5);
+        // Sequence numbers keep counting across topics
+        assert_eq!(hub.replay(&[Topic::Logs], None)[0].seq, limit + 6);
+    }
+
+    #[test]
+    fn topics_parse_by_name() {
+        for topic in Topic::ALL {
+            assert_eq!(Topic::parse(topic.name()), Some(topic));
+            assert_eq!(topic.room(), format!("stream:{}", topic.name()));
+        }
+        assert_eq!(Topic::parse("console"), None);
+    }
+}
@@ -0,0 +1,134 @@
+// TLS for the remote server, from `remote_config.tls`. Either a self-signed
+// certificate generated into the app data directory on first enable, or a PEM
+// certificate and key the user points at. Clients pin the self-signed one by
+// its SHA-256 fingerprint. The files are checked for changes while the server
+// runs, so a renewed certificate is picked up without dropping connections.
+use rcgen::{CertificateParams, DnType, KeyPair};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_REDIRECT_PORT: u16 = 9080;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TlsMode {
+    #[default]
+    SelfSigned,
+    Custom,
+}
+
+/// `remote_config.tls`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct TlsConfig {
+    pub enabled: bool,
+    pub mode: TlsMode,
+    /// PEM files, for `custom`
+    pub cert_path: Option<String>,
+    pub key_path: Option<String>,
+    /// Answers plain HTTP on `redirect_port` with a redirect to HTTPS
+    pub redirect_http: bool,
+    pub redirect_port: u16,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        TlsConfig {
+            enabled: false,
+            mode: TlsMode::SelfSigned,
+            cert_path: None,
+            key_path: None,
+            redirect_http: false,
+            redirect_port: DEFAULT_REDIRECT_PORT,
+        }
+    }
+}
+
+/// The certificate a listener serves, once resolved to files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsSettings {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    pub redirect_port: Option<u16>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CertificateInfo {
+    pub mode: TlsMode,
+    pub cert_path: String,
+    /// SHA-256 of the DER certificate, as colon-separated hex
+    pub fingerprint: String,
+}
+
+pub fn self_signed_paths(db_path: &str) -> (PathBuf, PathBuf) {
+    let dir = Path::new(db_path).with_file_name("remote-tls");
+    (dir.join("cert.pem"), dir.join("key.pem"))
+}
+
+/// Writes a new self-signed certificate for `names` (host names or IPs).
+pub fn generate_self_signed(cert_path: &Path, key_path: &Path, names: &[String]) -> Result<(), String> {
+    let mut params = CertificateParams::new(names.to_vec()).map_err(|e| e.to_string())?;
+    params.distinguished_name.push(DnType::CommonName, "Hytale Server Portal");
+    let key_pair = KeyPair::generate().map_err(|e| e.to_string())?;
+    let cert = params.self_signed(&key_pair).map_err(|e| e.to_string())?;
+
+    if let Some(dir) = cert_path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    fs::write(cert_path, cert.pem()).map_err(|e| e.to_string())?;
+    fs::write(key_path, key_pair.serialize_pem()).map_err(|e| e.to_string())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(key_path, fs::Permissions::from_mode(0o600));
+    }
+    eprintln!("[REMOTE] Generated self-signed certificate for {}", names.join(", "));
+    Ok(())
+}
+
+/// Where the certificate comes from, generating the self-signed one if it does
+/// not exist yet. None when TLS is off.
+pub fn resolve(config: &TlsConfig, db_path: &str, names: &[String]) -> Result<Option<TlsSettings>, String> {
+    if !config.enabled {
+        return Ok(None);
+    }
+    let (cert_path, key_path) = match config.mode {
+        TlsMode::SelfSigned => {
+            let (cert_path, key_path) = self_signed_paths(db_path);
+            if !cert_path.exists() || !key_path.exists() {
+                generate_self_signed(&cert_path, &key_path, names)?;
+            }
+            (cert_path, key_path)
+        }
+        TlsMode::Custom => {
+            let cert_path = config.cert_path.as_deref().filter(|p| !p.is_empty()).ok_or("No certificate file set")?;
+            let key_path = config.key_path.as_deref().filter(|p| !p.is_empty()).ok_or("No key file set")?;
+            (PathBuf::from(cert_path), PathBuf::from(key_path))
+        }
+    };
+    Ok(Some(TlsSettings {
+        cert_path,
+        key_path,
+        redirect_port: config.redirect_http.then_some(config.redirect_port),
+    }))
+}
+
+/// Fingerprint of the first certificate in a PEM file.
+pub fn fingerprint(cert_path: &Path) -> Result<String, String> {
+    let file = fs::File::open(cert_path).map_err(|e| format!("Failed to open {}: {}", cert_path.display(), e))?;
+    let der = rustls_pemfile::certs(&mut BufReader::new(file))
+        .next()
+        .ok_or_else(|| format!("No certificate in {}", cert_path.display()))?
+        .map_err(|e| e.to_string())?;
+    let digest = Sha256::digest(der.as_ref());
+    Ok(digest.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(":"))
+}
+
+/// Modification times of both files, to notice a renewal.
+pub fn file_stamp(settings: &TlsSettings) -> Option<(std::time::SystemTime, std::time::SystemTime)> {
+    let modified = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+    Some((modified(&settings.cert_path)?, modified(&settings.key_path)?))
+}
@@ -0,0 +1,160 @@
+// Access tokens for the remote protocol: short-lived HS256 JWTs naming the
+// user and the session they belong to. The signing key is generated on first
+// use and kept in `config` sealed under a key file next to the database, so a
+// copied database alone cannot mint tokens.
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rand::RngCore;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::utils::crypto;
+
+pub const ACCESS_TTL_SECS: i64 = 15 * 60;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Claims {
+    /// User id
+    pub sub: String,
+    pub name: String,
+    /// Session id, checked against the revocation list on use
+    pub sid: String,
+    pub jti: String,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+pub struct SigningKey {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+}
+
+fn key_file(db_path: &str) -> PathBuf {
+    Path::new(db_path).with_file_name("remote.key")
+}
+
+fn random_key() -> [u8; 32] {
+    let mut key = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut key);
+    key
+}
+
+// Only a missing file is replaced: a key that exists but cannot be read right
+// now must not be overwritten, or the sealed signing key is lost with it
+fn load_key_file(path: &Path) -> Result<[u8; 32], String> {
+    match fs::read(path) {
+        Ok(bytes) => match <[u8; 32]>::try_from(bytes.as_slice()) {
+            Ok(key) => return Ok(key),
+            Err(_) => eprintln!("[REMOTE] Ignoring malformed key file {}", path.display()),
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+
+    let key = random_key();
+    fs::write(path, key).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(path, fs::Permissions::from_mode(0o600));
+    }
+    Ok(key)
+}
+
+impl SigningKey {
+    /// Unseals the signing key from `config`, generating and storing one if
+    /// there is none or it cannot be decrypted (which signs every client out).
+    pub fn load_or_create(db_path: &str) -> Result<SigningKey, String> {
+        let master = load_key_file(&key_file(db_path))?;
+        let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+        let stored: Option<String> = conn
+            .query_row("SELECT value FROM config WHERE key = 'remote_signing_key'", [], |row| row.get(0))
+            .optional()
+            .map_err(|e| e.to_string())?;
+
+        let existing = stored.and_then(|sealed| {
+            let key = BASE64
+                .decode(sealed)
+                .ok()
+                .and_then(|sealed| crypto::unseal(&master, &sealed).ok());
+            if key.is_none() {
+                eprintln!("[REMOTE] Stored signing key cannot be decrypted, generating a new one");
+            }
+            key
+        });
+        let secret = match existing {
+            Some(secret) => secret,
+            None => {
+                let secret = random_key().to_vec();
+                conn.execute(
+                    "INSERT OR REPLACE INTO config (key, value) VALUES ('remote_signing_key', ?1)",
+                    [BASE64.encode(crypto::seal(&master, &secret))],
+                )
+                .map_err(|e| e.to_string())?;
+                secret
+            }
+        };
+
+        Ok(SigningKey {
+            encoding: EncodingKey::from_secret(&secret),
+            decoding: DecodingKey::from_secret(&secret),
+        })
+    }
+
+    /// Signs an access token; returns it with its expiry as a Unix timestamp.
+    pub fn issue(&self, user_id: &str, username: &str, session_id: &str) -> Result<(String, i64), String> {
+        let now = chrono::Utc::now().timestamp();
+        let claims = Claims {
+            sub: user_id.to_string(),
+            name: username.to_string(),
+            sid: session_id.to_string(),
+            jti: Uuid::new_v4().to_string(),
+            iat: now,
+            exp: now + ACCESS_TTL_SECS,
+        };
+        let token = jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &self.encoding)
+            .map_err(|e| e.to_string())?;
+        Ok((token, claims.exp))
+    }
+
+    /// Checks the signature and expiry only; revocation is up to the caller.
+    pub fn verify(&self, token: &str) -> Result<Claims, String> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.leeway = 5;
+        jsonwebtoken::decode::<Claims>(token, &self.decoding, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| match e.kind() {
+                jsonwebtoken::errors::ErrorKind::ExpiredSignature => "Token expired".to_string(),
+                _ => "Invalid token".to_string(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_missing_key_file_is_created_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("remote.key");
+        let key = load_key_file(&path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), key);
+        assert_eq!(load_key_file(&path).unwrap(), key);
+    }
+
+    #[test]
+    fn an_unreadable_key_file_is_not_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        // Reading a directory fails with something other than NotFound
+        let path = dir.path().join("remote.key");
+        fs::create_dir(&path).unwrap();
+        assert!(load_key_file(&path).unwrap_err().starts_with("Failed to read"));
+        assert!(path.is_dir());
+    }
+}
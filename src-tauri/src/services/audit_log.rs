@@ -0,0 +1,356 @@
+// Accountability for administrative actions, local or remote: who started or
+// stopped the server, sent which console command, changed or deleted which
+// file, restored a backup or changed users and permissions, from where, and
+// whether it worked. Entries go to `audit_log`, which triggers keep
+// append-only.
+//
+// Commands record themselves, so an action is logged the same way whichever
+// channel invoked it. The remote server runs its commands inside
+// `as_remote`; anything else is the local UI, attributed to its signed-in user.
+use chrono::{SecondsFormat, Utc};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::fs;
+use std::future::Future;
+use std::path::Path;
+
+const DEFAULT_LIMIT: u32 = 200;
+const MAX_LIMIT: u32 = 10_000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    Local,
+    Remote,
+}
+
+impl Channel {
+    fn as_str(self) -> &'static str {
+        match self {
+            Channel::Local => "local",
+            Channel::Remote => "remote",
+        }
+    }
+}
+
+/// Who an action is attributed to.
+#[derive(Debug, Clone)]
+pub struct Actor {
+    pub channel: Channel,
+    pub username: String,
+    pub user_id: Option<String>,
+    pub ip: Option<String>,
+}
+
+tokio::task_local! {
+    static REMOTE_ACTOR: Actor;
+}
+
+/// Runs a command on behalf of a remote user, so what it records is theirs.
+pub async fn as_remote<F: Future>(actor: Actor, command: F) -> F::Output {
+    REMOTE_ACTOR.scope(actor, command).await
+}
+
+// The local UI has a single account; it is whoever holds a live session
+fn local_actor(conn: &Connection) -> Actor {
+    let username = conn
+        .query_row(
+            "SELECT u.username FROM users u INNER JOIN sessions s ON u.id = s.user_id
+             WHERE s.expires_at > ?1 ORDER BY s.created_at DESC LIMIT 1",
+            [Utc::now().to_rfc3339()],
+            |row| row.get(0),
+        )
+        .unwrap_or_else(|_| "local".to_string());
+    Actor { channel: Channel::Local, username, user_id: None, ip: None }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub created_at: String,
+    pub channel: Channel,
+    pub username: String,
+    pub user_id: Option<String>,
+    pub ip: Option<String>,
+    /// Named like the remote commands, e.g. `server:start` or `users:delete`
+    pub action: String,
+    /// Path, user, backup or whatever else the action was applied to
+    pub target: Option<String>,
+    pub detail: Option<String>,
+    /// `success`, `failure` or `denied`
+    pub result: String,
+    pub error: Option<String>,
+}
+
+/// Appends an entry. Failing to write it is logged rather than passed on,
+/// since the action itself already happened.
+pub fn record(db_path: &str, action: &str, target: Option<&str>, detail: Option<&str>, error: Option<&str>) {
+    let result = if error.is_some() { "failure" } else { "success" };
+    append(db_path, action, target, detail, result, error);
+}
+
+/// Records a remote command refused for lack of permission.
+pub fn record_denied(db_path: &str, actor: &Actor, action: &str, error: &str) {
+    REMOTE_ACTOR.sync_scope(actor.clone(), || append(db_path, action, None, None, "denied", Some(error)));
+}
+
+/// Records the outcome of an action and passes it through.
+pub fn audited<T, E: Display>(
+    db_path: &str,
+    action: &str,
+    target: Option<&str>,
+    detail: Option<&str>,
+    result: Result<T, E>,
+) -> Result<T, E> {
+    let error = result.as_ref().err().map(|e| e.to_string());
+    record(db_path, action, target, detail, error.as_deref());
+    result
+}
+
+fn append(db_path: &str, action: &str, target: Option<&str>, detail: Option<&str>, result: &str, error: Option<&str>) {
+    let written = Connection::open(db_path).and_then(|conn| {
+        let actor = REMOTE_ACTOR.try_with(Actor::clone).unwrap_or_else(|_| local_actor(&conn));
+        conn.execute(
+            "INSERT INTO audit_log (created_at, channel, username, user_id, ip, action, target, detail, result, error)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            rusqlite::params![
+                Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+                actor.channel.as_str(),
+                actor.username,
+                actor.user_id,
+                actor.ip,
+                action,
+                target,
+                detail,
+                result,
+                error,
+            ],
+        )
+    });
+    if let Err(e) = written {
+        eprintln!("[AUDIT] Failed to record {}: {}", action, e);
+    }
+}
+
+/// Filters for `query`; every one that is set must match.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct AuditFilter {
+    pub username: Option<String>,
+    /// An action, or a prefix ending in `:` such as `files:`
+    pub action: Option<String>,
+    pub channel: Option<Channel>,
+    pub result: Option<String>,
+    pub ip: Option<String>,
+    /// Substring of the target or detail
+    pub search: Option<String>,
+    /// RFC 3339 timestamps, inclusive
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+/// Matching entries, newest first.
+pub fn query(db_path: &str, filter: &AuditFilter) -> Result<Vec<AuditEntry>, String> {
+    let mut clauses = Vec::new();
+    let mut params: Vec<SqlValue> = Vec::new();
+    // Each clause takes one value; every `?` in it refers to that value
+    let mut add = |clause: &str, value: String| {
+        params.push(SqlValue::Text(value));
+        clauses.push(clause.replace('?', &format!("?{}", params.len())));
+    };
+
+    if let Some(username) = &filter.username {
+        add("username = ? COLLATE NOCASE", username.clone());
+    }
+    match filter.action.as_deref() {
+        Some(prefix) if prefix.ends_with(':') => add("substr(action, 1, length(?)) = ?", prefix.to_string()),
+        Some(action) => add("action = ?", action.to_string()),
+        None => {}
+    }
+    if let Some(channel) = filter.channel {
+        add("channel = ?", channel.as_str().to_string());
+    }
+    if let Some(result) = &filter.result {
+        add("result = ?", result.clone());
+    }
+    if let Some(ip) = &filter.ip {
+        add("ip = ?", ip.clone());
+    }
+    if let Some(search) = filter.search.as_deref().filter(|s| !s.is_empty()) {
+        add("(instr(target, ?) > 0 OR instr(detail, ?) > 0)", search.to_string());
+    }
+    if let Some(since) = &filter.since {
+        add("created_at >= ?", since.clone());
+    }
+    if let Some(until) = &filter.until {
+        add("created_at <= ?", until.clone());
+    }
+
+    let mut sql = "SELECT id, created_at, channel, username, user_id, ip, action, target, detail, result, error
+                   FROM audit_log"
+        .to_string();
+    if !clauses.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&clauses.join(" AND "));
+    }
+    sql.push_str(&format!(
+        " ORDER BY id DESC LIMIT {} OFFSET {}",
+        filter.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT),
+        filter.offset.unwrap_or(0)
+    ));
+
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let entries = stmt
+        .query_map(rusqlite::params_from_iter(params), |row| {
+            let channel: String = row.get(2)?;
+            Ok(AuditEntry {
+                id: row.get(0)?,
+                created_at: row.get(1)?,
+                channel: if channel == "remote" { Channel::Remote } else { Channel::Local },
+                username: row.get(3)?,
+                user_id: row.get(4)?,
+                ip: row.get(5)?,
+                action: row.get(6)?,
+                target: row.get(7)?,
+                detail: row.get(8)?,
+                result: row.get(9)?,
+                error: row.get(10)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(entries)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditExportFormat {
+    Csv,
+    Json,
+}
+
+// Quoted when needed; values a spreadsheet would run as a formula are defused
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) { format!("'{}", value) } else { value.to_string() };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+/// Writes the matching entries to `path`, oldest first; returns how many.
+pub fn export(db_path: &str, filter: &AuditFilter, format: AuditExportFormat, path: &Path) -> Result<usize, String> {
+    let filter = AuditFilter { limit: Some(filter.limit.unwrap_or(MAX_LIMIT)), ..filter.clone() };
+    let mut entries = query(db_path, &filter)?;
+    entries.reverse();
+
+    let content = match format {
+        AuditExportFormat::Json => serde_json::to_string_pretty(&entries).map_err(|e| e.to_string())?,
+        AuditExportFormat::Csv => {
+            let mut csv = String::from("id,created_at,channel,username,user_id,ip,action,target,detail,result,error\n");
+            for entry in &entries {
+                let fields = [
+                    entry.id.to_string(),
+                    entry.created_at.clone(),
+                    entry.channel.as_str().to_string(),
+                    entry.username.clone(),
+                    entry.user_id.clone().unwrap_or_default(),
+                    entry.ip.clone().unwrap_or_default(),
+                    entry.action.clone(),
+                    entry.target.clone().unwrap_or_default(),
+                    entry.detail.clone().unwrap_or_default(),
+                    entry.result.clone(),
+                    entry.error.clone().unwrap_or_default(),
+                ];
+                csv.push_str(&fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(","));
+                csv.push('\n');
+            }
+            csv
+        }
+    };
+    fs::write(path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(entries.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database() -> (tempfile::TempDir, String) {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("app.db").to_string_lossy().to_string();
+        crate::initialize_database(&db_path).unwrap();
+        (dir, db_path)
+    }
+
+    #[test]
+    fn denials_are_audit_entries() {
+        let (_dir, db_path) = database();
+        let actor = Actor {
+            channel: Channel::Remote,
+            username: "bob".to_string(),
+            user_id: Some("u2".to_string()),
+            ip: Some("10.0.0.3".to_string()),
+        };
+        record_denied(&db_path, &actor, "stream:console", "Permission denied. Required: console.view");
+        record(&db_path, "files:write", Some("a.txt"), None, None);
+
+        let filter = AuditFilter { result: Some("denied".to_string()), ..Default::default() };
+        let denied = query(&db_path, &filter).unwrap();
+        let summary: Vec<_> = denied.iter().map(|e| (e.username.as_str(), e.action.as_str(), e.channel)).collect();
+        assert_eq!(summary, [("bob", "stream:console", Channel::Remote)]);
+        assert_eq!(denied[0].ip.as_deref(), Some("10.0.0.3"));
+        assert_eq!(query(&db_path, &AuditFilter::default()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn entries_cannot_be_changed_or_removed() {
+        let (_dir, db_path) = database();
+        record(&db_path, "files:delete", Some("world"), None, None);
+        let conn = Connection::open(&db_path).unwrap();
+
+        for statement in ["UPDATE audit_log SET target = 'elsewhere'", "DELETE FROM audit_log"] {
+            let error = conn.execute(statement, []).unwrap_err().to_string();
+            assert!(error.contains("audit_log is append-only"), "{}: {}", statement, error);
+        }
+        let target: String = conn.query_row("SELECT target FROM audit_log", [], |row| row.get(0)).unwrap();
+        assert_eq!(target, "world");
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_and_defused() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        for formula in ["=1+1", "+1", "-1", "@SUM(A1)"] {
+            assert_eq!(csv_field(formula), format!("'{}", formula));
+        }
+        assert_eq!(csv_field("=HYPERLINK(\"x\",1)"), "\"'=HYPERLINK(\"\"x\"\",1)\"");
+        assert_eq!(csv_field("a=b"), "a=b");
+    }
+
+    #[test]
+    fn csv_export_lists_entries_oldest_first() {
+        let (dir, db_path) = database();
+        record(&db_path, "files:write", Some("=cmd|' /C calc'!A0"), None, None);
+        record(&db_path, "files:delete", Some("a,b.txt"), None, Some("Not found"));
+
+        let path = dir.path().join("audit.csv");
+        assert_eq!(export(&db_path, &AuditFilter::default(), AuditExportFormat::Csv, &path).unwrap(), 2);
+        let csv = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert!(lines[0].starts_with("id,created_at,"));
+        assert!(lines[1].contains(",files:write,'=cmd|' /C calc'!A0,"), "{}", lines[1]);
+        assert!(lines[2].contains(",files:delete,\"a,b.txt\","), "{}", lines[2]);
+        assert!(lines[2].ends_with(",Not found"), "{}", lines[2]);
+    }
+}
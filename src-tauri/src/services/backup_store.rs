@@ -0,0 +1,428 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use anyhow::{anyhow, Context};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::utils::archive::{apply_mode, file_mode};
+
+pub const MANIFEST_EXTENSION: &str = "manifest";
+
+// Held while writing a snapshot and while collecting garbage, so GC never sees
+// blobs whose manifest has not been saved yet
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub sha256: Option<String>,
+    pub mode: Option<u32>,
+}
+
+/// An incremental backup: the directory tree at backup time, with every file
+/// pointing at a blob in the content-addressed store.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub id: String,
+    pub created_at: String,
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    pub fn total_size(&self) -> u64 {
+        self.entries.iter().map(|e| e.size).sum()
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Manifest> {
+        let data = fs::read(path).with_context(|| format!("Failed to read manifest {}", path.display()))?;
+        serde_json::from_slice(&data).context("Invalid backup manifest")
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GcReport {
+    pub removed_blobs: usize,
+    pub freed_bytes: u64,
+}
+
+/// Content-addressed blob store keyed by the SHA-256 of the uncompressed file.
+/// Blobs are gzip-compressed and fanned out by the first two hex digits.
+pub struct BackupStore {
+    root: PathBuf,
+}
+
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+static INCOMING: AtomicU64 = AtomicU64::new(0);
+
+// Hashes and counts the bytes passing through, so a file is stored and hashed from the same read
+struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+    len: u64,
+}
+
+impl<R: Read> HashingReader<R> {
+    fn new(inner: R) -> Self {
+        HashingReader { inner, hasher: Sha256::new(), len: 0 }
+    }
+
+    fn finish(self) -> (String, u64) {
+        (hex::encode(self.hasher.finalize()), self.len)
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        self.len += read as u64;
+        Ok(read)
+    }
+}
+
+// Manifest paths are joined onto the restore target, so they must be plain
+// relative paths
+fn check_entry_path(path: &str) -> anyhow::Result<()> {
+    let relative = Path::new(path);
+    let plain = !path.is_empty()
+        && relative.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !plain {
+        return Err(anyhow!("Manifest entry {} is not a relative path inside the backup", path));
+    }
+    Ok(())
+}
+
+impl BackupStore {
+    pub fn open(root: PathBuf) -> anyhow::Result<Self> {
+        fs::create_dir_all(&root).context("Failed to create backup store")?;
+        Ok(BackupStore { root })
+    }
+
+    fn blob_path(&self, hash: &str) -> PathBuf {
+        self.root.join(&hash[..2]).join(hash)
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        hash.len() > 2 && self.blob_path(hash).exists()
+    }
+
+    /// Stores `path` if its content is not already present and returns its
+    /// hash and size. The file is read once, so a file that changes while it is
+    /// stored still ends up under the hash of the bytes actually written.
+    pub fn put_file(&self, path: &Path) -> anyhow::Result<(String, u64)> {
+        // Compress into a temp file first, so an interrupted backup never leaves a truncated blob
+        let tmp = self.root.join(format!(
+            ".incoming-{}-{}",
+            std::process::id(),
+            INCOMING.fetch_add(1, Ordering::Relaxed)
+        ));
+        let result = (|| {
+            let mut reader = HashingReader::new(File::open(path)?);
+            let mut encoder = GzEncoder::new(File::create(&tmp)?, Compression::default());
+            io::copy(&mut reader, &mut encoder)?;
+            encoder.finish()?.sync_all()?;
+            let (hash, size) = reader.finish();
+
+            if !self.contains(&hash) {
+                let blob = self.blob_path(&hash);
+                fs::create_dir_all(blob.parent().unwrap())?;
+                fs::rename(&tmp, &blob)?;
+            }
+            Ok((hash, size))
+        })();
+        let _ = fs::remove_file(&tmp);
+        result
+    }
+
+    pub fn open_blob(&self, hash: &str) -> anyhow::Result<impl Read> {
+        if !self.contains(hash) {
+            return Err(anyhow!("Missing blob {}", hash));
+        }
+        Ok(GzDecoder::new(File::open(self.blob_path(hash))?))
+    }
+
+    /// Walks `source`, stores every file and saves the manifest describing the tree to `manifest_path`.
+    pub fn snapshot(&self, source: &Path, manifest_path: &Path, id: &str, created_at: &str) -> anyhow::Result<Manifest> {
+        let _guard = STORE_LOCK.lock().unwrap();
+        let mut entries = Vec::new();
+
+        for entry in walkdir::WalkDir::new(source).into_iter().filter_map(|e| e.ok()) {
+            let path = entry.path();
+            let name = path.strip_prefix(source).unwrap();
+            if name.as_os_str().is_empty() {
+                continue;
+            }
+
+            let metadata = entry.metadata()?;
+            let rel = name.to_string_lossy().replace('\\', "/");
+
+            if metadata.is_dir() {
+                entries.push(ManifestEntry {
+                    path: rel,
+                    is_dir: true,
+                    size: 0,
+                    sha256: None,
+                    mode: file_mode(&metadata),
+                });
+            } else if metadata.is_file() {
+                let (hash, size) = self.put_file(path)
+                    .with_context(|| format!("Failed to store {}", path.display()))?;
+                entries.push(ManifestEntry {
+                    path: rel,
+                    is_dir: false,
+                    size,
+                    sha256: Some(hash),
+                    mode: file_mode(&metadata),
+                });
+            }
+        }
+
+        let manifest = Manifest {
+            id: id.to_string(),
+            created_at: created_at.to_string(),
+            entries,
+        };
+        manifest.save(manifest_path)?;
+        Ok(manifest)
+    }
+
+    /// Checks that `manifest` can be restored in full: every path stays inside
+    /// the target and every blob it names is present.
+    pub fn check(&self, manifest: &Manifest) -> anyhow::Result<()> {
+        for entry in &manifest.entries {
+            check_entry_path(&entry.path)?;
+            if entry.is_dir {
+                continue;
+            }
+            let hash = entry.sha256.as_deref()
+                .ok_or_else(|| anyhow!("Manifest entry {} has no hash", entry.path))?;
+            if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(anyhow!("Manifest entry {} has an invalid hash", entry.path));
+            }
+            if !self.contains(hash) {
+                return Err(anyhow!("Missing blob {} for {}", hash, entry.path));
+            }
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the full tree described by `manifest` under `target`. The
+    /// manifest is checked first; a blob whose content no longer matches its
+    /// hash fails the restore.
+    pub fn restore(&self, manifest: &Manifest, target: &Path) -> anyhow::Result<()> {
+        self.check(manifest)?;
+        for entry in &manifest.entries {
+            let outpath = target.join(&entry.path);
+
+            if entry.is_dir {
+                fs::create_dir_all(&outpath)?;
+            } else {
+                if let Some(p) = outpath.parent() {
+                    fs::create_dir_all(p)?;
+                }
+                let hash = entry.sha256.as_deref()
+                    .ok_or_else(|| anyhow!("Manifest entry {} has no hash", entry.path))?;
+                let mut blob = self.open_blob(hash)?;
+                let mut outfile = File::create(&outpath)?;
+                let mut hasher = Sha256::new();
+                let mut buffer = [0u8; 64 * 1024];
+                loop {
+                    let read = blob.read(&mut buffer)
+                        .with_context(|| format!("Failed to read blob {}", hash))?;
+                    if read == 0 {
+                        break;
+                    }
+                    hasher.update(&buffer[..read]);
+                    outfile.write_all(&buffer[..read])?;
+                }
+                outfile.flush()?;
+                if hex::encode(hasher.finalize()) != hash {
+                    return Err(anyhow!("Blob {} for {} is corrupt", hash, entry.path));
+                }
+            }
+
+            apply_mode(&outpath, entry.mode)?;
+        }
+
+        Ok(())
+    }
+
+    /// Deletes every blob that is not referenced by a manifest in `manifest_dir`.
+    pub fn gc(&self, manifest_dir: &Path) -> anyhow::Result<GcReport> {
+        let _guard = STORE_LOCK.lock().unwrap();
+
+        let mut manifests = Vec::new();
+        for entry in fs::read_dir(manifest_dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|s| s.to_str()) == Some(MANIFEST_EXTENSION) {
+                manifests.push(Manifest::load(&path)?);
+            }
+        }
+
+        let referenced: HashSet<&str> = manifests
+            .iter()
+            .flat_map(|m| m.entries.iter())
+            .filter_map(|e| e.sha256.as_deref())
+            .collect();
+
+        let mut report = GcReport { removed_blobs: 0, freed_bytes: 0 };
+
+        for entry in walkdir::WalkDir::new(&self.root).into_iter().filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            if referenced.contains(name.as_str()) {
+                continue;
+            }
+
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            fs::remove_file(entry.path())?;
+            report.removed_blobs += 1;
+            report.freed_bytes += size;
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot_of(files: &[(&str, &str)]) -> (tempfile::TempDir, BackupStore, Manifest) {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source");
+        for (path, content) in files {
+            let file = source.join(path);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, content).unwrap();
+        }
+        let store = BackupStore::open(dir.path().join("store")).unwrap();
+        let manifest = store.snapshot(&source, &dir.path().join("b.manifest"), "b", "now").unwrap();
+        (dir, store, manifest)
+    }
+
+    fn file_entry(path: &str, hash: &str) -> ManifestEntry {
+        ManifestEntry { path: path.to_string(), is_dir: false, size: 1, sha256: Some(hash.to_string()), mode: None }
+    }
+
+    #[test]
+    fn restores_the_snapshotted_tree() {
+        let (dir, store, manifest) = snapshot_of(&[("a.txt", "one"), ("world/region.dat", "two")]);
+        let target = dir.path().join("target");
+        store.restore(&manifest, &target).unwrap();
+        assert_eq!(fs::read_to_string(target.join("a.txt")).unwrap(), "one");
+        assert_eq!(fs::read_to_string(target.join("world/region.dat")).unwrap(), "two");
+    }
+
+    #[test]
+    fn refuses_paths_outside_the_target() {
+        let (dir, store, manifest) = snapshot_of(&[("a.txt", "one")]);
+        let hash = manifest.entries[0].sha256.clone().unwrap();
+        for path in ["../escape.txt", "world/../../escape.txt", "/etc/escape.txt", ""] {
+            let mut evil = manifest.clone();
+            evil.entries.push(file_entry(path, &hash));
+            let target = dir.path().join("target");
+            assert!(store.restore(&evil, &target).is_err(), "{} was accepted", path);
+            assert!(!target.exists(), "{} wrote before being refused", path);
+        }
+        assert!(!dir.path().join("escape.txt").exists());
+    }
+
+    #[test]
+    fn missing_blob_is_refused_before_writing() {
+        let (dir, store, mut manifest) = snapshot_of(&[("a.txt", "one")]);
+        manifest.entries.push(file_entry("b.txt", &"ab".repeat(32)));
+        let target = dir.path().join("target");
+        assert!(store.check(&manifest).is_err());
+        assert!(store.restore(&manifest, &target).is_err());
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn malformed_hash_is_refused() {
+        let (_dir, store, mut manifest) = snapshot_of(&[("a.txt", "one")]);
+        manifest.entries.push(file_entry("b.txt", "../../outside"));
+        assert!(store.check(&manifest).is_err());
+    }
+
+    #[test]
+    fn corrupt_blob_fails_the_restore() {
+        let (dir, store, manifest) = snapshot_of(&[("a.txt", "one")]);
+        let hash = manifest.entries[0].sha256.clone().unwrap();
+        let blob = store.blob_path(&hash);
+        let mut encoder = GzEncoder::new(File::create(&blob).unwrap(), Compression::default());
+        encoder.write_all(b"not one").unwrap();
+        encoder.finish().unwrap();
+        assert!(store.restore(&manifest, &dir.path().join("target")).is_err());
+    }
+
+    #[test]
+    fn stored_blobs_match_their_hash_and_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("world.dat");
+        fs::write(&file, "region data").unwrap();
+        let store = BackupStore::open(dir.path().join("store")).unwrap();
+
+        let (hash, size) = store.put_file(&file).unwrap();
+        assert_eq!(hash, hash_file(&file).unwrap());
+        assert_eq!(size, 11);
+        let mut content = String::new();
+        store.open_blob(&hash).unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "region data");
+
+        // Storing the same content again reuses the blob and leaves no temp file behind
+        assert_eq!(store.put_file(&file).unwrap(), (hash, size));
+        let leftovers: Vec<_> = walkdir::WalkDir::new(&store.root)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .collect();
+        assert_eq!(leftovers.len(), 1);
+    }
+
+    #[test]
+    fn manifest_sizes_come_from_the_stored_bytes() {
+        let (_dir, _store, manifest) = snapshot_of(&[("a.txt", "one"), ("world/region.dat", "twelve bytes")]);
+        let sizes: Vec<(&str, u64)> = manifest
+            .entries
+            .iter()
+            .filter(|e| !e.is_dir)
+            .map(|e| (e.path.as_str(), e.size))
+            .collect();
+        assert!(sizes.contains(&("a.txt", 3)));
+        assert!(sizes.contains(&("world/region.dat", 12)));
+    }
+}
@@ -0,0 +1,203 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{checksum_name, is_backup_name, partial_name, BackupDestination, ProgressFn, RemoteBackup};
+use crate::services::backup_store::hash_file;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectoryConfig {
+    /// Typically a mounted network share or external drive
+    pub path: String,
+}
+
+pub struct DirectoryDestination {
+    root: PathBuf,
+}
+
+impl DirectoryDestination {
+    pub fn new(config: DirectoryConfig) -> Self {
+        DirectoryDestination { root: PathBuf::from(config.path) }
+    }
+}
+
+// Copies `reader` into `writer` in 1 MiB blocks, reporting progress from `done`
+pub(crate) fn copy_with_progress<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    mut done: u64,
+    total: u64,
+    progress: ProgressFn<'_>,
+) -> io::Result<u64> {
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        writer.write_all(&buffer[..read])?;
+        done += read as u64;
+        progress(done, total);
+    }
+    writer.flush()?;
+    Ok(done)
+}
+
+#[async_trait]
+impl BackupDestination for DirectoryDestination {
+    async fn upload(&self, local: &Path, remote_name: &str, sha256: &str, progress: ProgressFn<'_>) -> anyhow::Result<RemoteBackup> {
+        fs::create_dir_all(&self.root).context("Failed to create destination directory")?;
+        let target = self.root.join(remote_name);
+        let partial = self.root.join(partial_name(remote_name));
+        let total = fs::metadata(local)?.len();
+
+        // Resume from whatever an earlier attempt already copied
+        let mut out = OpenOptions::new().create(true).append(true).open(&partial)?;
+        let offset = out.metadata()?.len().min(total);
+        let mut source = File::open(local)?;
+        source.seek(SeekFrom::Start(offset))?;
+        copy_with_progress(&mut source, &mut out, offset, total, progress)?;
+        out.sync_all()?;
+        drop(out);
+
+        let stored = hash_file(&partial)?;
+        if stored != sha256 {
+            fs::remove_file(&partial)?;
+            return Err(anyhow!("Checksum mismatch after copying {}", remote_name));
+        }
+
+        fs::rename(&partial, &target)?;
+        fs::write(self.root.join(checksum_name(remote_name)), sha256)?;
+
+        Ok(RemoteBackup {
+            name: remote_name.to_string(),
+            size: total,
+            modified: Some(Utc::now().to_rfc3339()),
+            sha256: Some(sha256.to_string()),
+        })
+    }
+
+    async fn list(&self) -> anyhow::Result<Vec<RemoteBackup>> {
+        if !self.root.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut backups = Vec::new();
+        for entry in fs::read_dir(&self.root)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() || !is_backup_name(&name) {
+                continue;
+            }
+
+            backups.push(RemoteBackup {
+                sha256: fs::read_to_string(self.root.join(checksum_name(&name))).ok().map(|s| s.trim().to_string()),
+                modified: metadata.modified().ok().map(|t| DateTime::<Utc>::from(t).to_rfc3339()),
+                size: metadata.len(),
+                name,
+            });
+        }
+
+        Ok(backups)
+    }
+
+    async fn download(&self, remote_name: &str, local: &Path, progress: ProgressFn<'_>) -> anyhow::Result<()> {
+        let source_path = self.root.join(remote_name);
+        let total = fs::metadata(&source_path).context("Remote backup not found")?.len();
+        let mut source = File::open(&source_path)?;
+        let mut out = File::create(local)?;
+        copy_with_progress(&mut source, &mut out, 0, total, progress)?;
+
+        if let Ok(expected) = fs::read_to_string(self.root.join(checksum_name(remote_name))) {
+            if hash_file(local)? != expected.trim() {
+                fs::remove_file(local)?;
+                return Err(anyhow!("Checksum mismatch after downloading {}", remote_name));
+            }
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, remote_name: &str) -> anyhow::Result<()> {
+        fs::remove_file(self.root.join(remote_name))?;
+        let _ = fs::remove_file(self.root.join(checksum_name(remote_name)));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn archive(dir: &Path, len: usize) -> (PathBuf, Vec<u8>, String) {
+        let data: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+        let path = dir.join("local.zip");
+        fs::write(&path, &data).unwrap();
+        let sha256 = hash_file(&path).unwrap();
+        (path, data, sha256)
+    }
+
+    fn destination(dir: &Path) -> DirectoryDestination {
+        DirectoryDestination::new(DirectoryConfig { path: dir.join("remote").to_string_lossy().to_string() })
+    }
+
+    #[tokio::test]
+    async fn upload_resumes_after_the_partial_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let (local, data, sha256) = archive(dir.path(), 3 * 1024 * 1024);
+        let remote = dir.path().join("remote");
+        fs::create_dir_all(&remote).unwrap();
+        fs::write(remote.join("b.zip.part"), &data[..1_000_000]).unwrap();
+
+        let reported = Mutex::new(Vec::new());
+        let progress = |done: u64, _: u64| reported.lock().unwrap().push(done);
+        let stored = destination(dir.path()).upload(&local, "b.zip", &sha256, &progress).await.unwrap();
+
+        assert_eq!(stored.size, data.len() as u64);
+        assert_eq!(fs::read(remote.join("b.zip")).unwrap(), data);
+        assert_eq!(fs::read_to_string(remote.join("b.zip.sha256")).unwrap(), sha256);
+        assert!(!remote.join("b.zip.part").exists());
+        // Only what was missing was copied
+        assert_eq!(reported.lock().unwrap()[0], 1_000_000 + 1024 * 1024);
+    }
+
+    #[tokio::test]
+    async fn corrupt_partial_copy_is_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let (local, _, sha256) = archive(dir.path(), 4096);
+        let remote = dir.path().join("remote");
+        fs::create_dir_all(&remote).unwrap();
+        fs::write(remote.join("b.zip.part"), b"garbage").unwrap();
+
+        let target = destination(dir.path());
+        assert!(target.upload(&local, "b.zip", &sha256, &|_, _| {}).await.is_err());
+        assert!(!remote.join("b.zip.part").exists());
+        // The next attempt starts clean and succeeds
+        target.upload(&local, "b.zip", &sha256, &|_, _| {}).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn download_verifies_the_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let (local, _, sha256) = archive(dir.path(), 4096);
+        let target = destination(dir.path());
+        target.upload(&local, "b.zip", &sha256, &|_, _| {}).await.unwrap();
+
+        let listed = target.list().await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].sha256.as_deref(), Some(sha256.as_str()));
+
+        let fetched = dir.path().join("fetched.zip");
+        target.download("b.zip", &fetched, &|_, _| {}).await.unwrap();
+        assert_eq!(hash_file(&fetched).unwrap(), sha256);
+
+        fs::write(dir.path().join("remote/b.zip"), b"tampered").unwrap();
+        assert!(target.download("b.zip", &fetched, &|_, _| {}).await.is_err());
+        assert!(!fetched.exists());
+    }
+}
@@ -0,0 +1,68 @@
+// Off-site backup destinations
+pub mod directory;
+pub mod s3;
+pub mod sftp;
+
+use std::path::Path;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+pub use directory::{DirectoryConfig, DirectoryDestination};
+pub use s3::{S3Config, S3Destination};
+pub use sftp::{SftpConfig, SftpDestination};
+
+/// Reports `(bytes_done, bytes_total)` while a transfer runs.
+pub type ProgressFn<'a> = &'a (dyn Fn(u64, u64) + Send + Sync);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteBackup {
+    pub name: String,
+    pub size: u64,
+    pub modified: Option<String>,
+    pub sha256: Option<String>,
+}
+
+/// Where a destination stores backups, with its connection settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum DestinationKind {
+    S3(S3Config),
+    Sftp(SftpConfig),
+    Directory(DirectoryConfig),
+}
+
+/// A place backup archives can be copied to and fetched back from.
+///
+/// Uploads are resumable: calling `upload` again after an interrupted
+/// transfer continues where it stopped. Every implementation verifies the
+/// SHA-256 of the data it stored and of the data it downloads.
+#[async_trait]
+pub trait BackupDestination: Send + Sync {
+    async fn upload(&self, local: &Path, remote_name: &str, sha256: &str, progress: ProgressFn<'_>) -> anyhow::Result<RemoteBackup>;
+    async fn list(&self) -> anyhow::Result<Vec<RemoteBackup>>;
+    async fn download(&self, remote_name: &str, local: &Path, progress: ProgressFn<'_>) -> anyhow::Result<()>;
+    async fn delete(&self, remote_name: &str) -> anyhow::Result<()>;
+}
+
+/// `state_dir` holds resume state for transfers that outlive a single call.
+pub fn open_destination(kind: &DestinationKind, state_dir: &Path) -> anyhow::Result<Box<dyn BackupDestination>> {
+    Ok(match kind {
+        DestinationKind::S3(config) => Box::new(S3Destination::new(config.clone(), state_dir)?),
+        DestinationKind::Sftp(config) => Box::new(SftpDestination::new(config.clone())),
+        DestinationKind::Directory(config) => Box::new(DirectoryDestination::new(config.clone())),
+    })
+}
+
+// Directory and SFTP destinations keep each archive's hash next to it
+pub(crate) fn checksum_name(remote_name: &str) -> String {
+    format!("{}.sha256", remote_name)
+}
+
+// Partial uploads are written under this name and renamed once complete
+pub(crate) fn partial_name(remote_name: &str) -> String {
+    format!("{}.part", remote_name)
+}
+
+pub(crate) fn is_backup_name(name: &str) -> bool {
+    !name.starts_with('.') && !name.ends_with(".sha256") && !name.ends_with(".part")
+}
@@ -0,0 +1,293 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use ssh2::{HashType, OpenFlags, OpenType, RenameFlags, Session, Sftp};
+
+use super::directory::copy_with_progress;
+use super::{checksum_name, is_backup_name, partial_name, BackupDestination, ProgressFn, RemoteBackup};
+use crate::services::backup_store::hash_file;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SftpConfig {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    pub username: String,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub private_key_path: Option<String>,
+    #[serde(default)]
+    pub private_key_passphrase: Option<String>,
+    /// Hex SHA-256 of the server host key. Required: connections to any other
+    /// key, or with none pinned, are refused before credentials are sent
+    #[serde(default)]
+    pub host_key_sha256: Option<String>,
+    pub remote_dir: String,
+}
+
+fn default_port() -> u16 {
+    22
+}
+
+/// SFTP destination. libssh2 is blocking, so every operation runs on the
+/// blocking thread pool with its own session.
+pub struct SftpDestination {
+    config: Arc<SftpConfig>,
+}
+
+impl SftpDestination {
+    pub fn new(config: SftpConfig) -> Self {
+        SftpDestination { config: Arc::new(config) }
+    }
+
+    async fn run<T, F>(&self, op: F) -> anyhow::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&SftpConfig, &Sftp) -> anyhow::Result<T> + Send + 'static,
+    {
+        let config = self.config.clone();
+        tokio::task::spawn_blocking(move || {
+            let (_session, sftp) = connect(&config)?;
+            op(&config, &sftp)
+        })
+        .await?
+    }
+}
+
+// Without a pinned key there is no telling the server from an impostor, so
+// the connection is refused; the error carries the fingerprint to verify and pin
+fn check_host_key(config: &SftpConfig, fingerprint: &str) -> anyhow::Result<()> {
+    match config.host_key_sha256.as_deref().map(str::trim).filter(|k| !k.is_empty()) {
+        Some(expected) if expected.eq_ignore_ascii_case(fingerprint) => Ok(()),
+        Some(_) => Err(anyhow!("Host key mismatch for {}: got sha256 {}", config.host, fingerprint)),
+        None => Err(anyhow!(
+            "Host key for {} is not pinned. Verify that its SHA-256 fingerprint is {} and set it as the host key",
+            config.host,
+            fingerprint
+        )),
+    }
+}
+
+fn connect(config: &SftpConfig) -> anyhow::Result<(Session, Sftp)> {
+    let tcp = TcpStream::connect((config.host.as_str(), config.port))
+        .with_context(|| format!("Failed to connect to {}:{}", config.host, config.port))?;
+    let mut session = Session::new()?;
+    session.set_tcp_stream(tcp);
+    session.set_timeout(30_000);
+    session.handshake().context("SSH handshake failed")?;
+
+    let fingerprint = session
+        .host_key_hash(HashType::Sha256)
+        .map(hex::encode)
+        .ok_or_else(|| anyhow!("Server did not provide a host key"))?;
+    // Checked before any credentials are sent
+    check_host_key(config, &fingerprint)?;
+
+    match (&config.private_key_path, &config.password) {
+        (Some(key), _) => session.userauth_pubkey_file(
+            &config.username,
+            None,
+            Path::new(key),
+            config.private_key_passphrase.as_deref(),
+        )?,
+        (None, Some(password)) => session.userauth_password(&config.username, password)?,
+        (None, None) => return Err(anyhow!("SFTP destination needs a password or private key")),
+    }
+
+    let sftp = session.sftp()?;
+    Ok((session, sftp))
+}
+
+fn remote_path(config: &SftpConfig, name: &str) -> PathBuf {
+    Path::new(&config.remote_dir).join(name)
+}
+
+fn read_remote_string(sftp: &Sftp, path: &Path) -> Option<String> {
+    let mut content = String::new();
+    sftp.open(path).ok()?.read_to_string(&mut content).ok()?;
+    Some(content.trim().to_string())
+}
+
+fn ensure_remote_dir(sftp: &Sftp, dir: &Path) -> anyhow::Result<()> {
+    if sftp.stat(dir).is_ok() {
+        return Ok(());
+    }
+    if let Some(parent) = dir.parent() {
+        if !parent.as_os_str().is_empty() {
+            ensure_remote_dir(sftp, parent)?;
+        }
+    }
+    sftp.mkdir(dir, 0o755).with_context(|| format!("Failed to create {}", dir.display()))?;
+    Ok(())
+}
+
+#[async_trait]
+impl BackupDestination for SftpDestination {
+    async fn upload(&self, local: &Path, remote_name: &str, sha256: &str, progress: ProgressFn<'_>) -> anyhow::Result<RemoteBackup> {
+        let local = local.to_path_buf();
+        let remote_name = remote_name.to_string();
+        let sha256 = sha256.to_string();
+        let total = std::fs::metadata(&local)?.len();
+
+        // Progress is reported from the blocking thread through a channel
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let task = self.run(move |config, sftp| {
+            ensure_remote_dir(sftp, Path::new(&config.remote_dir))?;
+            let target = remote_path(config, &remote_name);
+            let partial = remote_path(config, &partial_name(&remote_name));
+
+            // Resume after the bytes an earlier attempt already wrote
+            let offset = sftp.stat(&partial).ok().and_then(|s| s.size).unwrap_or(0).min(total);
+            let mut out = sftp.open_mode(&partial, OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::APPEND, 0o644, OpenType::File)?;
+            let mut source = File::open(&local)?;
+            source.seek(SeekFrom::Start(offset))?;
+            let report = move |done: u64, total: u64| {
+                let _ = tx.send((done, total));
+            };
+            copy_with_progress(&mut source, &mut out, offset, total, &report)?;
+            drop(out);
+
+            // Read the upload back to make sure the server stored what we sent
+            let mut hasher = Sha256::new();
+            std::io::copy(&mut sftp.open(&partial)?, &mut hasher)?;
+            if hex::encode(hasher.finalize()) != sha256 {
+                let _ = sftp.unlink(&partial);
+                return Err(anyhow!("Checksum mismatch after uploading {}", remote_name));
+            }
+
+            let _ = sftp.unlink(&target);
+            sftp.rename(&partial, &target, Some(RenameFlags::OVERWRITE | RenameFlags::ATOMIC | RenameFlags::NATIVE))?;
+            sftp.create(&remote_path(config, &checksum_name(&remote_name)))?.write_all(sha256.as_bytes())?;
+
+            Ok(RemoteBackup {
+                name: remote_name,
+                size: total,
+                modified: Some(chrono::Utc::now().to_rfc3339()),
+                sha256: Some(sha256),
+            })
+        });
+
+        tokio::pin!(task);
+        loop {
+            tokio::select! {
+                result = &mut task => return result,
+                Some((done, total)) = rx.recv() => progress(done, total),
+            }
+        }
+    }
+
+    async fn list(&self) -> anyhow::Result<Vec<RemoteBackup>> {
+        self.run(|config, sftp| {
+            let dir = Path::new(&config.remote_dir);
+            if sftp.stat(dir).is_err() {
+                return Ok(Vec::new());
+            }
+
+            let mut backups = Vec::new();
+            for (path, stat) in sftp.readdir(dir)? {
+                let name = match path.file_name() {
+                    Some(name) => name.to_string_lossy().to_string(),
+                    None => continue,
+                };
+                if !stat.is_file() || !is_backup_name(&name) {
+                    continue;
+                }
+
+                backups.push(RemoteBackup {
+                    sha256: read_remote_string(sftp, &remote_path(config, &checksum_name(&name))),
+                    modified: stat
+                        .mtime
+                        .and_then(|t| DateTime::from_timestamp(t as i64, 0))
+                        .map(|t| t.to_rfc3339()),
+                    size: stat.size.unwrap_or(0),
+                    name,
+                });
+            }
+            Ok(backups)
+        })
+        .await
+    }
+
+    async fn download(&self, remote_name: &str, local: &Path, progress: ProgressFn<'_>) -> anyhow::Result<()> {
+        let local = local.to_path_buf();
+        let remote_name = remote_name.to_string();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let task = self.run(move |config, sftp| {
+            let source_path = remote_path(config, &remote_name);
+            let total = sftp.stat(&source_path).context("Remote backup not found")?.size.unwrap_or(0);
+            let mut source = sftp.open(&source_path)?;
+            let mut out = File::create(&local)?;
+            let report = move |done: u64, total: u64| {
+                let _ = tx.send((done, total));
+            };
+            copy_with_progress(&mut source, &mut out, 0, total, &report)?;
+
+            if let Some(expected) = read_remote_string(sftp, &remote_path(config, &checksum_name(&remote_name))) {
+                if hash_file(&local)? != expected {
+                    std::fs::remove_file(&local)?;
+                    return Err(anyhow!("Checksum mismatch after downloading {}", remote_name));
+                }
+            }
+            Ok(())
+        });
+
+        tokio::pin!(task);
+        loop {
+            tokio::select! {
+                result = &mut task => return result,
+                Some((done, total)) = rx.recv() => progress(done, total),
+            }
+        }
+    }
+
+    async fn delete(&self, remote_name: &str) -> anyhow::Result<()> {
+        let remote_name = remote_name.to_string();
+        self.run(move |config, sftp| {
+            sftp.unlink(&remote_path(config, &remote_name))?;
+            let _ = sftp.unlink(&remote_path(config, &checksum_name(&remote_name)));
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(host_key_sha256: Option<&str>) -> SftpConfig {
+        SftpConfig {
+            host: "backup.example".to_string(),
+            port: 22,
+            username: "hytale".to_string(),
+            password: Some("secret".to_string()),
+            private_key_path: None,
+            private_key_passphrase: None,
+            host_key_sha256: host_key_sha256.map(str::to_string),
+            remote_dir: "/backups".to_string(),
+        }
+    }
+
+    #[test]
+    fn unpinned_host_key_is_refused_with_its_fingerprint() {
+        for unpinned in [None, Some(""), Some("  ")] {
+            let error = check_host_key(&config(unpinned), "abcd").unwrap_err().to_string();
+            assert!(error.contains("not pinned") && error.contains("abcd"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn pinned_host_key_must_match() {
+        assert!(check_host_key(&config(Some("ABCD")), "abcd").is_ok());
+        assert!(check_host_key(&config(Some("abcd")), "abce").is_err());
+    }
+}
@@ -0,0 +1,387 @@
+// Versioned text edits for the file manager. A read returns the content with
+// a version token (its SHA-256); a write may pass the token it started from
+// and is rejected if the file changed in the meantime, so a local and a
+// remote editor cannot silently overwrite each other.
+//
+// Every write keeps the content it replaces in a short per-file history,
+// stored outside the server directory, which can be diffed and reverted.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use similar::TextDiff;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::utils::sandbox::{Sandbox, SandboxError};
+
+// Serializes check-and-write so two writers with the same version cannot both win
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Error)]
+pub enum EditError {
+    #[error(transparent)]
+    Sandbox(#[from] SandboxError),
+    #[error("File changed since it was read: {path}")]
+    Conflict { path: String, current: Option<Box<VersionedFile>> },
+    #[error("Not a text file: {0}")]
+    NotText(String),
+    #[error("Revision not found: {0}")]
+    RevisionNotFound(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VersionedFile {
+    pub path: String,
+    pub content: String,
+    pub version: String,
+    pub modified: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileVersion {
+    pub path: String,
+    pub version: String,
+    pub size: u64,
+    pub modified: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Revision {
+    pub id: String,
+    /// Relative to the server directory
+    pub path: String,
+    /// Version token of the content this revision holds
+    pub version: String,
+    pub size: u64,
+    /// When the content was replaced
+    pub saved_at: String,
+}
+
+pub fn version_of(content: &[u8]) -> String {
+    hex::encode(Sha256::digest(content))
+}
+
+fn modified_of(path: &Path) -> Option<String> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .map(|t| DateTime::<Utc>::from(t).to_rfc3339())
+}
+
+fn load(sandbox: &Sandbox, target: &Path) -> Result<VersionedFile, EditError> {
+    let bytes = fs::read(target)?;
+    let version = version_of(&bytes);
+    let content = String::from_utf8(bytes).map_err(|_| EditError::NotText(sandbox.relative(target)))?;
+    Ok(VersionedFile {
+        path: sandbox.relative(target),
+        content,
+        version,
+        modified: modified_of(target),
+    })
+}
+
+// Writes a sibling temp file and renames it over `target`, so a crash or a
+// concurrent reader never sees a half-written file
+fn replace_file(target: &Path, content: &[u8]) -> io::Result<()> {
+    let name = target.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let temp = target.with_file_name(format!(".{}.{}.tmp", name, Uuid::new_v4()));
+    let result = (|| {
+        fs::write(&temp, content)?;
+        if let Ok(metadata) = fs::metadata(target) {
+            fs::set_permissions(&temp, metadata.permissions())?;
+        }
+        fs::rename(&temp, target)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+pub fn read(sandbox: &Sandbox, path: &str) -> Result<VersionedFile, EditError> {
+    let target = sandbox.resolve(path)?;
+    load(sandbox, &target)
+}
+
+/// Writes `content`, replacing the file only if it still has version
+/// `expected`. Without `expected` the write is unconditional. A conflict
+/// carries the current content, or none if the file was deleted.
+pub fn write(
+    sandbox: &Sandbox,
+    history: Option<&History>,
+    path: &str,
+    content: &str,
+    expected: Option<&str>,
+) -> Result<FileVersion, EditError> {
+    let target = sandbox.resolve_writable(path)?;
+    let relative = sandbox.relative(&target);
+    let _guard = WRITE_LOCK.lock().unwrap();
+
+    let previous = match fs::read(&target) {
+        Ok(bytes) => Some(bytes),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    if let Some(expected) = expected {
+        if previous.as_deref().map(version_of).as_deref() != Some(expected) {
+            let current = match &previous {
+                Some(_) => load(sandbox, &target).ok().map(Box::new),
+                None => None,
+            };
+            return Err(EditError::Conflict { path: relative, current });
+        }
+    }
+
+    if let (Some(history), Some(previous)) = (history, &previous) {
+        if previous.as_slice() != content.as_bytes() {
+            history.record(&relative, previous)?;
+        }
+    }
+    replace_file(&target, content.as_bytes())?;
+
+    Ok(FileVersion {
+        path: relative,
+        version: version_of(content.as_bytes()),
+        size: content.len() as u64,
+        modified: modified_of(&target),
+    })
+}
+
+/// Unified diff from a revision to the current file, or to another revision when `against` is set.
+pub fn diff(
+    sandbox: &Sandbox,
+    history: &History,
+    path: &str,
+    revision_id: &str,
+    against: Option<&str>,
+) -> Result<String, EditError> {
+    let target = sandbox.resolve(path)?;
+    let relative = sandbox.relative(&target);
+    let old = history.read(&relative, revision_id)?;
+    let (new, new_label) = match against {
+        Some(other) => (history.read(&relative, other)?, other.to_string()),
+        None => (load(sandbox, &target)?.content, "current".to_string()),
+    };
+
+    Ok(TextDiff::from_lines(&old, &new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("{} ({})", relative, revision_id), &format!("{} ({})", relative, new_label))
+        .to_string())
+}
+
+/// Restores a revision's content through `write`, so the replaced content becomes a revision too.
+pub fn revert(
+    sandbox: &Sandbox,
+    history: &History,
+    path: &str,
+    revision_id: &str,
+    expected: Option<&str>,
+) -> Result<FileVersion, EditError> {
+    let relative = sandbox.relative(&sandbox.resolve_writable(path)?);
+    let content = history.read(&relative, revision_id)?;
+    write(sandbox, Some(history), path, &content, expected)
+}
+
+/// Revisions per file, kept as `<key>/<id>.txt` with metadata in `<key>/<id>.json`,
+/// where the key is derived from the relative path. Only the newest `limit` are kept.
+pub struct History {
+    root: PathBuf,
+    limit: usize,
+}
+
+impl History {
+    pub fn open(root: PathBuf, limit: usize) -> io::Result<History> {
+        fs::create_dir_all(&root)?;
+        Ok(History { root, limit })
+    }
+
+    fn file_dir(&self, relative: &str) -> PathBuf {
+        let key = Sha256::digest(relative.as_bytes());
+        self.root.join(hex::encode(&key[..16]))
+    }
+
+    fn revision_path(&self, relative: &str, id: &str, extension: &str) -> Result<PathBuf, EditError> {
+        Uuid::parse_str(id).map_err(|_| EditError::RevisionNotFound(id.to_string()))?;
+        Ok(self.file_dir(relative).join(format!("{}.{}", id, extension)))
+    }
+
+    pub fn record(&self, relative: &str, content: &[u8]) -> Result<Option<Revision>, EditError> {
+        if self.limit == 0 {
+            return Ok(None);
+        }
+        let revision = Revision {
+            id: Uuid::new_v4().to_string(),
+            path: relative.to_string(),
+            version: version_of(content),
+            size: content.len() as u64,
+            saved_at: Utc::now().to_rfc3339(),
+        };
+
+        fs::create_dir_all(self.file_dir(relative))?;
+        fs::write(self.revision_path(relative, &revision.id, "txt")?, content)?;
+        fs::write(
+            self.revision_path(relative, &revision.id, "json")?,
+            serde_json::to_vec_pretty(&revision).map_err(io::Error::other)?,
+        )?;
+
+        for old in self.list(relative)?.iter().skip(self.limit) {
+            self.remove(relative, &old.id)?;
+        }
+        Ok(Some(revision))
+    }
+
+    /// Revisions of one file, newest first.
+    pub fn list(&self, relative: &str) -> Result<Vec<Revision>, EditError> {
+        let dir = self.file_dir(relative);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut revisions = Vec::new();
+        for file in fs::read_dir(&dir)? {
+            let path = file?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            match fs::read(&path).ok().and_then(|b| serde_json::from_slice::<Revision>(&b).ok()) {
+                // Another path could only share the directory through a hash collision
+                Some(revision) if revision.path == relative => revisions.push(revision),
+                Some(_) => {}
+                None => eprintln!("[FILES] Skipping unreadable revision {}", path.display()),
+            }
+        }
+        revisions.sort_by(|a, b| b.saved_at.cmp(&a.saved_at));
+        Ok(revisions)
+    }
+
+    pub fn read(&self, relative: &str, id: &str) -> Result<String, EditError> {
+        let bytes = fs::read(self.revision_path(relative, id, "txt")?)
+            .map_err(|_| EditError::RevisionNotFound(id.to_string()))?;
+        String::from_utf8(bytes).map_err(|_| EditError::NotText(relative.to_string()))
+    }
+
+    fn remove(&self, relative: &str, id: &str) -> Result<(), EditError> {
+        let _ = fs::remove_file(self.revision_path(relative, id, "txt")?);
+        let _ = fs::remove_file(self.revision_path(relative, id, "json")?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup(limit: usize) -> (tempfile::TempDir, Sandbox, History) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("server");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("config.json"), "v1").unwrap();
+        let sandbox = Sandbox::new(&root, Vec::new()).unwrap();
+        let history = History::open(dir.path().join("history"), limit).unwrap();
+        (dir, sandbox, history)
+    }
+
+    fn contents(sandbox: &Sandbox, path: &str) -> String {
+        fs::read_to_string(sandbox.resolve(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn writes_from_the_current_version_succeed() {
+        let (_dir, sandbox, history) = setup(5);
+        let file = read(&sandbox, "config.json").unwrap();
+        assert_eq!(file.version, version_of(b"v1"));
+
+        let written = write(&sandbox, Some(&history), "config.json", "v2", Some(&file.version)).unwrap();
+        assert_eq!(written.version, version_of(b"v2"));
+        assert_eq!(contents(&sandbox, "config.json"), "v2");
+
+        // No temp file is left next to the target
+        let names: Vec<_> = fs::read_dir(sandbox.resolve("").unwrap()).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(names, ["config.json"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn replacing_keeps_the_file_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        let (_dir, sandbox, _history) = setup(5);
+        let target = sandbox.resolve("config.json").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o640)).unwrap();
+        write(&sandbox, None, "config.json", "v2", None).unwrap();
+        assert_eq!(fs::metadata(&target).unwrap().permissions().mode() & 0o777, 0o640);
+    }
+
+    #[test]
+    fn stale_versions_conflict() {
+        let (_dir, sandbox, history) = setup(5);
+        let stale = read(&sandbox, "config.json").unwrap().version;
+        write(&sandbox, Some(&history), "config.json", "theirs", None).unwrap();
+
+        match write(&sandbox, Some(&history), "config.json", "mine", Some(&stale)) {
+            Err(EditError::Conflict { path, current }) => {
+                assert_eq!(path, "config.json");
+                assert_eq!(current.unwrap().content, "theirs");
+            }
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+        assert_eq!(contents(&sandbox, "config.json"), "theirs");
+
+        fs::remove_file(sandbox.resolve("config.json").unwrap()).unwrap();
+        match write(&sandbox, Some(&history), "config.json", "mine", Some(&stale)) {
+            Err(EditError::Conflict { current, .. }) => assert!(current.is_none()),
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn history_keeps_the_newest_revisions() {
+        let (_dir, sandbox, history) = setup(2);
+        for content in ["v2", "v3", "v4"] {
+            write(&sandbox, Some(&history), "config.json", content, None).unwrap();
+        }
+        // Unchanged content is not a revision
+        write(&sandbox, Some(&history), "config.json", "v4", None).unwrap();
+
+        let revisions = history.list("config.json").unwrap();
+        let kept: Vec<String> = revisions.iter().map(|r| history.read("config.json", &r.id).unwrap()).collect();
+        assert_eq!(kept, ["v3", "v2"]);
+        assert_eq!(revisions[0].version, version_of(b"v3"));
+
+        let disabled = History::open(history.root.join("off"), 0).unwrap();
+        assert!(disabled.record("config.json", b"v1").unwrap().is_none());
+        assert!(disabled.list("config.json").unwrap().is_empty());
+    }
+
+    #[test]
+    fn revert_restores_a_revision_and_keeps_the_replaced_content() {
+        let (_dir, sandbox, history) = setup(5);
+        write(&sandbox, Some(&history), "config.json", "v2", None).unwrap();
+        let original = history.list("config.json").unwrap().remove(0);
+
+        let current = read(&sandbox, "config.json").unwrap().version;
+        let diff = diff(&sandbox, &history, "config.json", &original.id, None).unwrap();
+        assert!(diff.contains("-v1") && diff.contains("+v2"), "{}", diff);
+
+        revert(&sandbox, &history, "config.json", &original.id, Some(&current)).unwrap();
+        assert_eq!(contents(&sandbox, "config.json"), "v1");
+        let newest = history.list("config.json").unwrap().remove(0);
+        assert_eq!(history.read("config.json", &newest.id).unwrap(), "v2");
+
+        // Reverting from a version that is no longer current is a conflict like any write
+        assert!(matches!(
+            revert(&sandbox, &history, "config.json", &newest.id, Some(&current)),
+            Err(EditError::Conflict { .. })
+        ));
+        assert!(matches!(
+            revert(&sandbox, &history, "config.json", "not-a-revision", None),
+            Err(EditError::RevisionNotFound(_))
+        ));
+    }
+}
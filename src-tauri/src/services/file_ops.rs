@@ -0,0 +1,557 @@
+// Rename, move, copy, delete and trash for the file manager. Like
+// `file_transfer`, this works on a `Sandbox` and has no Tauri dependency.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::utils::sandbox::{Sandbox, SandboxError};
+
+// How long a deletion confirmation stays valid
+const CONFIRMATION_TTL: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Error)]
+pub enum FileOpError {
+    #[error(transparent)]
+    Sandbox(#[from] SandboxError),
+    #[error("Already exists: {0}")]
+    Exists(String),
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("Contains protected files: {0}")]
+    Protected(String),
+    #[error("Cannot copy or move a directory into itself: {0}")]
+    IntoItself(String),
+    #[error("Deleting {0} requires a confirmation token")]
+    ConfirmationRequired(String),
+    #[error("Invalid name: {0}")]
+    InvalidName(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Outcome of one item in a batch operation.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BatchResult {
+    pub path: String,
+    pub ok: bool,
+    pub error: Option<String>,
+    /// Where the item ended up, for move, copy and trash
+    pub result_path: Option<String>,
+}
+
+impl BatchResult {
+    pub fn from_result(path: &str, result: Result<Option<String>, FileOpError>) -> BatchResult {
+        match result {
+            Ok(result_path) => BatchResult { path: path.to_string(), ok: true, error: None, result_path },
+            Err(e) => BatchResult { path: path.to_string(), ok: false, error: Some(e.to_string()), result_path: None },
+        }
+    }
+}
+
+/// Returned by `request_deletion`; the token must be passed back to delete directories.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeletePlan {
+    pub token: String,
+    pub paths: Vec<String>,
+    pub file_count: u64,
+    pub total_size: u64,
+    pub expires_in_secs: u64,
+}
+
+struct PendingDeletion {
+    token: String,
+    paths: Vec<PathBuf>,
+    expires: Instant,
+}
+
+static PENDING_DELETIONS: Mutex<Vec<PendingDeletion>> = Mutex::new(Vec::new());
+
+/// Total size and file count of a file or directory tree. Symlinks are not followed.
+pub fn tree_size(path: &Path) -> (u64, u64) {
+    walkdir::WalkDir::new(path)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .fold((0, 0), |(files, bytes), e| {
+            (files + 1, bytes + e.metadata().map(|m| m.len()).unwrap_or(0))
+        })
+}
+
+// Resolves an existing entry that is about to be renamed, moved or deleted
+fn resolve_mutable(sandbox: &Sandbox, path: &str) -> Result<PathBuf, FileOpError> {
+    let resolved = sandbox.resolve_entry(path)?;
+    if fs::symlink_metadata(&resolved).is_err() {
+        return Err(FileOpError::NotFound(path.to_string()));
+    }
+    if sandbox.contains_protected(&resolved) {
+        return Err(FileOpError::Protected(path.to_string()));
+    }
+    Ok(resolved)
+}
+
+// Clears the way for `target`, honouring `overwrite`
+fn prepare_target(sandbox: &Sandbox, target: &Path, overwrite: bool) -> Result<(), FileOpError> {
+    if fs::symlink_metadata(target).is_err() {
+        return Ok(());
+    }
+    let relative = sandbox.relative(target);
+    if !overwrite {
+        return Err(FileOpError::Exists(relative));
+    }
+    if sandbox.contains_protected(target) {
+        return Err(FileOpError::Protected(relative));
+    }
+    remove_path(target)?;
+    Ok(())
+}
+
+// Client path of `name` inside the resolved directory `dir`
+fn child_path(sandbox: &Sandbox, dir: &Path, name: &str) -> String {
+    match sandbox.relative(dir) {
+        relative if relative.is_empty() => name.to_string(),
+        relative => format!("{}/{}", relative, name),
+    }
+}
+
+fn remove_path(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+pub fn rename(sandbox: &Sandbox, path: &str, new_name: &str) -> Result<PathBuf, FileOpError> {
+    if new_name.is_empty() || new_name == "." || new_name == ".." || new_name.contains(['/', '\\']) {
+        return Err(FileOpError::InvalidName(new_name.to_string()));
+    }
+
+    let source = resolve_mutable(sandbox, path)?;
+    let parent = source.parent().unwrap_or(&source);
+    let target = sandbox.resolve_writable(&child_path(sandbox, parent, new_name))?;
+    prepare_target(sandbox, &target, false)?;
+
+    fs::rename(&source, &target)?;
+    Ok(target)
+}
+
+/// Moves `path` into the directory `destination`.
+pub fn move_into(sandbox: &Sandbox, path: &str, destination: &str, overwrite: bool) -> Result<PathBuf, FileOpError> {
+    let source = resolve_mutable(sandbox, path)?;
+    let target_dir = sandbox.resolve(destination)?;
+    if target_dir.starts_with(&source) {
+        return Err(FileOpError::IntoItself(path.to_string()));
+    }
+
+    let name = source.file_name().ok_or_else(|| FileOpError::InvalidName(path.to_string()))?;
+    let target = sandbox.resolve_writable(&child_path(sandbox, &target_dir, &name.to_string_lossy()))?;
+    if target == source {
+        return Ok(target);
+    }
+    prepare_target(sandbox, &target, overwrite)?;
+
+    fs::create_dir_all(&target_dir)?;
+    move_path(&source, &target)?;
+    Ok(target)
+}
+
+// EXDEV and ERROR_NOT_SAME_DEVICE; `io::ErrorKind::CrossesDevices` is newer than our minimum Rust
+fn crosses_devices(error: &io::Error) -> bool {
+    #[cfg(unix)]
+    const CODE: i32 = 18;
+    #[cfg(windows)]
+    const CODE: i32 = 17;
+    error.raw_os_error() == Some(CODE)
+}
+
+// Renames, falling back to copy and delete when the rename crosses
+// filesystems. Any other failure is returned as is, since copying would
+// leave a duplicate behind once the delete fails the same way.
+fn move_path(source: &Path, target: &Path) -> io::Result<()> {
+    match fs::rename(source, target) {
+        Ok(()) => return Ok(()),
+        Err(e) if crosses_devices(&e) => {}
+        Err(e) => return Err(e),
+    }
+    if let Err(e) = copy_tree(source, target, &mut |_| {}) {
+        let _ = remove_path(target);
+        return Err(e);
+    }
+    remove_path(source)
+}
+
+// Copies a file or directory tree, calling `progress` with the bytes of each
+// file copied. Symlinks are recreated rather than followed.
+fn copy_tree(source: &Path, target: &Path, progress: &mut dyn FnMut(u64)) -> io::Result<()> {
+    for entry in walkdir::WalkDir::new(source).follow_links(false) {
+        let entry = entry.map_err(io::Error::other)?;
+        let relative = entry.path().strip_prefix(source).map_err(io::Error::other)?;
+        let destination = if relative.as_os_str().is_empty() {
+            target.to_path_buf()
+        } else {
+            target.join(relative)
+        };
+
+        let file_type = entry.file_type();
+        if file_type.is_dir() {
+            fs::create_dir_all(&destination)?;
+        } else if file_type.is_symlink() {
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(fs::read_link(entry.path())?, &destination)?;
+        } else {
+            // fs::copy also carries over permission bits
+            progress(fs::copy(entry.path(), &destination)?);
+        }
+    }
+    Ok(())
+}
+
+/// Copies `path` into the directory `destination`. `progress` receives the bytes copied so far.
+pub fn copy_into(
+    sandbox: &Sandbox,
+    path: &str,
+    destination: &str,
+    overwrite: bool,
+    progress: &mut dyn FnMut(u64),
+) -> Result<PathBuf, FileOpError> {
+    let source = sandbox.resolve(path)?;
+    if fs::symlink_metadata(&source).is_err() {
+        return Err(FileOpError::NotFound(path.to_string()));
+    }
+    let target_dir = sandbox.resolve(destination)?;
+    if target_dir.starts_with(&source) {
+        return Err(FileOpError::IntoItself(path.to_string()));
+    }
+
+    let name = source.file_name().ok_or_else(|| FileOpError::InvalidName(path.to_string()))?;
+    let target = sandbox.resolve_writable(&child_path(sandbox, &target_dir, &name.to_string_lossy()))?;
+    prepare_target(sandbox, &target, overwrite)?;
+
+    fs::create_dir_all(&target_dir)?;
+    let mut copied = 0;
+    copy_tree(&source, &target, &mut |bytes| {
+        copied += bytes;
+        progress(copied);
+    })?;
+    Ok(target)
+}
+
+/// Summarises what deleting `paths` would remove and issues a token that
+/// authorises deleting exactly those paths for a short time.
+pub fn request_deletion(sandbox: &Sandbox, paths: &[String]) -> Result<DeletePlan, FileOpError> {
+    let mut resolved = Vec::new();
+    let (mut file_count, mut total_size) = (0, 0);
+    for path in paths {
+        let target = resolve_mutable(sandbox, path)?;
+        let (files, bytes) = tree_size(&target);
+        file_count += files;
+        total_size += bytes;
+        resolved.push(target);
+    }
+
+    let token = Uuid::new_v4().to_string();
+    let mut pending = PENDING_DELETIONS.lock().unwrap();
+    pending.retain(|p| p.expires > Instant::now());
+    pending.push(PendingDeletion {
+        token: token.clone(),
+        paths: resolved,
+        expires: Instant::now() + CONFIRMATION_TTL,
+    });
+
+    Ok(DeletePlan {
+        token,
+        paths: paths.to_vec(),
+        file_count,
+        total_size,
+        expires_in_secs: CONFIRMATION_TTL.as_secs(),
+    })
+}
+
+fn is_confirmed(token: Option<&str>, target: &Path) -> bool {
+    let Some(token) = token else { return false };
+    PENDING_DELETIONS
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|p| p.token == token && p.expires > Instant::now() && p.paths.iter().any(|t| t == target))
+}
+
+/// Tokens are single use; call once the batch that used it has finished.
+pub fn consume_confirmation(token: &str) {
+    PENDING_DELETIONS.lock().unwrap().retain(|p| p.token != token);
+}
+
+/// Deletes `path`, moving it to `trash` unless it is `None`. Directories need
+/// a token from `request_deletion` that covers them.
+pub fn delete(
+    sandbox: &Sandbox,
+    trash: Option<&Trash>,
+    path: &str,
+    token: Option<&str>,
+) -> Result<Option<String>, FileOpError> {
+    let target = resolve_mutable(sandbox, path)?;
+    let is_dir = fs::symlink_metadata(&target)?.is_dir();
+    if is_dir && !is_confirmed(token, &target) {
+        return Err(FileOpError::ConfirmationRequired(path.to_string()));
+    }
+
+    match trash {
+        Some(trash) => Ok(Some(trash.put(sandbox, &target)?.id)),
+        None => {
+            remove_path(&target)?;
+            Ok(None)
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrashEntry {
+    pub id: String,
+    /// Relative to the server directory
+    pub original_path: String,
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub deleted_at: String,
+}
+
+/// Recoverable deletes. Each item is kept as `<id>/<name>` with its metadata in `<id>.json`.
+pub struct Trash {
+    root: PathBuf,
+}
+
+impl Trash {
+    pub fn open(root: PathBuf) -> io::Result<Trash> {
+        fs::create_dir_all(&root)?;
+        Ok(Trash { root })
+    }
+
+    fn entry_path(&self, id: &str) -> Result<PathBuf, FileOpError> {
+        Uuid::parse_str(id).map_err(|_| FileOpError::NotFound(id.to_string()))?;
+        Ok(self.root.join(id))
+    }
+
+    pub fn put(&self, sandbox: &Sandbox, target: &Path) -> Result<TrashEntry, FileOpError> {
+        let name = target
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .ok_or_else(|| FileOpError::InvalidName(target.display().to_string()))?;
+        let (_, size) = tree_size(target);
+        let entry = TrashEntry {
+            id: Uuid::new_v4().to_string(),
+            original_path: sandbox.relative(target),
+            name: name.clone(),
+            is_dir: fs::symlink_metadata(target)?.is_dir(),
+            size,
+            deleted_at: Utc::now().to_rfc3339(),
+        };
+
+        let dir = self.root.join(&entry.id);
+        fs::create_dir_all(&dir)?;
+        move_path(target, &dir.join(&name))?;
+        fs::write(
+            self.root.join(format!("{}.json", entry.id)),
+            serde_json::to_vec_pretty(&entry).map_err(io::Error::other)?,
+        )?;
+        Ok(entry)
+    }
+
+    pub fn list(&self) -> Result<Vec<TrashEntry>, FileOpError> {
+        let mut entries = Vec::new();
+        for file in fs::read_dir(&self.root)? {
+            let path = file?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            match fs::read(&path).ok().and_then(|b| serde_json::from_slice::<TrashEntry>(&b).ok()) {
+                Some(entry) => entries.push(entry),
+                None => eprintln!("[FILES] Skipping unreadable trash entry {}", path.display()),
+            }
+        }
+        entries.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
+        Ok(entries)
+    }
+
+    fn load(&self, id: &str) -> Result<TrashEntry, FileOpError> {
+        self.entry_path(id)?;
+        let bytes = fs::read(self.root.join(format!("{}.json", id)))
+            .map_err(|_| FileOpError::NotFound(id.to_string()))?;
+        serde_json::from_slice(&bytes).map_err(|e| FileOpError::Io(io::Error::other(e)))
+    }
+
+    /// Puts an item back where it was deleted from.
+    pub fn restore(&self, sandbox: &Sandbox, id: &str, overwrite: bool) -> Result<PathBuf, FileOpError> {
+        let entry = self.load(id)?;
+        let target = sandbox.resolve_writable(&entry.original_path)?;
+        prepare_target(sandbox, &target, overwrite)?;
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        move_path(&self.entry_path(id)?.join(&entry.name), &target)?;
+        self.remove(id)?;
+        Ok(target)
+    }
+
+    pub fn remove(&self, id: &str) -> Result<(), FileOpError> {
+        let dir = self.entry_path(id)?;
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        let _ = fs::remove_file(self.root.join(format!("{}.json", id)));
+        Ok(())
+    }
+
+    /// Permanently removes items deleted before `cutoff`; returns how many were purged.
+    pub fn purge_before(&self, cutoff: DateTime<Utc>) -> Result<usize, FileOpError> {
+        let mut purged = 0;
+        for entry in self.list()? {
+            let deleted_at = DateTime::parse_from_rfc3339(&entry.deleted_at).map(|t| t.with_timezone(&Utc));
+            if deleted_at.map_or(true, |t| t < cutoff) {
+                self.remove(&entry.id)?;
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (tempfile::TempDir, Sandbox, Trash) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("server");
+        fs::create_dir_all(root.join("world/region")).unwrap();
+        fs::create_dir_all(root.join("mods")).unwrap();
+        fs::create_dir_all(root.join("backups")).unwrap();
+        fs::write(root.join("world/region/r.0.0.dat"), "region").unwrap();
+        fs::write(root.join("world/level.dat"), "level").unwrap();
+        fs::write(root.join("notes.txt"), "notes").unwrap();
+        fs::write(root.join("config.json"), "{}").unwrap();
+        let sandbox = Sandbox::new(&root, vec!["config.json".to_string()]).unwrap();
+        let trash = Trash::open(dir.path().join("trash")).unwrap();
+        (dir, sandbox, trash)
+    }
+
+    fn exists(sandbox: &Sandbox, path: &str) -> bool {
+        sandbox.resolve(path).unwrap().exists()
+    }
+
+    #[test]
+    fn rename_checks_the_new_name() {
+        let (_dir, sandbox, _trash) = setup();
+        for name in ["", ".", "..", "a/b", "a\\b"] {
+            assert!(matches!(rename(&sandbox, "notes.txt", name), Err(FileOpError::InvalidName(_))), "{}", name);
+        }
+        assert!(matches!(rename(&sandbox, "notes.txt", "config.json"), Err(FileOpError::Sandbox(_))));
+        assert!(matches!(rename(&sandbox, "world", "mods"), Err(FileOpError::Exists(_))));
+        assert!(matches!(rename(&sandbox, "config.json", "settings.json"), Err(FileOpError::Protected(_))));
+
+        rename(&sandbox, "notes.txt", "readme.txt").unwrap();
+        assert!(exists(&sandbox, "readme.txt") && !exists(&sandbox, "notes.txt"));
+    }
+
+    #[test]
+    fn move_refuses_existing_targets_unless_overwriting() {
+        let (_dir, sandbox, _trash) = setup();
+        fs::write(sandbox.resolve("mods/notes.txt").unwrap(), "old").unwrap();
+        assert!(matches!(move_into(&sandbox, "notes.txt", "mods", false), Err(FileOpError::Exists(_))));
+        assert!(exists(&sandbox, "notes.txt"));
+
+        let moved = move_into(&sandbox, "notes.txt", "mods", true).unwrap();
+        assert_eq!(fs::read_to_string(moved).unwrap(), "notes");
+        assert!(!exists(&sandbox, "notes.txt"));
+    }
+
+    #[test]
+    fn directories_cannot_go_into_themselves() {
+        let (_dir, sandbox, _trash) = setup();
+        assert!(matches!(move_into(&sandbox, "world", "world/region", false), Err(FileOpError::IntoItself(_))));
+        let mut progress = |_| {};
+        assert!(matches!(copy_into(&sandbox, "world", "world", false, &mut progress), Err(FileOpError::IntoItself(_))));
+    }
+
+    #[test]
+    fn copy_reports_progress_and_keeps_the_source() {
+        let (_dir, sandbox, _trash) = setup();
+        let mut reported = Vec::new();
+        let copied = copy_into(&sandbox, "world", "backups", false, &mut |bytes| reported.push(bytes)).unwrap();
+        assert_eq!(fs::read_to_string(copied.join("region/r.0.0.dat")).unwrap(), "region");
+        assert_eq!(reported.last(), Some(&11));
+        assert!(exists(&sandbox, "world/level.dat"));
+    }
+
+    #[test]
+    fn failed_renames_are_not_turned_into_copies() {
+        let (dir, _sandbox, _trash) = setup();
+        let source = dir.path().join("server/notes.txt");
+        // A missing target directory fails the rename with something other than EXDEV
+        let error = move_path(&source, &dir.path().join("missing/notes.txt")).unwrap_err();
+        assert!(!crosses_devices(&error));
+        assert!(source.exists());
+        assert!(!dir.path().join("missing").exists());
+
+        #[cfg(unix)]
+        assert!(crosses_devices(&io::Error::from_raw_os_error(18)));
+    }
+
+    #[test]
+    fn directories_need_a_matching_confirmation() {
+        let (_dir, sandbox, _trash) = setup();
+        assert!(matches!(delete(&sandbox, None, "world", None), Err(FileOpError::ConfirmationRequired(_))));
+
+        let other = request_deletion(&sandbox, &["mods".to_string()]).unwrap();
+        assert!(matches!(delete(&sandbox, None, "world", Some(&other.token)), Err(FileOpError::ConfirmationRequired(_))));
+
+        let plan = request_deletion(&sandbox, &["world".to_string(), "notes.txt".to_string()]).unwrap();
+        assert_eq!((plan.file_count, plan.total_size), (3, 16));
+        delete(&sandbox, None, "world", Some(&plan.token)).unwrap();
+        assert!(!exists(&sandbox, "world"));
+
+        consume_confirmation(&plan.token);
+        fs::create_dir(sandbox.resolve("world").unwrap()).unwrap();
+        assert!(matches!(delete(&sandbox, None, "world", Some(&plan.token)), Err(FileOpError::ConfirmationRequired(_))));
+
+        // Single files need no token
+        delete(&sandbox, None, "notes.txt", None).unwrap();
+        assert!(matches!(delete(&sandbox, None, "config.json", None), Err(FileOpError::Protected(_))));
+    }
+
+    #[test]
+    fn trash_round_trip() {
+        let (_dir, sandbox, trash) = setup();
+        let id = delete(&sandbox, Some(&trash), "notes.txt", None).unwrap().unwrap();
+        assert!(!exists(&sandbox, "notes.txt"));
+
+        let entries = trash.list().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].original_path.as_str(), entries[0].size), ("notes.txt", 5));
+
+        fs::write(sandbox.resolve("notes.txt").unwrap(), "new").unwrap();
+        assert!(matches!(trash.restore(&sandbox, &id, false), Err(FileOpError::Exists(_))));
+        trash.restore(&sandbox, &id, true).unwrap();
+        assert_eq!(fs::read_to_string(sandbox.resolve("notes.txt").unwrap()).unwrap(), "notes");
+        assert!(trash.list().unwrap().is_empty());
+
+        assert!(matches!(trash.restore(&sandbox, "../escape", false), Err(FileOpError::NotFound(_))));
+    }
+
+    #[test]
+    fn purge_removes_only_older_items() {
+        let (_dir, sandbox, trash) = setup();
+        delete(&sandbox, Some(&trash), "notes.txt", None).unwrap();
+        assert_eq!(trash.purge_before(Utc::now() - chrono::Duration::hours(1)).unwrap(), 0);
+        assert_eq!(trash.purge_before(Utc::now() + chrono::Duration::seconds(1)).unwrap(), 1);
+        assert!(trash.list().unwrap().is_empty());
+        assert_eq!(fs::read_dir(&trash.root).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,394 @@
+// Chunked, resumable file transfers inside the server sandbox. Nothing here
+// depends on Tauri so the same calls back local commands and remote clients.
+//
+// Uploads are stateless on the server side: the upload id is derived from the
+// target path, size, hash and whether an existing file may be replaced, and
+// the partial data lives next to the target as a hidden `.part` file.
+// Re-running `begin_upload` after an interruption reports how much has
+// already arrived; partial files nobody has touched for a day are removed
+// the next time an upload begins in their directory.
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+use crate::services::backup_store::hash_file;
+use crate::utils::sandbox::{Sandbox, SandboxError};
+
+pub const CHUNK_SIZE: usize = 1024 * 1024;
+pub const MAX_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+const STALE_PARTIAL_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Error)]
+pub enum TransferError {
+    #[error(transparent)]
+    Sandbox(#[from] SandboxError),
+    #[error("Invalid upload id or checksum")]
+    InvalidUpload,
+    #[error("Chunk offset mismatch, expected {expected}")]
+    OffsetMismatch { expected: u64 },
+    #[error("Chunk exceeds the maximum size or the declared file size")]
+    TooLarge,
+    #[error("File already exists: {0}")]
+    Exists(String),
+    #[error("Upload incomplete: received {received} of {expected} bytes")]
+    Incomplete { received: u64, expected: u64 },
+    #[error("Checksum mismatch; the upload was discarded")]
+    ChecksumMismatch,
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UploadSession {
+    pub upload_id: String,
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+    /// Bytes already received; the next chunk must start here
+    pub offset: u64,
+    pub chunk_size: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DownloadInfo {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+    pub chunk_size: usize,
+}
+
+fn is_hex(value: &str, len: usize) -> bool {
+    value.len() == len && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn partial_path(target: &Path, upload_id: &str) -> Result<PathBuf, TransferError> {
+    if !is_hex(upload_id, 32) {
+        return Err(TransferError::InvalidUpload);
+    }
+    let name = target
+        .file_name()
+        .ok_or(TransferError::InvalidUpload)?
+        .to_string_lossy();
+    Ok(target.with_file_name(format!(".{}.{}.part", name, upload_id)))
+}
+
+fn partial_len(partial: &Path) -> u64 {
+    fs::metadata(partial).map(|m| m.len()).unwrap_or(0)
+}
+
+fn derive_upload_id(relative: &str, size: u64, sha256: &str, overwrite: bool) -> String {
+    let digest = Sha256::digest(format!("{}\n{}\n{}\n{}", relative, size, sha256, overwrite));
+    hex::encode(&digest[..16])
+}
+
+// `.<name>.<upload id>.part`
+fn is_partial_name(name: &str) -> bool {
+    name.strip_prefix('.')
+        .and_then(|n| n.strip_suffix(".part"))
+        .and_then(|n| n.rsplit_once('.'))
+        .is_some_and(|(_, id)| is_hex(id, 32))
+}
+
+// Removes partial uploads in `dir` that have not grown for `STALE_PARTIAL_AGE`
+fn expire_partials(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    let now = SystemTime::now();
+    for entry in entries.filter_map(|e| e.ok()) {
+        if !is_partial_name(&entry.file_name().to_string_lossy()) {
+            continue;
+        }
+        let stale = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age > STALE_PARTIAL_AGE);
+        if stale {
+            match fs::remove_file(entry.path()) {
+                Ok(()) => eprintln!("[FILES] Removed abandoned upload {}", entry.path().display()),
+                Err(e) => eprintln!("[FILES] Failed to remove abandoned upload {}: {}", entry.path().display(), e),
+            }
+        }
+    }
+}
+
+pub fn begin_upload(
+    sandbox: &Sandbox,
+    path: &str,
+    size: u64,
+    sha256: &str,
+    overwrite: bool,
+) -> Result<UploadSession, TransferError> {
+    let sha256 = sha256.to_lowercase();
+    if !is_hex(&sha256, 64) {
+        return Err(TransferError::InvalidUpload);
+    }
+
+    let target = sandbox.resolve_writable(path)?;
+    if target.is_dir() || (target.exists() && !overwrite) {
+        return Err(TransferError::Exists(path.to_string()));
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+        expire_partials(parent);
+    }
+
+    let relative = sandbox.relative(&target);
+    let upload_id = derive_upload_id(&relative, size, &sha256, overwrite);
+
+    let partial = partial_path(&target, &upload_id)?;
+    let mut offset = partial_len(&partial);
+    if offset > size {
+        fs::remove_file(&partial)?;
+        offset = 0;
+    }
+
+    Ok(UploadSession {
+        upload_id,
+        path: relative,
+        size,
+        sha256,
+        offset,
+        chunk_size: CHUNK_SIZE,
+    })
+}
+
+/// Appends `data` at `offset` and returns the new number of bytes received.
+pub fn write_chunk(
+    sandbox: &Sandbox,
+    path: &str,
+    upload_id: &str,
+    size: u64,
+    offset: u64,
+    data: &[u8],
+) -> Result<u64, TransferError> {
+    let target = sandbox.resolve_writable(path)?;
+    let partial = partial_path(&target, upload_id)?;
+
+    let received = partial_len(&partial);
+    if offset != received {
+        return Err(TransferError::OffsetMismatch { expected: received });
+    }
+    if data.len() > MAX_CHUNK_SIZE || offset + data.len() as u64 > size {
+        return Err(TransferError::TooLarge);
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(&partial)?;
+    file.write_all(data)?;
+    Ok(received + data.len() as u64)
+}
+
+/// Verifies the received data and moves it into place. An existing file is
+/// only replaced if the upload was begun with `overwrite`, including one that
+/// appeared while the upload was running.
+pub fn finish_upload(
+    sandbox: &Sandbox,
+    path: &str,
+    upload_id: &str,
+    size: u64,
+    sha256: &str,
+) -> Result<PathBuf, TransferError> {
+    let target = sandbox.resolve_writable(path)?;
+    let partial = partial_path(&target, upload_id)?;
+    let sha256 = sha256.to_lowercase();
+    let relative = sandbox.relative(&target);
+    let overwrite = if upload_id == derive_upload_id(&relative, size, &sha256, true) {
+        true
+    } else if upload_id == derive_upload_id(&relative, size, &sha256, false) {
+        false
+    } else {
+        return Err(TransferError::InvalidUpload);
+    };
+
+    let received = partial_len(&partial);
+    if received != size {
+        return Err(TransferError::Incomplete { received, expected: size });
+    }
+    if hash_file(&partial)? != sha256 {
+        fs::remove_file(&partial)?;
+        return Err(TransferError::ChecksumMismatch);
+    }
+
+    File::open(&partial)?.sync_all()?;
+    if target.is_dir() {
+        return Err(TransferError::Exists(relative));
+    }
+    if overwrite {
+        fs::rename(&partial, &target)?;
+    } else {
+        // Linking fails if the target exists, so nothing created since `begin_upload` is replaced
+        match fs::hard_link(&partial, &target) {
+            Ok(()) => fs::remove_file(&partial)?,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Err(TransferError::Exists(relative)),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(target)
+}
+
+pub fn abort_upload(sandbox: &Sandbox, path: &str, upload_id: &str) -> Result<(), TransferError> {
+    let target = sandbox.resolve_writable(path)?;
+    let partial = partial_path(&target, upload_id)?;
+    if partial.exists() {
+        fs::remove_file(&partial)?;
+    }
+    Ok(())
+}
+
+pub fn begin_download(sandbox: &Sandbox, path: &str) -> Result<DownloadInfo, TransferError> {
+    let source = sandbox.resolve(path)?;
+    let size = fs::metadata(&source)?.len();
+    Ok(DownloadInfo {
+        path: sandbox.relative(&source),
+        size,
+        sha256: hash_file(&source)?,
+        chunk_size: CHUNK_SIZE,
+    })
+}
+
+/// Reads up to `length` bytes from `offset`; an empty result means end of file.
+pub fn read_chunk(sandbox: &Sandbox, path: &str, offset: u64, length: usize) -> Result<Vec<u8>, TransferError> {
+    let source = sandbox.resolve(path)?;
+    let mut file = File::open(&source)?;
+    file.seek(SeekFrom::Start(offset))?;
+
+    let mut data = Vec::with_capacity(length.min(MAX_CHUNK_SIZE));
+    file.take(length.min(MAX_CHUNK_SIZE) as u64).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (tempfile::TempDir, Sandbox) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("server");
+        fs::create_dir_all(&root).unwrap();
+        let sandbox = Sandbox::new(&root, Vec::new()).unwrap();
+        (dir, sandbox)
+    }
+
+    fn sha(data: &[u8]) -> String {
+        hex::encode(Sha256::digest(data))
+    }
+
+    fn upload(sandbox: &Sandbox, path: &str, data: &[u8], overwrite: bool) -> Result<PathBuf, TransferError> {
+        let session = begin_upload(sandbox, path, data.len() as u64, &sha(data), overwrite)?;
+        write_chunk(sandbox, path, &session.upload_id, session.size, 0, data)?;
+        finish_upload(sandbox, path, &session.upload_id, session.size, &session.sha256)
+    }
+
+    #[test]
+    fn interrupted_uploads_resume_at_the_received_offset() {
+        let (_dir, sandbox) = setup();
+        let data = b"0123456789";
+        let session = begin_upload(&sandbox, "mods/a.jar", 10, &sha(data), false).unwrap();
+        assert_eq!(session.offset, 0);
+        assert_eq!(write_chunk(&sandbox, "mods/a.jar", &session.upload_id, 10, 0, &data[..4]).unwrap(), 4);
+
+        let resumed = begin_upload(&sandbox, "mods/a.jar", 10, &sha(data), false).unwrap();
+        assert_eq!(resumed.upload_id, session.upload_id);
+        assert_eq!(resumed.offset, 4);
+        assert!(matches!(
+            write_chunk(&sandbox, "mods/a.jar", &session.upload_id, 10, 0, &data[..4]),
+            Err(TransferError::OffsetMismatch { expected: 4 })
+        ));
+        assert!(matches!(
+            write_chunk(&sandbox, "mods/a.jar", &session.upload_id, 10, 4, b"too many bytes"),
+            Err(TransferError::TooLarge)
+        ));
+        assert!(matches!(
+            finish_upload(&sandbox, "mods/a.jar", &session.upload_id, 10, &session.sha256),
+            Err(TransferError::Incomplete { received: 4, expected: 10 })
+        ));
+
+        write_chunk(&sandbox, "mods/a.jar", &session.upload_id, 10, 4, &data[4..]).unwrap();
+        let target = finish_upload(&sandbox, "mods/a.jar", &session.upload_id, 10, &session.sha256).unwrap();
+        assert_eq!(fs::read(target).unwrap(), data);
+        assert_eq!(fs::read_dir(sandbox.resolve("mods").unwrap()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn checksum_mismatches_discard_the_upload() {
+        let (_dir, sandbox) = setup();
+        let session = begin_upload(&sandbox, "a.txt", 5, &sha(b"hello"), false).unwrap();
+        write_chunk(&sandbox, "a.txt", &session.upload_id, 5, 0, b"jello").unwrap();
+        assert!(matches!(
+            finish_upload(&sandbox, "a.txt", &session.upload_id, 5, &session.sha256),
+            Err(TransferError::ChecksumMismatch)
+        ));
+        assert!(!sandbox.resolve("a.txt").unwrap().exists());
+        assert_eq!(begin_upload(&sandbox, "a.txt", 5, &sha(b"hello"), false).unwrap().offset, 0);
+    }
+
+    #[test]
+    fn abort_removes_the_partial_data() {
+        let (_dir, sandbox) = setup();
+        let session = begin_upload(&sandbox, "a.txt", 5, &sha(b"hello"), false).unwrap();
+        write_chunk(&sandbox, "a.txt", &session.upload_id, 5, 0, b"hel").unwrap();
+        abort_upload(&sandbox, "a.txt", &session.upload_id).unwrap();
+        assert_eq!(fs::read_dir(sandbox.resolve("").unwrap()).unwrap().count(), 0);
+        assert!(matches!(abort_upload(&sandbox, "a.txt", "../../x"), Err(TransferError::InvalidUpload)));
+    }
+
+    #[test]
+    fn files_created_during_an_upload_are_not_replaced() {
+        let (_dir, sandbox) = setup();
+        let session = begin_upload(&sandbox, "a.txt", 5, &sha(b"hello"), false).unwrap();
+        write_chunk(&sandbox, "a.txt", &session.upload_id, 5, 0, b"hello").unwrap();
+        fs::write(sandbox.resolve("a.txt").unwrap(), "theirs").unwrap();
+
+        assert!(matches!(
+            finish_upload(&sandbox, "a.txt", &session.upload_id, 5, &session.sha256),
+            Err(TransferError::Exists(_))
+        ));
+        assert_eq!(fs::read_to_string(sandbox.resolve("a.txt").unwrap()).unwrap(), "theirs");
+
+        // Asking for overwrite up front is a different upload, which may replace it
+        assert!(matches!(upload(&sandbox, "a.txt", b"hello", false), Err(TransferError::Exists(_))));
+        upload(&sandbox, "a.txt", b"hello", true).unwrap();
+        assert_eq!(fs::read_to_string(sandbox.resolve("a.txt").unwrap()).unwrap(), "hello");
+    }
+
+    #[test]
+    fn upload_ids_must_match_the_upload() {
+        let (_dir, sandbox) = setup();
+        let session = begin_upload(&sandbox, "a.txt", 5, &sha(b"hello"), false).unwrap();
+        write_chunk(&sandbox, "a.txt", &session.upload_id, 5, 0, b"hello").unwrap();
+        assert!(matches!(
+            finish_upload(&sandbox, "b.txt", &session.upload_id, 5, &session.sha256),
+            Err(TransferError::InvalidUpload)
+        ));
+        assert!(matches!(
+            finish_upload(&sandbox, "a.txt", &session.upload_id, 5, &sha(b"other")),
+            Err(TransferError::InvalidUpload)
+        ));
+    }
+
+    #[test]
+    fn abandoned_partials_expire_when_an_upload_begins() {
+        let (_dir, sandbox) = setup();
+        let old = begin_upload(&sandbox, "old.txt", 5, &sha(b"hello"), false).unwrap();
+        write_chunk(&sandbox, "old.txt", &old.upload_id, 5, 0, b"he").unwrap();
+        let recent = begin_upload(&sandbox, "recent.txt", 5, &sha(b"hello"), false).unwrap();
+        write_chunk(&sandbox, "recent.txt", &recent.upload_id, 5, 0, b"he").unwrap();
+        let stale = partial_path(&sandbox.resolve("old.txt").unwrap(), &old.upload_id).unwrap();
+        File::options()
+            .write(true)
+            .open(&stale)
+            .unwrap()
+            .set_modified(SystemTime::now() - STALE_PARTIAL_AGE - Duration::from_secs(60))
+            .unwrap();
+        fs::write(sandbox.resolve(".notes.part").unwrap(), "not an upload").unwrap();
+
+        begin_upload(&sandbox, "new.txt", 5, &sha(b"hello"), false).unwrap();
+        assert!(!stale.exists());
+        assert_eq!(begin_upload(&sandbox, "recent.txt", 5, &sha(b"hello"), false).unwrap().offset, 2);
+        assert!(sandbox.resolve(".notes.part").unwrap().exists());
+    }
+}
@@ -0,0 +1,222 @@
+// Recursive watcher for the server directory. Raw filesystem events are
+// collected for a short quiet period and handed to a callback as one batch of
+// root-relative paths, so a server rewriting many files at once produces one
+// notification instead of hundreds. Like the other file services this has no
+// Tauri dependency; the caller decides where the batches go.
+use notify::event::{EventKind, ModifyKind};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::utils::glob::glob_match;
+
+// Flush once no event arrived for this long...
+const DEBOUNCE: Duration = Duration::from_millis(500);
+// ...or when a batch has been collecting for this long, for servers that never go quiet
+const MAX_DELAY: Duration = Duration::from_secs(3);
+
+/// Ignore rules used when `files_config` does not set any: logs, lock files,
+/// world chunk data and the partial files of uploads and archives in progress.
+pub const DEFAULT_IGNORE: &[&str] = &["logs", "*.log", "*.lck", "universe/worlds/*/chunks", ".*.part"];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Removed,
+    Renamed,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileChange {
+    /// Relative to the watched directory, with `/` separators
+    pub path: String,
+    pub kind: ChangeKind,
+}
+
+/// Globs matched against root-relative paths. A rule without `/` matches any
+/// single path component, so `logs` ignores every `logs` directory; a rule with
+/// `/` is matched from the root. A matching directory ignores everything below it.
+pub struct IgnoreRules {
+    rules: Vec<Vec<char>>,
+}
+
+impl IgnoreRules {
+    pub fn new(rules: &[String]) -> IgnoreRules {
+        IgnoreRules {
+            rules: rules
+                .iter()
+                .map(|r| r.trim_matches('/').replace('\\', "/"))
+                .filter(|r| !r.is_empty())
+                .map(|r| r.chars().collect())
+                .collect(),
+        }
+    }
+
+    pub fn is_ignored(&self, relative: &str) -> bool {
+        let components: Vec<&str> = relative.split('/').collect();
+        self.rules.iter().any(|rule| {
+            if rule.contains(&'/') {
+                (1..=components.len()).any(|n| {
+                    let prefix: Vec<char> = components[..n].join("/").chars().collect();
+                    glob_match(rule, &prefix)
+                })
+            } else {
+                components.iter().any(|c| glob_match(rule, &c.chars().collect::<Vec<_>>()))
+            }
+        })
+    }
+}
+
+/// Watches until dropped. Dropping the watcher closes the event channel, which
+/// flushes the pending batch and ends the debounce thread.
+pub struct FileWatcher {
+    _watcher: RecommendedWatcher,
+    root: PathBuf,
+}
+
+impl FileWatcher {
+    pub fn start(
+        root: &Path,
+        ignore: IgnoreRules,
+        on_change: impl Fn(Vec<FileChange>) + Send + 'static,
+    ) -> notify::Result<FileWatcher> {
+        let root = root.canonicalize()?;
+        let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        watcher.watch(&root, RecursiveMode::Recursive)?;
+
+        let thread_root = root.clone();
+        thread::spawn(move || {
+            let mut pending: HashMap<String, ChangeKind> = HashMap::new();
+            let mut first_event: Option<Instant> = None;
+
+            loop {
+                let result = rx.recv_timeout(DEBOUNCE);
+                let disconnected = matches!(result, Err(RecvTimeoutError::Disconnected));
+                let quiet = result.is_err();
+                match result {
+                    Ok(Ok(event)) => {
+                        let Some(kind) = change_kind(&event.kind) else { continue };
+                        for path in &event.paths {
+                            let Some(relative) = relative_path(&thread_root, path) else { continue };
+                            if ignore.is_ignored(&relative) {
+                                continue;
+                            }
+                            pending
+                                .entry(relative)
+                                .and_modify(|existing| *existing = merge(*existing, kind))
+                                .or_insert(kind);
+                        }
+                        first_event.get_or_insert_with(Instant::now);
+                    }
+                    Ok(Err(e)) => eprintln!("[FILES] Watch error: {}", e),
+                    Err(_) => {}
+                }
+
+                let overdue = first_event.is_some_and(|t| t.elapsed() >= MAX_DELAY);
+                if !pending.is_empty() && (quiet || overdue) {
+                    let mut changes: Vec<FileChange> = pending
+                        .drain()
+                        .map(|(path, kind)| FileChange { path, kind })
+                        .collect();
+                    changes.sort_by(|a, b| a.path.cmp(&b.path));
+                    on_change(changes);
+                    first_event = None;
+                }
+                if disconnected {
+                    break;
+                }
+            }
+        });
+
+        Ok(FileWatcher { _watcher: watcher, root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+fn change_kind(kind: &EventKind) -> Option<ChangeKind> {
+    match kind {
+        EventKind::Create(_) => Some(ChangeKind::Created),
+        EventKind::Modify(ModifyKind::Name(_)) => Some(ChangeKind::Renamed),
+        EventKind::Modify(_) | EventKind::Any => Some(ChangeKind::Modified),
+        EventKind::Remove(_) => Some(ChangeKind::Removed),
+        EventKind::Access(_) | EventKind::Other => None,
+    }
+}
+
+// A file created and then written within one batch is still new to the UI
+fn merge(existing: ChangeKind, next: ChangeKind) -> ChangeKind {
+    match (existing, next) {
+        (ChangeKind::Created, ChangeKind::Modified) => ChangeKind::Created,
+        (_, next) => next,
+    }
+}
+
+fn relative_path(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let relative = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    (!relative.is_empty()).then_some(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(rules: &[&str]) -> IgnoreRules {
+        IgnoreRules::new(&rules.iter().map(|r| r.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn rules_without_a_slash_match_any_component() {
+        let ignore = rules(&["logs", "*.log"]);
+        assert!(ignore.is_ignored("logs"));
+        assert!(ignore.is_ignored("logs/2024-01-01.txt"));
+        assert!(ignore.is_ignored("mods/example/logs/debug.txt"));
+        assert!(ignore.is_ignored("mods/latest.log"));
+        assert!(!ignore.is_ignored("logsheet.txt"));
+        assert!(!ignore.is_ignored("config.json"));
+    }
+
+    #[test]
+    fn rules_with_a_slash_match_from_the_root() {
+        let ignore = rules(&["universe/worlds/*/chunks", "/backups/"]);
+        assert!(ignore.is_ignored("universe/worlds/default/chunks"));
+        assert!(ignore.is_ignored("universe/worlds/default/chunks/0.0.region"));
+        assert!(!ignore.is_ignored("universe/worlds/default/config.json"));
+        assert!(!ignore.is_ignored("old/universe/worlds/default/chunks"));
+        // Leading and trailing slashes are trimmed, so this is a component rule
+        assert!(ignore.is_ignored("mods/backups/a.zip"));
+    }
+
+    #[test]
+    fn default_rules_cover_partial_files() {
+        let ignore = rules(DEFAULT_IGNORE);
+        assert!(ignore.is_ignored("mods/.plugin.jar.0123456789abcdef0123456789abcdef.part"));
+        assert!(ignore.is_ignored("server.lck"));
+        assert!(!ignore.is_ignored("mods/plugin.jar"));
+        assert!(!rules(&["", "/"]).is_ignored("anything"));
+    }
+
+    #[test]
+    fn created_then_modified_stays_created() {
+        assert_eq!(merge(ChangeKind::Created, ChangeKind::Modified), ChangeKind::Created);
+        assert_eq!(merge(ChangeKind::Modified, ChangeKind::Removed), ChangeKind::Removed);
+        assert_eq!(merge(ChangeKind::Created, ChangeKind::Removed), ChangeKind::Removed);
+        assert_eq!(relative_path(Path::new("/srv"), Path::new("/srv/mods/a.jar")).as_deref(), Some("mods/a.jar"));
+        assert_eq!(relative_path(Path::new("/srv"), Path::new("/srv")), None);
+        assert_eq!(relative_path(Path::new("/srv"), Path::new("/other/a.jar")), None);
+    }
+}
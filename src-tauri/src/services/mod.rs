@@ -1,2 +1,9 @@
 pub mod auth_service;
 pub mod server_service;
+pub mod backup_store;
+pub mod destinations;
+pub mod file_transfer;
+pub mod file_ops;
+pub mod file_edit;
+pub mod file_watcher;
+pub mod audit_log;
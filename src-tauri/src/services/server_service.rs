@@ -5,6 +5,7 @@ use std::io::{BufRead, BufReader, Write};
 use anyhow::{anyhow, Context};
 use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Emitter};
+use tokio::sync::{broadcast, watch};
 
 // Function to strip ANSI color codes from strings
 fn strip_ansi_codes(s: &str) -> String {
@@ -43,15 +44,25 @@ pub struct ServerService {
     db_path: String,
     process: Arc<Mutex<Option<Child>>>,
     logs: Arc<Mutex<Vec<String>>>,
+    log_tx: broadcast::Sender<String>,
+    status_tx: broadcast::Sender<ServerStatus>,
+    // Latest path passed to `set_server_path`, for services that follow the server directory
+    path_tx: watch::Sender<Option<String>>,
     app_handle: Option<AppHandle>,
 }
 
 impl ServerService {
     pub fn new(db_path: &str) -> Result<Self, String> {
+        let (log_tx, _) = broadcast::channel(1024);
+        let (status_tx, _) = broadcast::channel(16);
+        let (path_tx, _) = watch::channel(None);
         let service = ServerService {
             db_path: db_path.to_string(),
             process: Arc::new(Mutex::new(None)),
             logs: Arc::new(Mutex::new(Vec::new())),
+            log_tx,
+            status_tx,
+            path_tx,
             app_handle: None,
         };
         service.init_db().map_err(|e| e.to_string())?;
@@ -94,6 +105,7 @@ impl ServerService {
             "INSERT OR REPLACE INTO server_config (key, value) VALUES ('server_path', ?1)",
             [path],
         )?;
+        self.path_tx.send_replace(Some(path.to_string()));
         Ok(true)
     }
     
@@ -127,6 +139,7 @@ impl ServerService {
         // Capture stdout
         if let Some(stdout) = child.stdout.take() {
             let logs = self.logs.clone();
+            let log_tx = self.log_tx.clone();
             let app_handle = self.app_handle.clone();
             tokio::spawn(async move {
                 let reader = BufReader::new(stdout);
@@ -136,6 +149,7 @@ impl ServerService {
                         let clean_line = strip_ansi_codes(&line);
                         eprintln!("[SERVER STDOUT] {}", clean_line);
                         logs.lock().unwrap().push(clean_line.clone());
+                        let _ = log_tx.send(clean_line.clone());
                         if let Some(handle) = &app_handle {
                             let _ = handle.emit("server:logs-updated", vec![clean_line]);
                         }
@@ -147,6 +161,7 @@ impl ServerService {
         // Capture stderr
         if let Some(stderr) = child.stderr.take() {
             let logs = self.logs.clone();
+            let log_tx = self.log_tx.clone();
             let app_handle = self.app_handle.clone();
             tokio::spawn(async move {
                 let reader = BufReader::new(stderr);
@@ -157,6 +172,7 @@ impl ServerService {
                         let error_line = format!("[ERROR] {}", clean_line);
                         eprintln!("[SERVER STDERR] {}", clean_line);
                         logs.lock().unwrap().push(error_line.clone());
+                        let _ = log_tx.send(error_line.clone());
                         if let Some(handle) = &app_handle {
                             let _ = handle.emit("server:logs-updated", vec![error_line]);
                         }
@@ -171,6 +187,7 @@ impl ServerService {
         
         // Monitor process termination
         let process_handle = self.process.clone();
+        let status_tx = self.status_tx.clone();
         let app_handle_clone = self.app_handle.clone();
         tokio::spawn(async move {
             // Wait a bit before checking
@@ -188,6 +205,7 @@ impl ServerService {
                             *guard = None;
                             drop(guard);
                             
+                            let _ = status_tx.send(ServerStatus { running: false, pid: None });
                             if let Some(handle) = &app_handle_clone {
                                 let _ = handle.emit("server:status-changed", ServerStatus { 
                                     running: false, 
@@ -211,6 +229,7 @@ impl ServerService {
         });
         
         // Emit status change
+        let _ = self.status_tx.send(ServerStatus { running: true, pid: Some(pid) });
         if let Some(handle) = &self.app_handle {
             let _ = handle.emit("server:status-changed", ServerStatus { 
                 running: true, 
@@ -229,6 +248,7 @@ impl ServerService {
             child.wait().context("Failed to wait for server process")?;
             
             // Emit status change
+            let _ = self.status_tx.send(ServerStatus { running: false, pid: None });
             if let Some(handle) = &self.app_handle {
                 let _ = handle.emit("server:status-changed", ServerStatus { running: false, pid: None });
             }
@@ -260,6 +280,56 @@ impl ServerService {
         }
     }
     
+    pub fn is_running(&self) -> bool {
+        self.process.lock().unwrap().is_some()
+    }
+    
+    /// Receives every log line captured from the server from now on.
+    pub fn subscribe_logs(&self) -> broadcast::Receiver<String> {
+        self.log_tx.subscribe()
+    }
+    
+    /// Receives every status change from now on, as also emitted to the UI.
+    pub fn subscribe_status(&self) -> broadcast::Receiver<ServerStatus> {
+        self.status_tx.subscribe()
+    }
+    
+    /// Notified whenever `set_server_path` stores a new server directory.
+    pub fn subscribe_server_path(&self) -> watch::Receiver<Option<String>> {
+        self.path_tx.subscribe()
+    }
+    
+    /// Notifies path subscribers without changing the path, so they restart
+    /// with settings that changed since.
+    pub fn reload_server_path(&self) {
+        self.path_tx.send_modify(|_| {});
+    }
+    
+    /// Sends `command` and waits for a log line containing `pattern`.
+    /// Returns `Ok(false)` if no such line appears within `timeout`.
+    pub async fn send_command_and_wait(
+        &self,
+        command: &str,
+        pattern: &str,
+        timeout: std::time::Duration,
+    ) -> anyhow::Result<bool> {
+        // Subscribe first so a fast reply is not missed
+        let mut rx = self.subscribe_logs();
+        self.send_command(command).await?;
+        
+        let wait = async {
+            loop {
+                match rx.recv().await {
+                    Ok(line) if line.contains(pattern) => return true,
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => return false,
+                }
+            }
+        };
+        
+        Ok(tokio::time::timeout(timeout, wait).await.unwrap_or(false))
+    }
+    
     pub async fn get_logs(&self) -> anyhow::Result<Vec<String>> {
         Ok(self.logs.lock().unwrap().clone())
     }
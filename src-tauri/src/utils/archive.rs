@@ -0,0 +1,474 @@
+// Zip and tar.gz reading and writing shared by backups and the file manager.
+//
+// Extraction never writes outside the target directory: entry names with
+// `..` or absolute paths are rejected, link entries in tarballs are refused,
+// and every parent directory is checked after canonicalization so an existing
+// symlink inside the target cannot redirect writes elsewhere.
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("Unsafe path in archive: {0}")]
+    UnsafePath(String),
+    #[error("Unsupported archive entry: {0}")]
+    Unsupported(String),
+    #[error("Already exists: {0}")]
+    Exists(String),
+    #[error("Unsupported archive type; expected .zip, .tar.gz or .tgz")]
+    UnknownFormat,
+    #[error(transparent)]
+    Zip(#[from] zip::result::ZipError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    #[serde(rename = "zip")]
+    Zip,
+    #[serde(rename = "tar.gz")]
+    TarGz,
+}
+
+impl ArchiveFormat {
+    pub fn from_path(path: &Path) -> Option<ArchiveFormat> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else {
+            None
+        }
+    }
+
+    /// Strips this format's extension from a file name.
+    pub fn stem<'a>(&self, file_name: &'a str) -> &'a str {
+        let lower = file_name.to_lowercase();
+        let suffix_len = match self {
+            ArchiveFormat::Zip => ".zip".len(),
+            ArchiveFormat::TarGz if lower.ends_with(".tgz") => ".tgz".len(),
+            ArchiveFormat::TarGz => ".tar.gz".len(),
+        };
+        &file_name[..file_name.len().saturating_sub(suffix_len)]
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OnConflict {
+    /// Replace existing files
+    #[default]
+    Overwrite,
+    /// Keep existing files and leave the archive's copy out
+    Skip,
+    /// Stop at the first existing file
+    Fail,
+}
+
+pub struct ExtractOptions<'a> {
+    pub on_conflict: OnConflict,
+    /// Entry names to leave out, such as a backup's own manifest
+    pub skip_entries: &'a [&'a str],
+    /// Returns false for destinations that must not be written; those entries are skipped
+    pub allow: &'a dyn Fn(&Path) -> bool,
+}
+
+impl Default for ExtractOptions<'_> {
+    fn default() -> Self {
+        ExtractOptions {
+            on_conflict: OnConflict::Overwrite,
+            skip_entries: &[],
+            allow: &|_| true,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ExtractReport {
+    pub files: usize,
+    pub skipped: Vec<String>,
+}
+
+#[cfg(unix)]
+pub fn file_mode(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode())
+}
+
+#[cfg(not(unix))]
+pub fn file_mode(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+/// Applies the permission bits of an archived mode. Setuid, setgid and sticky
+/// bits are dropped, since archives may come from anyone with upload access.
+#[cfg(unix)]
+pub fn apply_mode(path: &Path, mode: Option<u32>) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    if let Some(mode) = mode {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o777))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn apply_mode(_path: &Path, _mode: Option<u32>) -> io::Result<()> {
+    Ok(())
+}
+
+// Turns an entry name into a path below `target`, or None for the archive root
+fn entry_destination(target: &Path, name: &Path) -> Result<Option<PathBuf>, ArchiveError> {
+    let mut destination = target.to_path_buf();
+    let mut depth = 0;
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => {
+                destination.push(part);
+                depth += 1;
+            }
+            Component::CurDir => {}
+            _ => return Err(ArchiveError::UnsafePath(name.display().to_string())),
+        }
+    }
+    Ok((depth > 0).then_some(destination))
+}
+
+// Creates `dir` and makes sure it did not end up outside `target` through a symlink
+fn create_confined_dir(target: &Path, dir: &Path) -> Result<(), ArchiveError> {
+    fs::create_dir_all(dir)?;
+    if !dir.canonicalize()?.starts_with(target.canonicalize()?) {
+        return Err(ArchiveError::UnsafePath(dir.display().to_string()));
+    }
+    Ok(())
+}
+
+enum Decision {
+    Write,
+    Skip,
+}
+
+fn check_destination(
+    target: &Path,
+    destination: &Path,
+    options: &ExtractOptions<'_>,
+) -> Result<Decision, ArchiveError> {
+    if !(options.allow)(destination) {
+        return Ok(Decision::Skip);
+    }
+    if let Some(parent) = destination.parent() {
+        create_confined_dir(target, parent)?;
+    }
+
+    let existing = match fs::symlink_metadata(destination) {
+        Ok(metadata) => metadata,
+        Err(_) => return Ok(Decision::Write),
+    };
+    if existing.is_dir() {
+        return Ok(Decision::Write);
+    }
+    match options.on_conflict {
+        OnConflict::Skip => Ok(Decision::Skip),
+        OnConflict::Fail => Err(ArchiveError::Exists(destination.display().to_string())),
+        OnConflict::Overwrite => {
+            // Never write through an existing symlink
+            if existing.file_type().is_symlink() {
+                fs::remove_file(destination)?;
+            }
+            Ok(Decision::Write)
+        }
+    }
+}
+
+/// Extracts a zip archive into `target`. `progress` receives uncompressed bytes done and total.
+pub fn extract_zip<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    target: &Path,
+    options: &ExtractOptions<'_>,
+    progress: &mut dyn FnMut(u64, u64),
+) -> Result<ExtractReport, ArchiveError> {
+    let mut total = 0;
+    for i in 0..archive.len() {
+        total += archive.by_index_raw(i)?.size();
+    }
+
+    let mut report = ExtractReport::default();
+    let mut done = 0;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if options.skip_entries.contains(&file.name()) {
+            continue;
+        }
+        let name = file
+            .enclosed_name()
+            .map(|p| p.to_path_buf())
+            .ok_or_else(|| ArchiveError::UnsafePath(file.name().to_string()))?;
+        let Some(outpath) = entry_destination(target, &name)? else { continue };
+
+        if file.is_dir() {
+            create_confined_dir(target, &outpath)?;
+        } else {
+            if let Decision::Skip = check_destination(target, &outpath, options)? {
+                report.skipped.push(name.to_string_lossy().to_string());
+                continue;
+            }
+            let mut outfile = File::create(&outpath)?;
+            done += io::copy(&mut file, &mut outfile)?;
+            report.files += 1;
+            progress(done, total);
+        }
+        apply_mode(&outpath, file.unix_mode())?;
+    }
+
+    Ok(report)
+}
+
+// Counts the compressed bytes read so tar progress can be reported
+struct CountingReader<R> {
+    inner: R,
+    count: std::rc::Rc<std::cell::Cell<u64>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count.set(self.count.get() + read as u64);
+        Ok(read)
+    }
+}
+
+/// Extracts a gzipped tarball into `target`. `progress` receives compressed bytes read and the file size.
+pub fn extract_tar_gz(
+    source: &Path,
+    target: &Path,
+    options: &ExtractOptions<'_>,
+    progress: &mut dyn FnMut(u64, u64),
+) -> Result<ExtractReport, ArchiveError> {
+    let file = File::open(source)?;
+    let total = file.metadata()?.len();
+    let count = std::rc::Rc::new(std::cell::Cell::new(0));
+    let reader = CountingReader { inner: file, count: count.clone() };
+    let mut archive = tar::Archive::new(GzDecoder::new(reader));
+
+    let mut report = ExtractReport::default();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.into_owned();
+        if options.skip_entries.iter().any(|s| Path::new(s) == name) {
+            continue;
+        }
+        let Some(outpath) = entry_destination(target, &name)? else { continue };
+
+        match entry.header().entry_type() {
+            tar::EntryType::Directory => create_confined_dir(target, &outpath)?,
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                if let Decision::Skip = check_destination(target, &outpath, options)? {
+                    report.skipped.push(name.to_string_lossy().to_string());
+                    continue;
+                }
+                let mut outfile = File::create(&outpath)?;
+                io::copy(&mut entry, &mut outfile)?;
+                report.files += 1;
+            }
+            other => {
+                return Err(ArchiveError::Unsupported(format!("{:?} entry {}", other, name.display())));
+            }
+        }
+        apply_mode(&outpath, entry.header().mode().ok())?;
+        progress(count.get(), total);
+    }
+
+    Ok(report)
+}
+
+/// Extracts either format, chosen by the file name of `source`.
+pub fn extract(
+    source: &Path,
+    target: &Path,
+    options: &ExtractOptions<'_>,
+    progress: &mut dyn FnMut(u64, u64),
+) -> Result<ExtractReport, ArchiveError> {
+    match ArchiveFormat::from_path(source).ok_or(ArchiveError::UnknownFormat)? {
+        ArchiveFormat::Zip => extract_zip(&mut ZipArchive::new(File::open(source)?)?, target, options, progress),
+        ArchiveFormat::TarGz => extract_tar_gz(source, target, options, progress),
+    }
+}
+
+pub fn append_tar_entry<W: Write, R: Read>(
+    builder: &mut tar::Builder<W>,
+    path: &str,
+    is_dir: bool,
+    size: u64,
+    mode: Option<u32>,
+    mtime: u64,
+    data: R,
+) -> io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    if is_dir {
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_size(0);
+    } else {
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_size(size);
+    }
+    header.set_mode(mode.map(|m| m & 0o7777).unwrap_or(if is_dir { 0o755 } else { 0o644 }));
+    header.set_mtime(mtime);
+    builder.append_data(&mut header, path, data)
+}
+
+pub fn tar_gz_builder(output: &Path) -> io::Result<tar::Builder<GzEncoder<File>>> {
+    Ok(tar::Builder::new(GzEncoder::new(File::create(output)?, Compression::default())))
+}
+
+pub fn finish_tar_gz(builder: tar::Builder<GzEncoder<File>>) -> io::Result<()> {
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// Zip entry options carrying the permission bits of `metadata`.
+pub fn zip_options(metadata: Option<&fs::Metadata>, is_dir: bool) -> FileOptions {
+    let mode = metadata.and_then(file_mode).map(|m| m & 0o7777);
+    FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .unix_permissions(mode.unwrap_or(if is_dir { 0o755 } else { 0o644 }))
+}
+
+/// Packs each source, file or directory, into a new archive under its own
+/// name. Paths in `exclude` are left out. Returns the number of files written.
+pub fn compress(
+    sources: &[PathBuf],
+    output: &Path,
+    format: ArchiveFormat,
+    exclude: &[PathBuf],
+    progress: &mut dyn FnMut(u64, u64),
+) -> Result<usize, ArchiveError> {
+    // Collect first so progress has a total
+    let mut entries = Vec::new();
+    for source in sources {
+        let base = source.parent().unwrap_or(source);
+        for entry in walkdir::WalkDir::new(source).follow_links(false) {
+            let entry = entry.map_err(io::Error::other)?;
+            if entry.path() == output || exclude.iter().any(|e| entry.path().starts_with(e)) {
+                continue;
+            }
+            let file_type = entry.file_type();
+            if !file_type.is_dir() && !file_type.is_file() {
+                // Symlinks and special files are not archived
+                continue;
+            }
+            let name = entry
+                .path()
+                .strip_prefix(base)
+                .map_err(io::Error::other)?
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            entries.push((entry.path().to_path_buf(), name, entry.metadata().map_err(io::Error::other)?));
+        }
+    }
+    let total: u64 = entries.iter().filter(|(_, _, m)| m.is_file()).map(|(_, _, m)| m.len()).sum();
+    let mut done = 0;
+    let mut files = 0;
+
+    match format {
+        ArchiveFormat::Zip => {
+            let mut zip = ZipWriter::new(File::create(output)?);
+            for (path, name, metadata) in &entries {
+                let options = zip_options(Some(metadata), metadata.is_dir());
+                if metadata.is_dir() {
+                    zip.add_directory(name.as_str(), options)?;
+                } else {
+                    zip.start_file(name.as_str(), options)?;
+                    done += io::copy(&mut File::open(path)?, &mut zip)?;
+                    files += 1;
+                    progress(done, total);
+                }
+            }
+            zip.finish()?;
+        }
+        ArchiveFormat::TarGz => {
+            let mut builder = tar_gz_builder(output)?;
+            for (path, name, metadata) in &entries {
+                let mtime = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map_or(0, |d| d.as_secs());
+                if metadata.is_dir() {
+                    append_tar_entry(&mut builder, name, true, 0, file_mode(metadata), mtime, io::empty())?;
+                } else {
+                    append_tar_entry(&mut builder, name, false, metadata.len(), file_mode(metadata), mtime, File::open(path)?)?;
+                    done += metadata.len();
+                    files += 1;
+                    progress(done, total);
+                }
+            }
+            finish_tar_gz(builder)?;
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_names_stay_inside_the_target() {
+        let target = Path::new("/srv/target");
+        assert_eq!(
+            entry_destination(target, Path::new("world/./region.dat")).unwrap(),
+            Some(target.join("world/region.dat"))
+        );
+        assert_eq!(entry_destination(target, Path::new("./")).unwrap(), None);
+        for name in ["../escape", "world/../../escape", "/etc/passwd"] {
+            assert!(entry_destination(target, Path::new(name)).is_err(), "{} was accepted", name);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn special_mode_bits_are_dropped() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("evil.tar.gz");
+        {
+            let mut builder = tar::Builder::new(GzEncoder::new(File::create(&archive).unwrap(), Compression::default()));
+            let mut header = tar::Header::new_gnu();
+            header.set_size(2);
+            header.set_mode(0o6755);
+            header.set_cksum();
+            builder.append_data(&mut header, "run.sh", &b"hi"[..]).unwrap();
+            builder.into_inner().unwrap().finish().unwrap();
+        }
+
+        let target = dir.path().join("out");
+        fs::create_dir_all(&target).unwrap();
+        extract_tar_gz(&archive, &target, &ExtractOptions::default(), &mut |_, _| {}).unwrap();
+
+        let mode = fs::metadata(target.join("run.sh")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o7777, 0o755);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn apply_mode_masks_to_permission_bits() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        fs::write(&file, "x").unwrap();
+        apply_mode(&file, Some(0o104777)).unwrap();
+        assert_eq!(fs::metadata(&file).unwrap().permissions().mode() & 0o7777, 0o777);
+    }
+}
@@ -0,0 +1,457 @@
+// Passphrase-based streaming encryption for backup archives.
+//
+// Layout: header, then a sequence of chunks. Each chunk is a flag byte
+// (1 on the final chunk), a big-endian u32 ciphertext length and the
+// AES-256-GCM ciphertext. The nonce is a random per-file prefix followed by
+// the chunk counter and the final flag, and the header is authenticated as
+// associated data, so chunks cannot be reordered, truncated or moved between
+// files.
+//
+// `seal` and `unseal` cover the other use: small secrets such as signing keys,
+// encrypted in one piece under a raw key instead of a passphrase.
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use thiserror::Error;
+
+const MAGIC: &[u8; 8] = b"HSPENC01";
+const SALT_LEN: usize = 16;
+const NONCE_PREFIX_LEN: usize = 7;
+const TAG_LEN: usize = 16;
+const HEADER_LEN: usize = MAGIC.len() + 12 + SALT_LEN + NONCE_PREFIX_LEN + 4 + 32;
+pub const CHUNK_SIZE: u32 = 1024 * 1024;
+
+#[derive(Debug, Error)]
+pub enum CryptoError {
+    #[error("Wrong passphrase")]
+    WrongPassphrase,
+    #[error("Encrypted data is corrupt or truncated")]
+    Corrupt,
+    #[error("Data is not encrypted")]
+    NotEncrypted,
+    #[error("Key derivation failed: {0}")]
+    Kdf(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Argon2id cost parameters. They are written into every encrypted file so
+/// changing the defaults never breaks older backups.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+// Headers come from files we did not necessarily write, so the cost they ask
+// for is capped before Argon2 allocates or loops on it
+const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+const MAX_ITERATIONS: u32 = 10;
+const MAX_PARALLELISM: u32 = 16;
+
+impl KdfParams {
+    fn check(&self) -> Result<(), CryptoError> {
+        if self.memory_kib > MAX_MEMORY_KIB || self.iterations > MAX_ITERATIONS || self.parallelism > MAX_PARALLELISM {
+            return Err(CryptoError::Kdf(format!(
+                "Parameters exceed the limit ({} KiB, {} iterations, {} lanes)",
+                MAX_MEMORY_KIB, MAX_ITERATIONS, MAX_PARALLELISM
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+struct Header {
+    params: KdfParams,
+    salt: [u8; SALT_LEN],
+    nonce_prefix: [u8; NONCE_PREFIX_LEN],
+    chunk_size: u32,
+    key_check: [u8; 32],
+}
+
+impl Header {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LEN);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&self.params.memory_kib.to_be_bytes());
+        out.extend_from_slice(&self.params.iterations.to_be_bytes());
+        out.extend_from_slice(&self.params.parallelism.to_be_bytes());
+        out.extend_from_slice(&self.salt);
+        out.extend_from_slice(&self.nonce_prefix);
+        out.extend_from_slice(&self.chunk_size.to_be_bytes());
+        out.extend_from_slice(&self.key_check);
+        out
+    }
+
+    fn from_bytes(bytes: &[u8; HEADER_LEN]) -> Result<Header, CryptoError> {
+        if &bytes[..MAGIC.len()] != MAGIC {
+            return Err(CryptoError::NotEncrypted);
+        }
+
+        let u32_at = |pos: usize| u32::from_be_bytes(bytes[pos..pos + 4].try_into().unwrap());
+        let mut pos = MAGIC.len();
+        let params = KdfParams {
+            memory_kib: u32_at(pos),
+            iterations: u32_at(pos + 4),
+            parallelism: u32_at(pos + 8),
+        };
+        params.check()?;
+        pos += 12;
+
+        let mut salt = [0u8; SALT_LEN];
+        salt.copy_from_slice(&bytes[pos..pos + SALT_LEN]);
+        pos += SALT_LEN;
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN];
+        nonce_prefix.copy_from_slice(&bytes[pos..pos + NONCE_PREFIX_LEN]);
+        pos += NONCE_PREFIX_LEN;
+        // Sizes the decryption buffers, so never trust anything but our own
+        let chunk_size = u32_at(pos);
+        if chunk_size != CHUNK_SIZE {
+            return Err(CryptoError::Corrupt);
+        }
+        pos += 4;
+        let mut key_check = [0u8; 32];
+        key_check.copy_from_slice(&bytes[pos..pos + 32]);
+
+        Ok(Header { params, salt, nonce_prefix, chunk_size, key_check })
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], params: KdfParams) -> Result<[u8; 32], CryptoError> {
+    params.check()?;
+    let argon_params = Params::new(params.memory_kib, params.iterations, params.parallelism, Some(32))
+        .map_err(|e| CryptoError::Kdf(e.to_string()))?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, argon_params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| CryptoError::Kdf(e.to_string()))?;
+    Ok(key)
+}
+
+// Lets a wrong passphrase be told apart from corrupted data
+fn key_check(key: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"hytale-server-portal backup key check");
+    hasher.update(key);
+    hasher.finalize().into()
+}
+
+fn chunk_nonce(prefix: &[u8; NONCE_PREFIX_LEN], counter: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..NONCE_PREFIX_LEN].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_LEN..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+// Fills `buf` as far as the reader allows, returning the number of bytes read
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+pub fn encrypt<R: Read, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    passphrase: &str,
+    params: KdfParams,
+) -> Result<(), CryptoError> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce_prefix = [0u8; NONCE_PREFIX_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce_prefix);
+
+    let key = derive_key(passphrase, &salt, params)?;
+    let header = Header {
+        params,
+        salt,
+        nonce_prefix,
+        chunk_size: CHUNK_SIZE,
+        key_check: key_check(&key),
+    };
+    let aad = header.to_bytes();
+    writer.write_all(&aad)?;
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let mut current = vec![0u8; CHUNK_SIZE as usize];
+    let mut next = vec![0u8; CHUNK_SIZE as usize];
+    let mut current_len = read_full(&mut reader, &mut current)?;
+    let mut counter: u32 = 0;
+
+    // Read one chunk ahead so the final chunk can be flagged
+    loop {
+        let next_len = if current_len == current.len() {
+            read_full(&mut reader, &mut next)?
+        } else {
+            0
+        };
+        let last = next_len == 0;
+
+        let nonce = chunk_nonce(&nonce_prefix, counter, last);
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: &current[..current_len], aad: &aad })
+            .map_err(|_| CryptoError::Corrupt)?;
+
+        writer.write_all(&[last as u8])?;
+        writer.write_all(&(ciphertext.len() as u32).to_be_bytes())?;
+        writer.write_all(&ciphertext)?;
+
+        if last {
+            break;
+        }
+        std::mem::swap(&mut current, &mut next);
+        current_len = next_len;
+        counter = counter.checked_add(1).ok_or(CryptoError::Corrupt)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn decrypt<R: Read, W: Write>(mut reader: R, mut writer: W, passphrase: &str) -> Result<(), CryptoError> {
+    let mut header_bytes = [0u8; HEADER_LEN];
+    if read_full(&mut reader, &mut header_bytes)? < HEADER_LEN {
+        return Err(CryptoError::NotEncrypted);
+    }
+    let header = Header::from_bytes(&header_bytes)?;
+
+    let key = derive_key(passphrase, &header.salt, header.params)?;
+    if key_check(&key) != header.key_check {
+        return Err(CryptoError::WrongPassphrase);
+    }
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let max_len = header.chunk_size as usize + TAG_LEN;
+    let mut counter: u32 = 0;
+
+    loop {
+        let mut frame = [0u8; 5];
+        if read_full(&mut reader, &mut frame)? < frame.len() {
+            // Stream ended before the final chunk
+            return Err(CryptoError::Corrupt);
+        }
+        let last = match frame[0] {
+            0 => false,
+            1 => true,
+            _ => return Err(CryptoError::Corrupt),
+        };
+        let len = u32::from_be_bytes(frame[1..].try_into().unwrap()) as usize;
+        if len > max_len {
+            return Err(CryptoError::Corrupt);
+        }
+
+        let mut ciphertext = vec![0u8; len];
+        if read_full(&mut reader, &mut ciphertext)? < len {
+            return Err(CryptoError::Corrupt);
+        }
+
+        let nonce = chunk_nonce(&header.nonce_prefix, counter, last);
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: &header_bytes })
+            .map_err(|_| CryptoError::Corrupt)?;
+        writer.write_all(&plaintext)?;
+
+        if last {
+            break;
+        }
+        counter = counter.checked_add(1).ok_or(CryptoError::Corrupt)?;
+    }
+
+    // Anything after the final chunk means the file was tampered with
+    if reader.read(&mut [0u8; 1])? != 0 {
+        return Err(CryptoError::Corrupt);
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Checks `passphrase` against the key check stored in an encrypted file header.
+pub fn check_passphrase(path: &Path, passphrase: &str) -> Result<(), CryptoError> {
+    let mut header_bytes = [0u8; HEADER_LEN];
+    if read_full(&mut File::open(path)?, &mut header_bytes)? < HEADER_LEN {
+        return Err(CryptoError::NotEncrypted);
+    }
+    let header = Header::from_bytes(&header_bytes)?;
+
+    let key = derive_key(passphrase, &header.salt, header.params)?;
+    if key_check(&key) != header.key_check {
+        return Err(CryptoError::WrongPassphrase);
+    }
+    Ok(())
+}
+
+pub fn encrypt_file(source: &Path, dest: &Path, passphrase: &str, params: KdfParams) -> Result<(), CryptoError> {
+    let reader = io::BufReader::new(File::open(source)?);
+    let writer = io::BufWriter::new(File::create(dest)?);
+    encrypt(reader, writer, passphrase, params)
+}
+
+pub fn decrypt_file(source: &Path, dest: &Path, passphrase: &str) -> Result<(), CryptoError> {
+    let reader = io::BufReader::new(File::open(source)?);
+    let writer = io::BufWriter::new(File::create(dest)?);
+    decrypt(reader, writer, passphrase)
+}
+
+/// Encrypts a small secret under a raw 256-bit key, as a random nonce followed by the ciphertext.
+pub fn seal(key: &[u8; 32], plaintext: &[u8]) -> Vec<u8> {
+    let mut nonce = [0u8; 12];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    // Encrypting an in-memory buffer only fails past the GCM length limit
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .expect("secret too large to seal");
+    [nonce.as_slice(), &ciphertext].concat()
+}
+
+/// Reverses `seal`; a wrong key and tampered data both come back as `Corrupt`.
+pub fn unseal(key: &[u8; 32], sealed: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    if sealed.len() < 12 + TAG_LEN {
+        return Err(CryptoError::Corrupt);
+    }
+    let (nonce, ciphertext) = sealed.split_at(12);
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| CryptoError::Corrupt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cheap enough to keep the tests fast
+    const PARAMS: KdfParams = KdfParams { memory_kib: 1024, iterations: 1, parallelism: 1 };
+    const CHUNK_FRAME: usize = 5 + CHUNK_SIZE as usize + TAG_LEN;
+
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn encrypted(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        encrypt(data, &mut out, "hunter2", PARAMS).unwrap();
+        out
+    }
+
+    fn decrypted(data: &[u8], passphrase: &str) -> Result<Vec<u8>, CryptoError> {
+        let mut out = Vec::new();
+        decrypt(data, &mut out, passphrase).map(|_| out)
+    }
+
+    #[test]
+    fn round_trip() {
+        for len in [0, 10, CHUNK_SIZE as usize, 2 * CHUNK_SIZE as usize + 123] {
+            let data = sample(len);
+            let sealed = encrypted(&data);
+            assert_eq!(decrypted(&sealed, "hunter2").unwrap(), data, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn wrong_passphrase_is_told_apart_from_corruption() {
+        let sealed = encrypted(&sample(100));
+        assert!(matches!(decrypted(&sealed, "hunter3"), Err(CryptoError::WrongPassphrase)));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("backup.zip.enc");
+        std::fs::write(&path, &sealed).unwrap();
+        assert!(check_passphrase(&path, "hunter2").is_ok());
+        assert!(matches!(check_passphrase(&path, "hunter3"), Err(CryptoError::WrongPassphrase)));
+    }
+
+    #[test]
+    fn plain_data_is_not_encrypted() {
+        assert!(matches!(decrypted(b"PK\x03\x04", "hunter2"), Err(CryptoError::NotEncrypted)));
+        assert!(matches!(decrypted(&[0u8; HEADER_LEN + 10], "hunter2"), Err(CryptoError::NotEncrypted)));
+    }
+
+    #[test]
+    fn truncated_streams_are_rejected() {
+        let sealed = encrypted(&sample(CHUNK_SIZE as usize + 100));
+        // Cut inside the last chunk, and cleanly after the first one
+        for len in [sealed.len() - 1, HEADER_LEN + CHUNK_FRAME, HEADER_LEN] {
+            assert!(matches!(decrypted(&sealed[..len], "hunter2"), Err(CryptoError::Corrupt)), "length {}", len);
+        }
+    }
+
+    #[test]
+    fn tampering_is_detected() {
+        let sealed = encrypted(&sample(CHUNK_SIZE as usize + 100));
+
+        let mut flipped = sealed.clone();
+        flipped[HEADER_LEN + 100] ^= 1;
+        assert!(matches!(decrypted(&flipped, "hunter2"), Err(CryptoError::Corrupt)));
+
+        // Marking the first chunk as final would silently drop the rest
+        let mut early_end = sealed[..HEADER_LEN + CHUNK_FRAME].to_vec();
+        early_end[HEADER_LEN] = 1;
+        assert!(matches!(decrypted(&early_end, "hunter2"), Err(CryptoError::Corrupt)));
+
+        let mut trailing = sealed.clone();
+        trailing.push(0);
+        assert!(matches!(decrypted(&trailing, "hunter2"), Err(CryptoError::Corrupt)));
+
+        // The header is authenticated too
+        let mut header = sealed.clone();
+        header[MAGIC.len() + 12] ^= 1;
+        assert!(decrypted(&header, "hunter2").is_err());
+    }
+
+    #[test]
+    fn oversized_chunks_are_refused_before_allocating() {
+        let mut sealed = encrypted(&sample(100));
+        let pos = MAGIC.len() + 12 + SALT_LEN + NONCE_PREFIX_LEN;
+        sealed[pos..pos + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(decrypted(&sealed, "hunter2"), Err(CryptoError::Corrupt)));
+    }
+
+    #[test]
+    fn excessive_kdf_costs_are_refused() {
+        let sealed = encrypted(&sample(100));
+        for (offset, value) in [(0, u32::MAX), (0, MAX_MEMORY_KIB + 1), (4, u32::MAX), (4, MAX_ITERATIONS + 1), (8, MAX_PARALLELISM + 1)] {
+            let mut crafted = sealed.clone();
+            let pos = MAGIC.len() + offset;
+            crafted[pos..pos + 4].copy_from_slice(&value.to_be_bytes());
+            assert!(matches!(decrypted(&crafted, "hunter2"), Err(CryptoError::Kdf(_))), "offset {} value {}", offset, value);
+        }
+
+        let greedy = KdfParams { memory_kib: MAX_MEMORY_KIB * 2, ..PARAMS };
+        assert!(matches!(encrypt(&b"data"[..], &mut Vec::new(), "hunter2", greedy), Err(CryptoError::Kdf(_))));
+    }
+
+    #[test]
+    fn seal_round_trip() {
+        let key = [7u8; 32];
+        let sealed = seal(&key, b"signing key");
+        assert_eq!(unseal(&key, &sealed).unwrap(), b"signing key");
+        assert!(matches!(unseal(&[8u8; 32], &sealed), Err(CryptoError::Corrupt)));
+        assert!(matches!(unseal(&key, &sealed[..20]), Err(CryptoError::Corrupt)));
+    }
+}
@@ -0,0 +1,63 @@
+// Glob matching for file search and watcher ignore rules
+
+/// Glob with `*` (any run of characters, including `/`) and `?` (one character)
+pub fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` swallow one more character
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        glob_match(&pattern.chars().collect::<Vec<_>>(), &text.chars().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn literals_and_wildcards() {
+        assert!(matches("server.jar", "server.jar"));
+        assert!(!matches("server.jar", "server.jar.bak"));
+        assert!(matches("*.log", "latest.log"));
+        assert!(matches("*.log", ".log"));
+        assert!(!matches("*.log", "latest.log.gz"));
+        assert!(matches("r.?.?.dat", "r.0.1.dat"));
+        assert!(!matches("r.?.dat", "r.10.dat"));
+        assert!(matches("", ""));
+        assert!(!matches("", "a"));
+        assert!(matches("*", ""));
+    }
+
+    #[test]
+    fn stars_cross_separators_and_backtrack() {
+        assert!(matches("world/*", "world/region/r.0.0.dat"));
+        assert!(matches("*a*b*c", "xxaxxbxxbxc"));
+        assert!(!matches("*a*b*c", "xxaxxbxxbx"));
+        assert!(matches("a**b", "ab"));
+        assert!(matches("*.tar.gz", "backup.tar.tar.gz"));
+    }
+}
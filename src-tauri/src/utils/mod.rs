@@ -1,2 +1,6 @@
 // Utility modules
-// TODO: Add encryption, storage, etc.
+// TODO: Add storage, etc.
+pub mod archive;
+pub mod crypto;
+pub mod glob;
+pub mod sandbox;
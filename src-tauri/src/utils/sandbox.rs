@@ -0,0 +1,260 @@
+// Path resolution for file commands. Every path a client sends is resolved
+// against the server directory; anything that would end up outside it, by
+// `..`, an absolute path elsewhere or a symlink, is rejected.
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SandboxError {
+    #[error("Path is outside the server directory: {0}")]
+    OutsideRoot(String),
+    #[error("Path is protected: {0}")]
+    Protected(String),
+    #[error("Server directory is not available: {0}")]
+    Root(io::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+pub struct Sandbox {
+    root: PathBuf,
+    // The root as configured, which may differ from `root` if it goes through a symlink
+    configured_root: PathBuf,
+    protected: Vec<String>,
+}
+
+impl Sandbox {
+    /// `protected` holds paths relative to `root`; a directory protects everything below it.
+    pub fn new(root: &Path, protected: Vec<String>) -> Result<Sandbox, SandboxError> {
+        let configured_root = root.to_path_buf();
+        let root = root.canonicalize().map_err(SandboxError::Root)?;
+        let protected = protected
+            .iter()
+            .map(|p| p.trim_matches('/').replace('\\', "/"))
+            .filter(|p| !p.is_empty())
+            .collect();
+        Ok(Sandbox { root, configured_root, protected })
+    }
+
+    /// Resolves a client path to an absolute path inside the root. Relative
+    /// paths are taken from the root; absolute ones must already point inside
+    /// it. The target does not have to exist yet.
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, SandboxError> {
+        let requested = Path::new(path);
+        let relative = if requested.is_absolute() {
+            requested
+                .strip_prefix(&self.root)
+                .or_else(|_| requested.strip_prefix(&self.configured_root))
+                .map_err(|_| SandboxError::OutsideRoot(path.to_string()))?
+        } else {
+            requested
+        };
+
+        let mut resolved = self.root.clone();
+        for component in relative.components() {
+            match component {
+                Component::Normal(part) => resolved.push(part),
+                Component::CurDir => {}
+                _ => return Err(SandboxError::OutsideRoot(path.to_string())),
+            }
+        }
+
+        // Canonicalize the deepest existing ancestor so symlinks are followed
+        // before the containment check; the rest does not exist and cannot be a link
+        let mut existing = resolved.as_path();
+        let mut missing = Vec::new();
+        while !existing.exists() && existing.symlink_metadata().is_err() {
+            match (existing.parent(), existing.file_name()) {
+                (Some(parent), Some(name)) => {
+                    missing.push(name.to_os_string());
+                    existing = parent;
+                }
+                _ => break,
+            }
+        }
+
+        let mut canonical = match existing.canonicalize() {
+            Ok(canonical) => canonical,
+            // A dangling symlink: refuse rather than guess where it points
+            Err(_) => return Err(SandboxError::OutsideRoot(path.to_string())),
+        };
+        if !canonical.starts_with(&self.root) {
+            return Err(SandboxError::OutsideRoot(path.to_string()));
+        }
+        for name in missing.into_iter().rev() {
+            canonical.push(name);
+        }
+
+        Ok(canonical)
+    }
+
+    /// Resolves the directory entry itself rather than what it points to: a
+    /// symlink at `path` is not followed, so renaming or deleting it affects
+    /// the link. The root itself is never an entry.
+    pub fn resolve_entry(&self, path: &str) -> Result<PathBuf, SandboxError> {
+        let requested = Path::new(path);
+        match (requested.parent(), requested.file_name()) {
+            (Some(parent), Some(name)) => {
+                let parent = self.resolve(&parent.to_string_lossy())?;
+                Ok(parent.join(name))
+            }
+            _ => Err(SandboxError::Protected(path.to_string())),
+        }
+    }
+
+    /// Like `resolve`, but also refuses protected paths. Use for anything that modifies the tree.
+    pub fn resolve_writable(&self, path: &str) -> Result<PathBuf, SandboxError> {
+        let resolved = self.resolve(path)?;
+        if resolved == self.root || self.is_protected(&resolved) {
+            return Err(SandboxError::Protected(path.to_string()));
+        }
+        Ok(resolved)
+    }
+
+    /// The root-relative form of a resolved path, with `/` separators.
+    pub fn relative(&self, resolved: &Path) -> String {
+        resolved
+            .strip_prefix(&self.root)
+            .unwrap_or(resolved)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// True if `resolved` is a protected path or lies inside one.
+    pub fn is_protected(&self, resolved: &Path) -> bool {
+        let relative = self.relative(resolved);
+        self.protected
+            .iter()
+            .any(|p| relative == *p || relative.starts_with(&format!("{}/", p)))
+    }
+
+    /// True if removing or replacing `resolved` would also affect a protected path below it.
+    pub fn contains_protected(&self, resolved: &Path) -> bool {
+        let prefix = format!("{}/", self.relative(resolved));
+        self.is_protected(resolved) || self.protected.iter().any(|p| p.starts_with(&prefix))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // A server root next to a directory it must not reach
+    fn setup() -> (tempfile::TempDir, Sandbox) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("server");
+        fs::create_dir_all(root.join("mods")).unwrap();
+        fs::create_dir_all(dir.path().join("outside")).unwrap();
+        fs::write(dir.path().join("outside/secret.txt"), "secret").unwrap();
+        fs::write(root.join("config.json"), "{}").unwrap();
+        let sandbox = Sandbox::new(&root, vec!["config.json".to_string(), "/mods/".to_string()]).unwrap();
+        (dir, sandbox)
+    }
+
+    fn outside(result: Result<PathBuf, SandboxError>) -> bool {
+        matches!(result, Err(SandboxError::OutsideRoot(_)))
+    }
+
+    #[test]
+    fn relative_paths_resolve_inside_the_root() {
+        let (_dir, sandbox) = setup();
+        assert_eq!(sandbox.resolve("mods").unwrap(), sandbox.root.join("mods"));
+        assert_eq!(sandbox.resolve("./mods/new/file.jar").unwrap(), sandbox.root.join("mods/new/file.jar"));
+        assert_eq!(sandbox.resolve("").unwrap(), sandbox.root);
+        assert_eq!(sandbox.relative(&sandbox.root.join("mods/a.jar")), "mods/a.jar");
+    }
+
+    #[test]
+    fn parent_components_are_rejected() {
+        let (_dir, sandbox) = setup();
+        for path in ["..", "../outside/secret.txt", "mods/../../outside", "mods/.."] {
+            assert!(outside(sandbox.resolve(path)), "{}", path);
+        }
+    }
+
+    #[test]
+    fn absolute_paths_must_point_inside_the_root() {
+        let (dir, sandbox) = setup();
+        let inside = sandbox.root.join("mods");
+        assert_eq!(sandbox.resolve(&inside.to_string_lossy()).unwrap(), inside);
+
+        let secret = dir.path().join("outside/secret.txt");
+        assert!(outside(sandbox.resolve(&secret.to_string_lossy())));
+        assert!(outside(sandbox.resolve("/etc/passwd")));
+        let sneaky = format!("{}/../outside", sandbox.root.display());
+        assert!(outside(sandbox.resolve(&sneaky)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_cannot_escape_the_root() {
+        let (dir, sandbox) = setup();
+        let link = sandbox.root.join("escape");
+        std::os::unix::fs::symlink(dir.path().join("outside"), &link).unwrap();
+
+        assert!(outside(sandbox.resolve("escape")));
+        assert!(outside(sandbox.resolve("escape/secret.txt")));
+        // Paths that do not exist yet are checked through their existing parent
+        assert!(outside(sandbox.resolve("escape/new/file.txt")));
+        assert!(outside(sandbox.resolve_writable("escape/new.txt")));
+
+        // The link itself can still be renamed or deleted
+        assert_eq!(sandbox.resolve_entry("escape").unwrap(), link);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_inside_the_root_are_followed() {
+        let (_dir, sandbox) = setup();
+        std::os::unix::fs::symlink(sandbox.root.join("mods"), sandbox.root.join("alias")).unwrap();
+        assert_eq!(sandbox.resolve("alias/a.jar").unwrap(), sandbox.root.join("mods/a.jar"));
+        // Protection applies to where the link leads
+        assert!(matches!(sandbox.resolve_writable("alias/a.jar"), Err(SandboxError::Protected(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dangling_symlinks_are_refused() {
+        let (dir, sandbox) = setup();
+        std::os::unix::fs::symlink(dir.path().join("missing"), sandbox.root.join("dangling")).unwrap();
+        assert!(outside(sandbox.resolve("dangling")));
+        assert!(outside(sandbox.resolve("dangling/file.txt")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_symlinked_root_accepts_its_configured_path() {
+        let (dir, _) = setup();
+        let configured = dir.path().join("server-link");
+        std::os::unix::fs::symlink(dir.path().join("server"), &configured).unwrap();
+        let sandbox = Sandbox::new(&configured, Vec::new()).unwrap();
+
+        let requested = configured.join("mods");
+        assert_eq!(sandbox.resolve(&requested.to_string_lossy()).unwrap(), sandbox.root.join("mods"));
+    }
+
+    #[test]
+    fn protected_paths_are_read_only() {
+        let (_dir, sandbox) = setup();
+        assert!(sandbox.resolve("config.json").is_ok());
+
+        for path in ["config.json", "mods", "mods/a.jar", "", "."] {
+            assert!(matches!(sandbox.resolve_writable(path), Err(SandboxError::Protected(_))), "{}", path);
+        }
+        assert!(sandbox.resolve_writable("config.json.bak").is_ok());
+        assert!(sandbox.resolve_writable("modsx/a.jar").is_ok());
+    }
+
+    #[test]
+    fn parents_of_protected_paths_are_flagged() {
+        let dir = tempfile::tempdir().unwrap();
+        let sandbox = Sandbox::new(dir.path(), vec!["data/keys".to_string()]).unwrap();
+        assert!(sandbox.contains_protected(&sandbox.root.join("data")));
+        assert!(sandbox.contains_protected(&sandbox.root.join("data/keys/a")));
+        assert!(!sandbox.contains_protected(&sandbox.root.join("database")));
+    }
+}